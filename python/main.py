@@ -1,18 +1,25 @@
 from dotenv import load_dotenv
 from langchain_xai import ChatXAI
-from langchain_core.messages import HumanMessage, SystemMessage
+from langchain_core.messages import AIMessage, BaseMessage, HumanMessage, SystemMessage
 import os
 
-def run(msg: str) -> str:
+DEFAULT_SYSTEM_PROMPT = "You are a helpful assistant at the table of a group playing the tabletop role playing game Burgen & Bullywugs."
+
+
+def run(
+    messages: list[tuple[str, str]],
+    system_prompt: str = DEFAULT_SYSTEM_PROMPT,
+    model: str = "grok-beta",
+    temperature: float = 0.7,
+) -> str:
     load_dotenv()
     assert "XAI_API_KEY" in os.environ
 
-    chat = ChatXAI(temperature=0, model="grok-beta", stop_sequences=None)
-    messages = [
-        SystemMessage("Translate the following from English into Italian"),
-        HumanMessage(msg),
-    ]
-    response = chat.invoke(messages)
+    chat = ChatXAI(temperature=temperature, model=model, stop_sequences=None)
+    prompt: list[BaseMessage] = [SystemMessage(system_prompt)]
+    for role, content in messages:
+        prompt.append(AIMessage(content) if role == "assistant" else HumanMessage(content))
+    response = chat.invoke(prompt)
     assert isinstance(response.content, str)
     return response.content
 
@@ -20,7 +27,10 @@ if __name__ == "__main__":
     import argparse
     parser = argparse.ArgumentParser()
     parser.add_argument("msg", type=str)
+    parser.add_argument("--system-prompt", type=str, default=DEFAULT_SYSTEM_PROMPT)
+    parser.add_argument("--model", type=str, default="grok-beta")
+    parser.add_argument("--temperature", type=float, default=0.7)
     args = parser.parse_args()
-    response = run(args.msg)
+    response = run([("user", args.msg)], args.system_prompt, args.model, args.temperature)
     print(response)
 
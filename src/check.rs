@@ -0,0 +1,672 @@
+use std::time::Duration;
+
+use poise::serenity_prelude as serenity;
+use poise::{CreateReply, ReplyHandle};
+use rand::Rng;
+
+use crate::config::DiceTheme;
+use crate::dice_image::{self, Die};
+use crate::error::Error;
+use crate::fate::{self, FateUse};
+use crate::get_ability_value::get_ability_value;
+use crate::i18n::{tr, translator, Tr};
+use crate::permissions::{self, Level};
+use crate::reply;
+use crate::sheets::assert_character_name;
+use crate::{Data, PoiseContext};
+
+/// Embed colors of successful, failed and untargeted checks
+const SUCCESS_COLOR: u32 = 0x2e_cc_71;
+const FAILURE_COLOR: u32 = 0xe7_4c_3c;
+const NEUTRAL_COLOR: u32 = 0x58_65_f2;
+
+/// How long the buttons under a check can be used. Slash command replies can only be edited
+/// for 15 minutes.
+const BUTTON_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+fn d4() -> rand::distributions::Uniform<u8> {
+    rand::distributions::Uniform::new_inclusive(1, 4)
+}
+
+/// Outcome of an ability check: the resolved abilities and the rolled dice
+#[derive(Debug, Clone)]
+pub struct CheckResult {
+    pub character_name: String,
+    /// Full ability names and values as found on the character sheet
+    pub abilities: Vec<(String, u8)>,
+    pub pos_roll: u8,
+    pub neg_roll: u8,
+    /// Number the total has to reach for the check to succeed
+    pub target: Option<i16>,
+    /// Situational modifier added by the buttons under the check
+    pub modifier: i16,
+    /// How a fate point spent on the check changed the negative d4
+    pub fate: Option<FateUse>,
+}
+
+impl CheckResult {
+    /// Rolls the positive and negative d4 for the given abilities
+    pub fn roll<R: Rng>(
+        character_name: &str,
+        abilities: Vec<(String, u8)>,
+        target: Option<i16>,
+        rng: &mut R,
+    ) -> CheckResult {
+        CheckResult {
+            character_name: character_name.to_owned(),
+            abilities,
+            pos_roll: rng.sample(d4()),
+            neg_roll: rng.sample(d4()),
+            target,
+            modifier: 0,
+            fate: None,
+        }
+    }
+
+    /// Applies a fate point spent on the check to the negative d4
+    pub fn use_fate<R: Rng>(&mut self, fate: FateUse, rng: &mut R) {
+        if fate == FateUse::Reroll {
+            self.reroll_negative(rng);
+        }
+        self.fate = Some(fate);
+    }
+
+    /// The negative d4 as it counts towards the total
+    fn negative(&self) -> i16 {
+        match self.fate {
+            Some(FateUse::Drop) => 0,
+            _ => self.neg_roll as i16,
+        }
+    }
+
+    /// Rolls the negative d4 again
+    pub fn reroll_negative<R: Rng>(&mut self, rng: &mut R) {
+        self.neg_roll = rng.sample(d4());
+    }
+
+    /// Sum of the abilities, dice and modifier. A single ability counts twice, a dropped
+    /// negative d4 not at all.
+    pub fn total(&self) -> i16 {
+        let abilities: i16 = match self.abilities.as_slice() {
+            [(_, value)] => 2 * *value as i16,
+            abilities => abilities.iter().map(|(_, value)| *value as i16).sum(),
+        };
+        abilities + self.pos_roll as i16 - self.negative() + self.modifier
+    }
+
+    /// By how much the total exceeds (positive) or misses (negative) the target, if any.
+    /// Reaching the target exactly is a success.
+    pub fn margin(&self) -> Option<i16> {
+        self.target.map(|target| self.total() - target)
+    }
+
+    pub fn is_success(&self) -> Option<bool> {
+        self.margin().map(|margin| margin >= 0)
+    }
+
+    /// The d4s as they count towards the total, for drawing them
+    pub fn dice(&self) -> Vec<Die> {
+        let d4 = |value: u8, negative| Die {
+            sides: 4,
+            value: value.into(),
+            negative,
+        };
+        let mut dice = vec![d4(self.pos_roll, false)];
+        if self.fate != Some(FateUse::Drop) {
+            dice.push(d4(self.neg_roll, true));
+        }
+        dice
+    }
+}
+
+impl CheckResult {
+    /// Describes the check in the reader's language, with Discord markdown
+    pub fn render(&self, tr: Tr) -> String {
+        let abilities = match self.abilities.as_slice() {
+            [(name, value)] => format!("**{}**(2x{})", name, value),
+            abilities => abilities
+                .iter()
+                .map(|(name, value)| format!("**{}**({})", name, value))
+                .collect::<Vec<_>>()
+                .join(" + "),
+        };
+        let id = match self.fate {
+            Some(FateUse::Drop) => "check-result-dropped",
+            _ => "check-result",
+        };
+        let mut text = tr!(
+            tr,
+            id,
+            character = self.character_name.as_str(),
+            abilities = abilities,
+            pos = self.pos_roll,
+            neg = self.neg_roll,
+            modifier = match self.modifier {
+                0 => String::new(),
+                modifier if modifier > 0 => format!(" + {}", modifier),
+                modifier => format!(" - {}", -modifier),
+            },
+            total = self.total()
+        );
+        if let (Some(target), Some(margin)) = (self.target, self.margin()) {
+            text.push(' ');
+            text.push_str(&if margin >= 0 {
+                tr!(tr, "check-success", target = target, margin = margin)
+            } else {
+                tr!(tr, "check-failure", target = target, margin = -margin)
+            });
+        }
+        if let Some(fate) = self.fate {
+            text.push(' ');
+            text.push_str(&fate_note(tr, fate));
+        }
+        text
+    }
+
+    /// Embed with one field per ability, the dice, the total and the outcome colored by success
+    pub fn embed(&self, tr: Tr) -> serenity::CreateEmbed {
+        let mut embed = serenity::CreateEmbed::new().title(tr!(
+            tr,
+            "check-embed-title",
+            character = self.character_name.as_str()
+        ));
+        match self.abilities.as_slice() {
+            [(name, value)] => embed = embed.field(name, format!("2 × {}", value), true),
+            abilities => {
+                for (name, value) in abilities {
+                    embed = embed.field(name, value.to_string(), true);
+                }
+            }
+        }
+        embed = embed.field(
+            tr.get("check-embed-dice"),
+            match self.fate {
+                Some(FateUse::Drop) => format!("+{} / ~~−{}~~", self.pos_roll, self.neg_roll),
+                _ => format!("+{} / −{}", self.pos_roll, self.neg_roll),
+            },
+            true,
+        );
+        if let Some(fate) = self.fate {
+            embed = embed.footer(serenity::CreateEmbedFooter::new(fate_note(tr, fate)));
+        }
+        if self.modifier != 0 {
+            embed = embed.field(
+                tr.get("check-embed-modifier"),
+                format!("{:+}", self.modifier),
+                true,
+            );
+        }
+        embed = embed.field(
+            tr.get("check-embed-total"),
+            format!("**{}**", self.total()),
+            true,
+        );
+        match (self.target, self.margin()) {
+            (Some(target), Some(margin)) => {
+                let (outcome, color) = if margin >= 0 {
+                    (
+                        tr!(tr, "check-embed-success", margin = margin),
+                        SUCCESS_COLOR,
+                    )
+                } else {
+                    (
+                        tr!(tr, "check-embed-failure", margin = -margin),
+                        FAILURE_COLOR,
+                    )
+                };
+                embed
+                    .field(tr.get("check-embed-target"), target.to_string(), true)
+                    .description(outcome)
+                    .color(color)
+            }
+            _ => embed.color(NEUTRAL_COLOR),
+        }
+    }
+}
+
+fn fate_note(tr: Tr, fate: FateUse) -> String {
+    tr.get(match fate {
+        FateUse::Drop => "check-fate-dropped",
+        FateUse::Reroll => "check-fate-rerolled",
+    })
+}
+
+/// Changes the buttons under a check make to its result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Adjustment {
+    RerollNegative,
+    Plus,
+    Minus,
+}
+
+impl Adjustment {
+    const ALL: [Adjustment; 3] = [
+        Adjustment::RerollNegative,
+        Adjustment::Plus,
+        Adjustment::Minus,
+    ];
+
+    /// Suffix of the button's custom ID
+    fn key(self) -> &'static str {
+        match self {
+            Adjustment::RerollNegative => "reroll",
+            Adjustment::Plus => "plus",
+            Adjustment::Minus => "minus",
+        }
+    }
+
+    fn button(self, tr: Tr, prefix: &str) -> serenity::CreateButton {
+        let button = serenity::CreateButton::new(format!("{}{}", prefix, self.key()))
+            .style(serenity::ButtonStyle::Secondary);
+        match self {
+            Adjustment::RerollNegative => button.label(tr.get("check-button-reroll")),
+            Adjustment::Plus => button.label("+1"),
+            Adjustment::Minus => button.label("−1"),
+        }
+    }
+}
+
+/// A check result posted in a channel
+pub struct PostedCheck<'a> {
+    handle: ReplyHandle<'a>,
+    /// Whether the guild wants plain text instead of embeds
+    plain: bool,
+    /// The result as plain text, which is also what sessions record
+    pub text: String,
+}
+
+/// Custom IDs of the buttons of a command's check start with this
+fn button_prefix(ctx: PoiseContext<'_>) -> String {
+    format!("check-{}-", ctx.id())
+}
+
+/// Action row with the buttons that are still available
+fn buttons(tr: Tr, prefix: &str, rerolled: bool) -> Vec<serenity::CreateActionRow> {
+    let buttons = Adjustment::ALL
+        .into_iter()
+        .filter(|adjustment| !(rerolled && *adjustment == Adjustment::RerollNegative))
+        .map(|adjustment| adjustment.button(tr, prefix))
+        .collect();
+    vec![serenity::CreateActionRow::Buttons(buttons)]
+}
+
+/// The embed of a check with the picture of its dice, if they are drawn
+fn check_embed(
+    tr: Tr,
+    result: &CheckResult,
+    theme: &DiceTheme,
+) -> (serenity::CreateEmbed, Option<serenity::CreateAttachment>) {
+    let embed = result.embed(tr);
+    match dice_image::attachment(&result.dice(), theme) {
+        Some(image) => (
+            embed.image(format!("attachment://{}", dice_image::FILE_NAME)),
+            Some(image),
+        ),
+        None => (embed, None),
+    }
+}
+
+fn check_reply(tr: Tr, result: &CheckResult, plain: bool, theme: &DiceTheme) -> CreateReply {
+    if plain {
+        return CreateReply::default().content(result.render(tr));
+    }
+    let (embed, image) = check_embed(tr, result, theme);
+    let reply = CreateReply::default().embed(embed);
+    match image {
+        Some(image) => reply.attachment(image),
+        None => reply,
+    }
+}
+
+fn check_update(
+    tr: Tr,
+    result: &CheckResult,
+    plain: bool,
+    theme: &DiceTheme,
+) -> serenity::CreateInteractionResponseMessage {
+    let message = serenity::CreateInteractionResponseMessage::new();
+    if plain {
+        return message.content(result.render(tr));
+    }
+    let (embed, image) = check_embed(tr, result, theme);
+    // Replaces the picture of the dice before the change
+    message.embed(embed).files(image)
+}
+
+/// Posts the result of a check as an embed, or as plain text if the guild prefers that,
+/// with buttons to adjust it
+pub async fn post_result<'a>(
+    ctx: PoiseContext<'a>,
+    result: &CheckResult,
+) -> Result<PostedCheck<'a>, Error> {
+    let tr = translator(ctx);
+    let plain = ctx
+        .data()
+        .sheet_db()
+        .plain_checks(ctx.guild_id().unwrap().get())?;
+    // A fate point already took care of the negative d4
+    let rerolled = result.fate.is_some();
+    let reply = check_reply(tr, result, plain, &ctx.data().config.dice_theme).components(buttons(
+        tr,
+        &button_prefix(ctx),
+        rerolled,
+    ));
+    let handle = ctx.send(reply).await?;
+    Ok(PostedCheck {
+        handle,
+        plain,
+        text: result.render(tr),
+    })
+}
+
+/// What pressing a button under a check did
+enum Pressed {
+    /// The result changed, as described for the session
+    Changed(String),
+    /// Nothing changed, for the reason shown to the presser
+    Refused(String),
+    /// Nothing changed, and there is nothing to tell
+    Ignored,
+}
+
+/// Applies the button with the given key, pressed by `user_id`, to the result.
+/// The author of the command and GMs may reroll, while only GMs may change the modifier.
+async fn press(
+    ctx: PoiseContext<'_>,
+    tr: Tr<'_>,
+    key: &str,
+    user_id: serenity::UserId,
+    result: &mut CheckResult,
+) -> Pressed {
+    let Some(adjustment) = Adjustment::ALL.into_iter().find(|a| a.key() == key) else {
+        return Pressed::Ignored;
+    };
+    let may_press = match adjustment {
+        Adjustment::RerollNegative if user_id == ctx.author().id => Ok(true),
+        _ => permissions::level_of(ctx, user_id)
+            .await
+            .map(|level| level >= Level::Gm),
+    };
+    match may_press {
+        Ok(true) => {}
+        Ok(false) => {
+            return Pressed::Refused(tr.get(match adjustment {
+                Adjustment::RerollNegative => "check-buttons-not-yours",
+                Adjustment::Plus | Adjustment::Minus => "check-buttons-gm-only",
+            }))
+        }
+        Err(err) => {
+            tracing::warn!("Failed determining who pressed a check button: {}", err);
+            return Pressed::Refused(err.user_message(tr));
+        }
+    }
+
+    Pressed::Changed(match adjustment {
+        // A double click, or a click on the button before it was removed
+        Adjustment::RerollNegative if result.fate.is_some() => {
+            return Pressed::Refused(tr.get("check-already-rerolled"))
+        }
+        Adjustment::RerollNegative => {
+            let spent = fate::spend_for_check(
+                &mut ctx.data().sheet_db(),
+                ctx.guild_id().unwrap().get(),
+                &result.character_name,
+            );
+            if let Err(err) = spent {
+                if !err.is_user_error() {
+                    tracing::warn!("Failed spending a fate point on a reroll: {}", err);
+                }
+                return Pressed::Refused(err.user_message(tr));
+            }
+            let old = result.neg_roll;
+            let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_entropy();
+            result.use_fate(FateUse::Reroll, &mut rng);
+            tr!(tr, "check-change-reroll", old = old, new = result.neg_roll)
+        }
+        Adjustment::Plus => {
+            result.modifier += 1;
+            tr!(tr, "check-change-modifier", modifier = "+1")
+        }
+        Adjustment::Minus => {
+            result.modifier -= 1;
+            tr!(tr, "check-change-modifier", modifier = "−1")
+        }
+    })
+}
+
+impl PostedCheck<'_> {
+    /// Applies the buttons pressed under the check until they time out, editing the message
+    /// and recording every change in the running session. Rerolling the negative die spends a
+    /// fate point of the character, so it is refused without one and not offered once a fate
+    /// point was spent on the check.
+    pub async fn adjust(self, ctx: PoiseContext<'_>, mut result: CheckResult) -> Result<(), Error> {
+        let tr = translator(ctx);
+        let prefix = button_prefix(ctx);
+        let theme = &ctx.data().config.dice_theme;
+        while let Some(interaction) = serenity::ComponentInteractionCollector::new(ctx)
+            .filter({
+                let prefix = prefix.clone();
+                move |interaction| interaction.data.custom_id.starts_with(&prefix)
+            })
+            .timeout(BUTTON_TIMEOUT)
+            .await
+        {
+            let key = &interaction.data.custom_id[prefix.len()..];
+            let pressed = press(ctx, tr, key, interaction.user.id, &mut result).await;
+            // Every press is answered, or Discord shows it as failed
+            let response = match &pressed {
+                Pressed::Changed(_) => serenity::CreateInteractionResponse::UpdateMessage(
+                    check_update(tr, &result, self.plain, theme).components(buttons(
+                        tr,
+                        &prefix,
+                        result.fate.is_some(),
+                    )),
+                ),
+                Pressed::Refused(reason) => serenity::CreateInteractionResponse::Message(
+                    serenity::CreateInteractionResponseMessage::new()
+                        .content(reason)
+                        .ephemeral(true),
+                ),
+                Pressed::Ignored => serenity::CreateInteractionResponse::Acknowledge,
+            };
+            if let Err(err) = interaction.create_response(ctx, response).await {
+                tracing::warn!("Failed answering a check button: {}", err);
+            }
+            let Pressed::Changed(change) = pressed else {
+                continue;
+            };
+
+            let recorded = ctx.data().sheet_db().record_session_roll(
+                ctx.guild_id().unwrap().get(),
+                ctx.channel_id().get(),
+                &result.character_name,
+                &format!("{} ({})", result.render(tr), change),
+            );
+            if let Err(err) = recorded {
+                tracing::warn!("Failed recording check change in session: {}", err);
+            }
+        }
+
+        // Without the buttons, the message shows that it can no longer be changed
+        let reply = check_reply(tr, &result, self.plain, theme).components(Vec::new());
+        if let Err(err) = self.handle.edit(ctx, reply).await {
+            tracing::warn!("Failed removing the buttons of a check: {}", err);
+        }
+        Ok(())
+    }
+}
+
+/// Show check results as plain text instead of embeds, e.g. for screen readers
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    check = "crate::permissions::gm"
+)]
+pub async fn plain_checks(
+    ctx: PoiseContext<'_>,
+    #[description = "Whether checks should be shown as plain text"] enabled: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .sheet_db()
+        .set_plain_checks(guild_id.get(), enabled)?;
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr.get(if enabled {
+            "plain-checks-enabled"
+        } else {
+            "plain-checks-disabled"
+        }),
+    )
+    .await
+}
+
+/// Looks up the full names and values of the given abilities on the character's sheet
+pub async fn resolve_abilities(
+    data: &Data,
+    character_name: &str,
+    abilities: &[&str],
+) -> Result<Vec<(String, u8)>, Error> {
+    let spreadsheet_id = &data.config.character_spreadsheet_id;
+    assert_character_name(&data.sheets, spreadsheet_id, character_name).await?;
+
+    let mut resolved = Vec::with_capacity(abilities.len());
+    for ability in abilities {
+        resolved.push(
+            get_ability_value(
+                &data.sheets,
+                &data.config.gviz,
+                spreadsheet_id,
+                character_name,
+                ability,
+            )
+            .await?,
+        );
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::get_ability_value::Error as AbilityError;
+    use crate::i18n::Language;
+    use crate::test_support::MockGoogle;
+
+    #[tokio::test]
+    async fn check_rolls_resolved_abilities() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+
+        let abilities = resolve_abilities(&data, "Gandalf", &["cha", "zau"])
+            .await
+            .unwrap();
+        assert_eq!(
+            abilities,
+            vec![("Charisma".to_owned(), 3), ("Zaubern".to_owned(), 5)]
+        );
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let result = CheckResult::roll("Gandalf", abilities, None, &mut rng);
+        assert!((1..=4).contains(&result.pos_roll));
+        assert!((1..=4).contains(&result.neg_roll));
+        assert_eq!(
+            result.total(),
+            8 + result.pos_roll as i16 - result.neg_roll as i16
+        );
+        let text = result.render(Tr::new(&data.translations, Language::English));
+        assert!(text.starts_with("**Gandalf** rolls **Charisma**(3) + **Zaubern**(5) + d4("));
+
+        let total = result.total();
+        let mut adjusted = result.clone();
+        adjusted.modifier = -2;
+        adjusted.reroll_negative(&mut rng);
+        assert_eq!(
+            adjusted.total(),
+            total - 2 + result.neg_roll as i16 - adjusted.neg_roll as i16
+        );
+        assert!(adjusted
+            .render(Tr::new(&data.translations, Language::English))
+            .ends_with(&format!(") - 2 = **{}**", adjusted.total())));
+    }
+
+    #[tokio::test]
+    async fn single_ability_counts_twice() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+
+        let abilities = resolve_abilities(&data, "Bilbo", &["schl"]).await.unwrap();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let result = CheckResult::roll("Bilbo", abilities, Some(10), &mut rng);
+        assert_eq!(
+            result.total(),
+            10 + result.pos_roll as i16 - result.neg_roll as i16
+        );
+        assert_eq!(result.margin(), Some(result.total() - 10));
+        let text = result.render(Tr::new(&data.translations, Language::English));
+        assert!(text.contains("**Schleichen**(2x5)"));
+        assert!(text.contains(" vs 10: **"));
+
+        let embed =
+            serde_json::to_value(result.embed(Tr::new(&data.translations, Language::English)))
+                .unwrap();
+        let color = if result.total() >= 10 {
+            SUCCESS_COLOR
+        } else {
+            FAILURE_COLOR
+        };
+        assert_eq!(embed["color"], color);
+        assert_eq!(embed["fields"][0]["name"], "Schleichen");
+        assert_eq!(embed["fields"][0]["value"], "2 × 5");
+    }
+
+    #[tokio::test]
+    async fn unknown_character_is_rejected() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+
+        let err = resolve_abilities(&data, "Sauron", &["cha"])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidCharacter(name) if name == "Sauron"));
+    }
+
+    #[tokio::test]
+    async fn ambiguous_and_missing_abilities_are_rejected() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+
+        let err = resolve_abilities(&data, "Gandalf", &["st"])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::Ability(ref err) if matches!(**err, AbilityError::AbilityUniquenessError(_, ref found) if found.len() == 3))
+        );
+
+        let err = resolve_abilities(&data, "Gandalf", &["xyz"])
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::Ability(ref err) if matches!(**err, AbilityError::NoAbilityError(_)))
+        );
+    }
+
+    #[tokio::test]
+    async fn transient_gviz_failures_are_retried() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+
+        mock.fail_next_gviz_requests(2);
+        let abilities = resolve_abilities(&data, "Bilbo", &["cha"]).await.unwrap();
+        assert_eq!(abilities, vec![("Charisma".to_owned(), 2)]);
+        assert_eq!(mock.gviz_requests(), 3);
+
+        // Out of attempts
+        mock.fail_next_gviz_requests(3);
+        assert!(resolve_abilities(&data, "Bilbo", &["cha"]).await.is_err());
+    }
+}
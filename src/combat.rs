@@ -0,0 +1,426 @@
+//! Hit points and conditions of characters and NPCs, keyed by the same names as the claims.
+//!
+//! Maximum hit points come from the configured ability on the character's sheet, or are set by
+//! the GM for NPCs without one. Conditions can last a number of rounds, which count down with
+//! the initiative order of the channel.
+
+use poise::serenity_prelude as serenity;
+
+use crate::check::resolve_abilities;
+use crate::db::{Condition, HitPoints, Initiative};
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message, Tr};
+use crate::permissions;
+use crate::reply;
+use crate::PoiseContext;
+
+/// Discord shows at most this many fields in an embed
+const MAX_EMBED_FIELDS: usize = 25;
+
+impl HitPoints {
+    /// Hit points after taking damage, not going below 0
+    pub fn damaged(self, amount: i64) -> HitPoints {
+        HitPoints {
+            current: (self.current - amount).max(0),
+            ..self
+        }
+    }
+
+    /// Hit points after healing, not going above the maximum
+    pub fn healed(self, amount: i64) -> HitPoints {
+        HitPoints {
+            current: (self.current + amount).min(self.max),
+            ..self
+        }
+    }
+}
+
+/// Stored hit points of a character, or full hit points as given on their sheet
+async fn hit_points(ctx: PoiseContext<'_>, character_name: &str) -> Result<HitPoints, Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let stored = ctx
+        .data()
+        .sheet_db()
+        .get_hit_points(guild_id, character_name)?;
+    if let Some(stored) = stored {
+        return Ok(stored);
+    }
+    let data = ctx.data();
+    let abilities =
+        resolve_abilities(data, character_name, &[data.config.hp_ability.as_str()]).await?;
+    let max = abilities.first().map_or(0, |(_, value)| *value as i64);
+    Ok(HitPoints { current: max, max })
+}
+
+fn describe_hit_points(tr: Tr, character_name: &str, hit_points: HitPoints) -> String {
+    let mut text = tr!(
+        tr,
+        "hp-status",
+        character = character_name,
+        current = hit_points.current,
+        max = hit_points.max
+    );
+    if hit_points.current == 0 {
+        text.push(' ');
+        text.push_str(&tr!(tr, "hp-down", character = character_name));
+    }
+    text
+}
+
+fn describe_condition(tr: Tr, condition: &Condition) -> String {
+    match condition.rounds_left {
+        Some(rounds) => tr!(
+            tr,
+            "condition-rounds",
+            condition = condition.name.as_str(),
+            rounds = rounds
+        ),
+        None => condition.name.clone(),
+    }
+}
+
+/// Counts down the conditions of the combatants when the initiative order starts a new round.
+/// Returns a note on the conditions that ran out, if any.
+pub fn new_round(ctx: PoiseContext<'_>, initiative: &Initiative) -> Result<Option<String>, Error> {
+    let names: Vec<String> = initiative
+        .combatants
+        .iter()
+        .map(|combatant| combatant.name.clone())
+        .collect();
+    let expired = ctx
+        .data()
+        .sheet_db()
+        .tick_conditions(ctx.guild_id().unwrap().get(), &names)?;
+    if expired.is_empty() {
+        return Ok(None);
+    }
+    let tr = translator(ctx);
+    let expired: Vec<String> = expired
+        .iter()
+        .map(|condition| format!("{} ({})", condition.name, condition.character_name))
+        .collect();
+    Ok(Some(tr!(
+        tr,
+        "condition-expired",
+        conditions = expired.join(", ")
+    )))
+}
+
+async fn change_hit_points(
+    ctx: PoiseContext<'_>,
+    character_name: &str,
+    change: impl FnOnce(HitPoints) -> HitPoints,
+) -> Result<(), Error> {
+    permissions::ensure_may_roll_for(ctx, character_name).await?;
+    let hit_points = change(hit_points(ctx, character_name).await?);
+    ctx.data().sheet_db().set_hit_points(
+        ctx.guild_id().unwrap().get(),
+        character_name,
+        hit_points,
+    )?;
+    let tr = translator(ctx);
+    reply::say(ctx, describe_hit_points(tr, character_name, hit_points)).await
+}
+
+/// Track the hit points of characters and NPCs
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("hp_damage", "hp_heal", "hp_set"),
+    subcommand_required
+)]
+pub async fn hp(_ctx: PoiseContext<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Deal damage to a character or NPC
+#[poise::command(prefix_command, slash_command, guild_only, rename = "damage")]
+async fn hp_damage(
+    ctx: PoiseContext<'_>,
+    #[description = "Character or NPC taking the damage"] character_name: String,
+    #[description = "Points of damage"] amount: u32,
+) -> Result<(), Error> {
+    change_hit_points(ctx, &character_name, |hit_points| {
+        hit_points.damaged(amount as i64)
+    })
+    .await
+}
+
+/// Heal a character or NPC, up to their maximum hit points
+#[poise::command(prefix_command, slash_command, guild_only, rename = "heal")]
+async fn hp_heal(
+    ctx: PoiseContext<'_>,
+    #[description = "Character or NPC being healed"] character_name: String,
+    #[description = "Points healed"] amount: u32,
+) -> Result<(), Error> {
+    change_hit_points(ctx, &character_name, |hit_points| {
+        hit_points.healed(amount as i64)
+    })
+    .await
+}
+
+/// Set the hit points of a character or NPC, e.g. for NPCs without a sheet
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "set",
+    check = "crate::permissions::gm"
+)]
+async fn hp_set(
+    ctx: PoiseContext<'_>,
+    #[description = "Character or NPC"] character_name: String,
+    #[description = "Maximum hit points"] max: u32,
+    #[description = "Current hit points, defaults to the maximum"] current: Option<u32>,
+) -> Result<(), Error> {
+    let hit_points = HitPoints {
+        current: current.unwrap_or(max).min(max) as i64,
+        max: max as i64,
+    };
+    ctx.data().sheet_db().set_hit_points(
+        ctx.guild_id().unwrap().get(),
+        &character_name,
+        hit_points,
+    )?;
+    let tr = translator(ctx);
+    reply::say(ctx, describe_hit_points(tr, &character_name, hit_points)).await
+}
+
+/// Track conditions such as "stunned" on characters and NPCs
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("condition_add", "condition_remove"),
+    subcommand_required
+)]
+pub async fn condition(_ctx: PoiseContext<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Put a condition on a character or NPC
+#[poise::command(prefix_command, slash_command, guild_only, rename = "add")]
+async fn condition_add(
+    ctx: PoiseContext<'_>,
+    #[description = "Character or NPC"] character_name: String,
+    #[description = "Condition, e.g. stunned"] condition: String,
+    #[description = "Rounds of the initiative order it lasts, leave empty until removed"]
+    rounds: Option<u32>,
+) -> Result<(), Error> {
+    permissions::ensure_may_roll_for(ctx, &character_name).await?;
+    if rounds == Some(0) {
+        return Err(Error::InvalidArgument(Message::new(
+            "condition-invalid-rounds",
+        )));
+    }
+    let condition = Condition {
+        character_name,
+        name: condition.trim().to_lowercase(),
+        rounds_left: rounds.map(i64::from),
+    };
+    ctx.data()
+        .sheet_db()
+        .add_condition(ctx.guild_id().unwrap().get(), &condition)?;
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr!(
+            tr,
+            "condition-added",
+            character = condition.character_name.as_str(),
+            condition = describe_condition(tr, &condition)
+        ),
+    )
+    .await
+}
+
+/// Remove a condition from a character or NPC
+#[poise::command(prefix_command, slash_command, guild_only, rename = "remove")]
+async fn condition_remove(
+    ctx: PoiseContext<'_>,
+    #[description = "Character or NPC"] character_name: String,
+    #[description = "Condition to remove"] condition: String,
+) -> Result<(), Error> {
+    permissions::ensure_may_roll_for(ctx, &character_name).await?;
+    let condition = condition.trim().to_lowercase();
+    let removed = ctx.data().sheet_db().remove_condition(
+        ctx.guild_id().unwrap().get(),
+        &character_name,
+        &condition,
+    )?;
+    if !removed {
+        return Err(Error::InvalidArgument(
+            Message::new("condition-unknown")
+                .arg("character", &character_name)
+                .arg("condition", &condition),
+        ));
+    }
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr!(
+            tr,
+            "condition-removed",
+            character = character_name,
+            condition = condition
+        ),
+    )
+    .await
+}
+
+/// Show the hit points and conditions of the party and the fight in this channel
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn status(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let (claimed, stored, conditions, initiative) = {
+        let mut sheet_db = ctx.data().sheet_db();
+        (
+            sheet_db.list_sheets(guild_id)?,
+            sheet_db.list_hit_points(guild_id)?,
+            sheet_db.list_conditions(guild_id)?,
+            sheet_db.get_initiative(guild_id, ctx.channel_id().get())?,
+        )
+    };
+
+    // The fight's order first, then everyone else with a claim or a state by name
+    let mut names: Vec<String> = initiative
+        .iter()
+        .flat_map(|initiative| &initiative.combatants)
+        .map(|combatant| combatant.name.clone())
+        .collect();
+    let mut others: Vec<String> = claimed
+        .into_iter()
+        .chain(stored.iter().map(|(name, _)| name.clone()))
+        .chain(conditions.iter().map(|c| c.character_name.clone()))
+        .filter(|name| !names.contains(name))
+        .collect();
+    others.sort();
+    others.dedup();
+    names.extend(others);
+
+    let tr = translator(ctx);
+    let mut embed = serenity::CreateEmbed::new().title(tr.get("status-title"));
+    if let Some(initiative) = &initiative {
+        embed = embed.description(tr!(tr, "init-round", round = initiative.round));
+    }
+    if names.is_empty() {
+        embed = embed.description(tr.get("status-empty"));
+    }
+    for name in names.iter().take(MAX_EMBED_FIELDS) {
+        let hit_points = match stored.iter().find(|(stored, _)| stored == name) {
+            Some((_, hit_points)) => Some(*hit_points),
+            None => hit_points(ctx, name).await.ok(),
+        };
+        let mut value = match hit_points {
+            Some(hit_points) => tr!(
+                tr,
+                "status-hp",
+                current = hit_points.current,
+                max = hit_points.max
+            ),
+            None => tr.get("status-hp-unknown"),
+        };
+        let active: Vec<String> = conditions
+            .iter()
+            .filter(|condition| &condition.character_name == name)
+            .map(|condition| describe_condition(tr, condition))
+            .collect();
+        if !active.is_empty() {
+            value.push('\n');
+            value.push_str(&active.join(", "));
+        }
+        embed = embed.field(name, value, true);
+    }
+    ctx.send(poise::CreateReply::default().embed(embed)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SheetDB;
+
+    const HIT_POINTS: HitPoints = HitPoints {
+        current: 5,
+        max: 12,
+    };
+
+    fn condition(name: &str, rounds_left: Option<i64>) -> Condition {
+        Condition {
+            character_name: "Gandalf".to_owned(),
+            name: name.to_owned(),
+            rounds_left,
+        }
+    }
+
+    #[test]
+    fn damage_stops_at_zero() {
+        assert_eq!(HIT_POINTS.damaged(3).current, 2);
+        assert_eq!(HIT_POINTS.damaged(7).current, 0);
+    }
+
+    #[test]
+    fn healing_stops_at_the_maximum() {
+        assert_eq!(HIT_POINTS.healed(3).current, 8);
+        assert_eq!(HIT_POINTS.healed(20).current, 12);
+    }
+
+    #[test]
+    fn condition_expires_after_rounds() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        sheet_db
+            .add_condition(1, &condition("burning", Some(2)))
+            .unwrap();
+
+        let names = vec!["Gandalf".to_owned()];
+        assert!(sheet_db.tick_conditions(1, &names).unwrap().is_empty());
+        assert_eq!(
+            sheet_db.list_conditions(1).unwrap(),
+            vec![condition("burning", Some(1))]
+        );
+        assert_eq!(
+            sheet_db.tick_conditions(1, &names).unwrap(),
+            vec![condition("burning", Some(0))]
+        );
+        assert!(sheet_db.list_conditions(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn conditions_without_rounds_last_until_removed() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        sheet_db
+            .add_condition(1, &condition("stunned", None))
+            .unwrap();
+
+        for _ in 0..3 {
+            assert!(sheet_db
+                .tick_conditions(1, &["Gandalf".to_owned()])
+                .unwrap()
+                .is_empty());
+        }
+        assert_eq!(
+            sheet_db.list_conditions(1).unwrap(),
+            vec![condition("stunned", None)]
+        );
+        assert!(sheet_db.remove_condition(1, "Gandalf", "stunned").unwrap());
+        assert!(!sheet_db.remove_condition(1, "Gandalf", "stunned").unwrap());
+    }
+
+    #[test]
+    fn only_the_given_characters_count_down() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        sheet_db
+            .add_condition(1, &condition("burning", Some(1)))
+            .unwrap();
+
+        assert!(sheet_db
+            .tick_conditions(1, &["Bilbo".to_owned()])
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            sheet_db.list_conditions(1).unwrap(),
+            vec![condition("burning", Some(1))]
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! The completion pipeline shared by all commands that ask the LLM backend for text
+
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::i18n::{tr, translator};
+use crate::llm::{ChatMessage, Completion, CompletionRequest, LlmBackend};
+use crate::persona;
+use crate::quota;
+use crate::reply;
+use crate::PoiseContext;
+
+/// Requests a completion on behalf of the author of the command, giving up after the configured
+/// timeout. Giving up drops the pending request, which cancels it.
+///
+/// Every completion goes through here, so the role allowlist, the daily quotas and the blocklist
+/// apply to all of them, and their usage counts against the author's and the guild's quotas.
+pub async fn complete(
+    ctx: PoiseContext<'_>,
+    request: CompletionRequest,
+) -> Result<Completion, Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+    let user_id = ctx.author().id.get();
+    quota::ensure_allowed(ctx).await?;
+    quota::ensure_within_quota(data, guild_id, user_id)?;
+    // Earlier turns of the history were checked when they were asked
+    if let Some(prompt) = request.messages.last() {
+        quota::ensure_not_blocked(&data.config.quota, &prompt.content)?;
+    }
+    // Without usage reported by the backend, estimate the tokens of prompt and answer
+    let prompt_tokens: usize = request
+        .messages
+        .iter()
+        .map(ChatMessage::estimated_tokens)
+        .sum();
+
+    let completion = complete_within(data.llm.as_ref(), request, data.config.llm.timeout).await?;
+    if let Some(tokens) = completion.tokens_used {
+        tracing::debug!("Completion used {} tokens", tokens);
+    }
+    let tokens = completion.tokens_used.map_or_else(
+        || prompt_tokens + ChatMessage::assistant(completion.text.as_str()).estimated_tokens(),
+        |tokens| tokens as usize,
+    );
+    data.sheet_db()
+        .record_usage(guild_id, user_id, tokens as u64)?;
+    quota::ensure_not_blocked(&data.config.quota, &completion.text)?;
+    Ok(completion)
+}
+
+/// Requests a completion from the backend, giving up after the timeout
+pub async fn complete_within(
+    llm: &dyn LlmBackend,
+    request: CompletionRequest,
+    timeout: Duration,
+) -> Result<Completion, Error> {
+    tokio::time::timeout(timeout, llm.complete(request))
+        .await
+        .map_err(|_| Error::LlmTimeout(timeout))?
+}
+
+/// Drops the oldest turns until the estimated tokens of the rest fit into the budget
+fn fit_history(mut turns: Vec<ChatMessage>, token_budget: usize) -> Vec<ChatMessage> {
+    let mut tokens: usize = turns.iter().map(ChatMessage::estimated_tokens).sum();
+    let mut skip = 0;
+    while tokens > token_budget && skip < turns.len() {
+        tokens -= turns[skip].estimated_tokens();
+        skip += 1;
+    }
+    turns.split_off(skip)
+}
+
+/// Run a chat completion, remembering the recent conversation of this channel
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn completion(
+    ctx: PoiseContext<'_>,
+    #[description = "Message you want a completion on"] message: String,
+    #[description = "Persona to answer as, instead of the server's default"] persona: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+    let channel_id = ctx.channel_id().get();
+    let settings = persona::resolve(data, guild_id, persona.as_deref())?;
+
+    let history =
+        data.sheet_db()
+            .get_conversation(guild_id, channel_id, data.config.llm.history_turns)?;
+    let mut messages = fit_history(history, data.config.llm.history_tokens);
+    let message = ChatMessage::user(message);
+    messages.push(message.clone());
+
+    // Answer right away, the LLM may take a while
+    let placeholder = ctx.say(translator(ctx).get("thinking")).await?;
+    let result = complete(ctx, CompletionRequest { settings, messages }).await;
+
+    match result {
+        Ok(completion) => {
+            data.sheet_db().append_conversation(
+                guild_id,
+                channel_id,
+                &[message, ChatMessage::assistant(completion.text.as_str())],
+                data.config.llm.history_turns,
+            )?;
+            reply::edit(ctx, placeholder, completion.text).await?;
+            Ok(())
+        }
+        Err(err) => {
+            // The error handler reports the error instead
+            reply::delete_placeholder(ctx, placeholder).await;
+            Err(err)
+        }
+    }
+}
+
+/// Forget the conversation of this channel, so completions start over without context
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn forget(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let forgotten = ctx
+        .data()
+        .sheet_db()
+        .forget_conversation(guild_id.get(), ctx.channel_id().get())?;
+    let tr = translator(ctx);
+    reply::say(ctx, tr!(tr, "forget-done", count = forgotten)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use poise::serenity_prelude::async_trait;
+
+    use super::*;
+    use crate::persona::CompletionSettings;
+
+    /// Backend that takes its time before echoing the prompt
+    struct SlowBackend(Duration);
+
+    #[async_trait]
+    impl LlmBackend for SlowBackend {
+        async fn complete(&self, request: CompletionRequest) -> Result<Completion, Error> {
+            tokio::time::sleep(self.0).await;
+            Ok(Completion {
+                text: request.messages[0].content.clone(),
+                tokens_used: None,
+            })
+        }
+    }
+
+    fn request(prompt: &str) -> CompletionRequest {
+        CompletionRequest {
+            settings: CompletionSettings {
+                system_prompt: "You are a test".to_owned(),
+                model: "test".to_owned(),
+                temperature: 0.0,
+            },
+            messages: vec![ChatMessage::user(prompt)],
+        }
+    }
+
+    #[tokio::test]
+    async fn completions_give_up_after_timeout() {
+        let backend = SlowBackend(Duration::from_millis(200));
+        let timeout = Duration::from_millis(20);
+        match complete_within(&backend, request("slow"), timeout).await {
+            Err(Error::LlmTimeout(after)) => assert_eq!(after, timeout),
+            other => panic!("expected a timeout, got {:?}", other.map(|c| c.text)),
+        }
+
+        let completion = complete_within(&backend, request("fast"), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(completion.text, "fast");
+    }
+
+    #[test]
+    fn history_drops_oldest_turns_over_budget() {
+        let turns = vec![
+            ChatMessage::user("a".repeat(400)),
+            ChatMessage::assistant("b".repeat(40)),
+            ChatMessage::user("c".repeat(40)),
+        ];
+        let fitted = fit_history(turns.clone(), 50);
+        assert_eq!(fitted, turns[1..]);
+        assert_eq!(fit_history(turns.clone(), 1000), turns);
+        assert!(fit_history(turns, 0).is_empty());
+    }
+}
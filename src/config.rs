@@ -0,0 +1,210 @@
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+use crate::llm::BackendKind;
+use crate::retry::RetryPolicy;
+
+#[derive(Debug)]
+pub struct Config {
+    pub google_application_credentials: String,
+    pub character_spreadsheet_id: String,
+    pub discord_bot_token: String,
+    /// Endpoint of the Sheets API, if not Google's
+    pub sheets_base_url: Option<String>,
+    pub gviz: GvizConfig,
+    pub llm: LlmConfig,
+    pub rules: RulesConfig,
+    /// Minimum time between two narrations in a guild
+    pub narration_cooldown: Duration,
+    /// Replies longer than this many characters are sent as a file instead of several messages
+    pub attachment_threshold: usize,
+    pub quota: QuotaConfig,
+    /// Sheet that NPCs are rolled from
+    pub npc_template_sheet: String,
+    /// Ability that initiative is rolled on
+    pub initiative_ability: String,
+    /// Ability holding the maximum hit points of a character
+    pub hp_ability: String,
+    pub dice_theme: DiceTheme,
+}
+
+/// Access to the gviz query endpoint used for ability lookups
+#[derive(Debug, Clone)]
+pub struct GvizConfig {
+    /// Endpoint serving `spreadsheets/d/<id>/gviz/tq`, with trailing slash
+    pub base_url: String,
+    pub retry: RetryPolicy,
+}
+
+#[derive(Clone)]
+pub struct LlmConfig {
+    pub backend: BackendKind,
+    /// Endpoint of the OpenAI compatible API, with trailing slash
+    pub base_url: String,
+    pub api_key: Option<String>,
+    /// Deadline for a single completion
+    pub timeout: Duration,
+    /// Number of previous messages of a channel's conversation sent along with a completion
+    pub history_turns: usize,
+    /// Estimated token budget for the previous messages of a conversation
+    pub history_tokens: usize,
+    /// Directory with the packages needed by the python backend
+    #[cfg_attr(not(feature = "python"), allow(dead_code))]
+    pub python_site_packages: String,
+    // Defaults for completions, used where a persona does not override them
+    pub model: String,
+    pub temperature: f64,
+    pub system_prompt: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RulesConfig {
+    /// Directory holding the Markdown and text rule documents
+    pub dir: PathBuf,
+    /// Number of passages quoted or given to the LLM per question
+    pub passages: usize,
+}
+
+/// Colors of the dice in rendered roll results, as `0xRRGGBB`
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "dice-images"), allow(dead_code))]
+pub struct DiceTheme {
+    /// Dice added to the total, like the positive d4 of a check
+    pub positive: u32,
+    /// Dice subtracted from the total, like the negative d4 of a check
+    pub negative: u32,
+}
+
+/// A color given as `#rrggbb` or `rrggbb`
+struct Rgb(u32);
+
+impl FromStr for Rgb {
+    type Err = ();
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let hex = s.trim().trim_start_matches('#');
+        if hex.len() != 6 {
+            return Err(());
+        }
+        u32::from_str_radix(hex, 16).map(Rgb).map_err(|_| ())
+    }
+}
+
+/// Daily limits for completions, 0 meaning unlimited
+#[derive(Debug, Clone)]
+pub struct QuotaConfig {
+    pub user_requests: u64,
+    pub user_tokens: u64,
+    pub guild_requests: u64,
+    pub guild_tokens: u64,
+    /// Lowercase terms that must not appear in prompts or responses
+    pub blocklist: Vec<String>,
+}
+
+// Keeps the API key out of the logs
+impl fmt::Debug for LlmConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LlmConfig")
+            .field("backend", &self.backend)
+            .field("base_url", &self.base_url)
+            .field("timeout", &self.timeout)
+            .field("model", &self.model)
+            .field("temperature", &self.temperature)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Config> {
+        let expect_var = |x: &str| {
+            dotenv::var(x).map_err(|_| Error::Config(format!("{} env var must be defined!", x)))
+        };
+        let default_retry = RetryPolicy::default();
+        Ok(Config {
+            google_application_credentials: expect_var("GOOGLE_APPLICATION_CREDENTIALS")?,
+            character_spreadsheet_id: expect_var("CHARACTER_SPREADSHEET_ID")?,
+            discord_bot_token: expect_var("DISCORD_BOT_TOKEN")?,
+            sheets_base_url: dotenv::var("SHEETS_BASE_URL").ok(),
+            gviz: GvizConfig {
+                base_url: dotenv::var("GVIZ_BASE_URL")
+                    .unwrap_or_else(|_| "https://docs.google.com/".to_owned()),
+                retry: RetryPolicy {
+                    max_attempts: parse_var("GVIZ_MAX_ATTEMPTS", default_retry.max_attempts)?,
+                    base_delay: Duration::from_millis(parse_var(
+                        "GVIZ_BASE_DELAY_MS",
+                        default_retry.base_delay.as_millis() as u64,
+                    )?),
+                    max_delay: Duration::from_millis(parse_var(
+                        "GVIZ_MAX_DELAY_MS",
+                        default_retry.max_delay.as_millis() as u64,
+                    )?),
+                    timeout: Duration::from_secs(parse_var(
+                        "GVIZ_TIMEOUT_SECS",
+                        default_retry.timeout.as_secs(),
+                    )?),
+                },
+            },
+            llm: LlmConfig {
+                backend: parse_var("LLM_BACKEND", BackendKind::OpenAi)?,
+                base_url: dotenv::var("LLM_BASE_URL")
+                    .unwrap_or_else(|_| "https://api.x.ai/v1/".to_owned()),
+                api_key: dotenv::var("LLM_API_KEY")
+                    .or_else(|_| dotenv::var("XAI_API_KEY"))
+                    .ok(),
+                timeout: Duration::from_secs(parse_var("LLM_TIMEOUT_SECS", 60)?),
+                history_turns: parse_var("LLM_HISTORY_TURNS", 10)?,
+                history_tokens: parse_var("LLM_HISTORY_TOKENS", 2000)?,
+                python_site_packages: dotenv::var("PYTHON_SITE_PACKAGES")
+                    .unwrap_or_else(|_| ".venv/lib/python3.12/site-packages".to_owned()),
+                model: dotenv::var("LLM_MODEL").unwrap_or_else(|_| "grok-beta".to_owned()),
+                temperature: parse_var("LLM_TEMPERATURE", 0.7)?,
+                system_prompt: dotenv::var("LLM_SYSTEM_PROMPT").unwrap_or_else(|_| {
+                    "You are a helpful assistant at the table of a group playing the tabletop role playing game Burgen & Bullywugs.".to_owned()
+                }),
+            },
+            rules: RulesConfig {
+                dir: PathBuf::from(dotenv::var("RULES_DIR").unwrap_or_else(|_| "rules".to_owned())),
+                passages: parse_var("RULES_PASSAGES", 3)?,
+            },
+            narration_cooldown: Duration::from_secs(parse_var("NARRATION_COOLDOWN_SECS", 30)?),
+            attachment_threshold: parse_var("REPLY_ATTACHMENT_CHARS", 8000)?,
+            npc_template_sheet: dotenv::var("NPC_TEMPLATE_SHEET")
+                .unwrap_or_else(|_| "NPC Template".to_owned()),
+            initiative_ability: dotenv::var("INITIATIVE_ABILITY")
+                .unwrap_or_else(|_| "Strategie".to_owned()),
+            hp_ability: dotenv::var("HP_ABILITY").unwrap_or_else(|_| "Lebenspunkte".to_owned()),
+            dice_theme: DiceTheme {
+                positive: parse_var("DICE_POSITIVE_COLOR", Rgb(0x2e_cc_71))?.0,
+                negative: parse_var("DICE_NEGATIVE_COLOR", Rgb(0xe7_4c_3c))?.0,
+            },
+            quota: QuotaConfig {
+                user_requests: parse_var("QUOTA_USER_REQUESTS", 50)?,
+                user_tokens: parse_var("QUOTA_USER_TOKENS", 50_000)?,
+                guild_requests: parse_var("QUOTA_GUILD_REQUESTS", 500)?,
+                guild_tokens: parse_var("QUOTA_GUILD_TOKENS", 500_000)?,
+                blocklist: dotenv::var("LLM_BLOCKLIST")
+                    .unwrap_or_default()
+                    .split(',')
+                    .map(|term| term.trim().to_lowercase())
+                    .filter(|term| !term.is_empty())
+                    .collect(),
+            },
+        })
+    }
+}
+
+/// Parses an optional env var, falling back to `default` if it is not defined
+fn parse_var<T: FromStr>(name: &str, default: T) -> Result<T> {
+    match dotenv::var(name) {
+        Ok(value) => value.parse().map_err(|_| {
+            Error::Parse(format!(
+                "{} env var has an invalid value '{}'!",
+                name, value
+            ))
+        }),
+        Err(_) => Ok(default),
+    }
+}
@@ -1,32 +1,96 @@
+mod combat;
+mod conversations;
+mod experience_marks;
+mod fate_points;
+mod guild_settings;
+mod initiative;
+mod migrations;
+mod personas;
+mod rules;
+mod sessions;
+mod usage;
+mod user_settings;
+
 use std::path::Path;
 
+pub use combat::{Condition, HitPoints};
+pub use experience_marks::ExperienceMark;
+pub use initiative::{Combatant, Initiative};
+pub use migrations::Migration;
+pub use personas::Persona;
+pub use rules::RulePassage;
+pub use sessions::{Session, SessionMessage, SessionRoll};
 pub use sqlite::{Error, Result};
+pub use usage::Usage;
+
+pub const DEFAULT_PATH: &str = "db.sqlite";
 
 pub struct SheetDB {
     connection: sqlite::ConnectionWithFullMutex,
 }
 
+/// An open transaction, rolled back when dropped before it was committed
+struct Transaction<'a> {
+    sheet_db: &'a mut SheetDB,
+    committed: bool,
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.committed {
+            if let Err(err) = self.sheet_db.connection.execute("ROLLBACK;") {
+                tracing::warn!("Failed rolling back a transaction: {}", err);
+            }
+        }
+    }
+}
+
 impl SheetDB {
     pub fn open() -> Result<SheetDB> {
-        SheetDB::open_with_path("db.sqlite")
+        SheetDB::open_with_path(DEFAULT_PATH)
     }
 
     pub fn open_with_path<T: AsRef<Path>>(path: T) -> Result<SheetDB> {
         let connection = sqlite::Connection::open_with_full_mutex(path)?;
-
-        connection.execute("CREATE TABLE IF NOT EXISTS users (guild_id UNSIGNED BIG INT, author_id UNSIGNED BIG INT, sheet TEXT);")?;
-        connection.execute(
-            "CREATE UNIQUE INDEX IF NOT EXISTS idx_users ON users(guild_id, author_id, sheet);",
-        )?;
-        // Every author can only appear once per sheet
-        connection
-            .execute("CREATE UNIQUE INDEX IF NOT EXISTS idx_unique_author_per_guild ON users(guild_id, author_id);")?;
-        // Every sheet can only appear once per guild
-        connection.execute("CREATE UNIQUE INDEX IF NOT EXISTS idx_unique_sheet_per_guild ON users(guild_id, sheet);")?;
+        for migration in migrations::run(&connection)? {
+            tracing::info!(
+                "Applied DB migration {}: {}",
+                migration.version,
+                migration.description
+            );
+        }
 
         Ok(SheetDB { connection })
     }
 
+    /// Lists the migrations that opening the DB at `path` would apply, without applying them
+    pub fn pending_migrations<T: AsRef<Path>>(path: T) -> Result<Vec<&'static Migration>> {
+        // A DB that does not exist yet needs every migration
+        if !path.as_ref().exists() {
+            return Ok(migrations::MIGRATIONS.iter().collect());
+        }
+        let connection =
+            sqlite::Connection::open_with_flags(path, sqlite::OpenFlags::new().set_read_only())?;
+        migrations::pending(&connection)
+    }
+
+    /// Runs `f` in a transaction, which is committed if it succeeds and rolled back otherwise,
+    /// including when `f` panics or committing fails
+    pub fn transaction<T, E: From<Error>>(
+        &mut self,
+        f: impl FnOnce(&mut SheetDB) -> std::result::Result<T, E>,
+    ) -> std::result::Result<T, E> {
+        self.connection.execute("BEGIN;")?;
+        let mut transaction = Transaction {
+            sheet_db: self,
+            committed: false,
+        };
+        let value = f(transaction.sheet_db)?;
+        transaction.sheet_db.connection.execute("COMMIT;")?;
+        transaction.committed = true;
+        Ok(value)
+    }
+
     pub fn get_sheet(&mut self, guild_id: u64, author_id: u64) -> Result<Option<String>> {
         let mut statement = self
             .connection
@@ -44,6 +108,27 @@ impl SheetDB {
         }
     }
 
+    /// Claims a sheet for the author, unless another author of the guild holds it.
+    /// Returns whether the sheet was claimed.
+    pub fn claim_sheet(&mut self, guild_id: u64, author_id: u64, sheet: &str) -> Result<bool> {
+        // A single statement, so two authors cannot claim the same sheet at once
+        let mut statement = self.connection.prepare(
+            "INSERT OR REPLACE INTO users (guild_id, author_id, sheet)
+             SELECT :guild_id, :author_id, :sheet
+             WHERE NOT EXISTS (SELECT 1 FROM users WHERE guild_id=:guild_id AND sheet=:sheet AND author_id!=:author_id)",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":author_id", (author_id as i64).into()),
+                (":sheet", sheet.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(self.connection.change_count() > 0)
+    }
+
+    /// Stores the author's claim, taking the sheet away from whoever held it before
     pub fn store_sheet(&mut self, guild_id: u64, author_id: u64, sheet: &str) -> Result<()> {
         let mut statement = self.connection.prepare("INSERT OR REPLACE INTO users (guild_id, author_id, sheet) VALUES (:guild_id, :author_id, :sheet);")?;
         statement.bind::<&[(&str, sqlite::Value)]>(
@@ -57,4 +142,82 @@ impl SheetDB {
 
         Ok(())
     }
+
+    /// Names of all claimed sheets of a guild
+    pub fn list_sheets(&mut self, guild_id: u64) -> Result<Vec<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT sheet FROM users WHERE guild_id=:guild_id ORDER BY sheet")?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        let mut sheets = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            sheets.push(statement.read::<String, _>("sheet")?);
+        }
+        Ok(sheets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sheets_claimed_by_others_are_not_taken_over() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        assert!(sheet_db.claim_sheet(1, 10, "Gandalf").unwrap());
+        // Claiming again, or another sheet, is fine
+        assert!(sheet_db.claim_sheet(1, 10, "Gandalf").unwrap());
+        assert!(sheet_db.claim_sheet(1, 11, "Bilbo").unwrap());
+
+        assert!(!sheet_db.claim_sheet(1, 11, "Gandalf").unwrap());
+        assert_eq!(
+            sheet_db.get_sheet(1, 10).unwrap().as_deref(),
+            Some("Gandalf")
+        );
+        assert_eq!(sheet_db.get_sheet(1, 11).unwrap().as_deref(), Some("Bilbo"));
+        // Other guilds have their own claims
+        assert!(sheet_db.claim_sheet(2, 11, "Gandalf").unwrap());
+
+        sheet_db.store_sheet(1, 11, "Gandalf").unwrap();
+        assert_eq!(sheet_db.get_sheet(1, 10).unwrap(), None);
+        assert_eq!(
+            sheet_db.get_sheet(1, 11).unwrap().as_deref(),
+            Some("Gandalf")
+        );
+    }
+
+    #[test]
+    fn transactions_roll_back_on_errors_and_panics() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        let failed = sheet_db.transaction(|sheet_db| {
+            sheet_db.store_sheet(1, 10, "Gandalf")?;
+            Err::<(), _>(Error {
+                code: None,
+                message: Some("failed".to_owned()),
+            })
+        });
+        assert!(failed.is_err());
+        assert_eq!(sheet_db.get_sheet(1, 10).unwrap(), None);
+
+        let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            sheet_db.transaction(|sheet_db| {
+                sheet_db.store_sheet(1, 10, "Gandalf")?;
+                panic!("interrupted");
+                #[allow(unreachable_code)]
+                Ok::<(), Error>(())
+            })
+        }));
+        assert!(panicked.is_err());
+        assert_eq!(sheet_db.get_sheet(1, 10).unwrap(), None);
+
+        // No transaction was left open
+        sheet_db
+            .transaction(|sheet_db| sheet_db.store_sheet(1, 10, "Gandalf"))
+            .unwrap();
+        assert_eq!(
+            sheet_db.get_sheet(1, 10).unwrap().as_deref(),
+            Some("Gandalf")
+        );
+    }
 }
@@ -0,0 +1,202 @@
+use super::{Result, SheetDB};
+
+/// Hit points of a character or NPC
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HitPoints {
+    pub current: i64,
+    pub max: i64,
+}
+
+/// A condition such as "stunned" on a character or NPC
+#[derive(Debug, Clone, PartialEq)]
+pub struct Condition {
+    pub character_name: String,
+    pub name: String,
+    /// Rounds of the initiative order until the condition ends, `None` if it lasts until removed
+    pub rounds_left: Option<i64>,
+}
+
+impl SheetDB {
+    pub fn get_hit_points(
+        &mut self,
+        guild_id: u64,
+        character_name: &str,
+    ) -> Result<Option<HitPoints>> {
+        let mut statement = self.connection.prepare(
+            "SELECT hp, max_hp FROM hit_points WHERE guild_id=:guild_id AND character_name=:character_name",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", character_name.into()),
+            ][..],
+        )?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Some(HitPoints {
+                current: statement.read::<i64, _>("hp")?,
+                max: statement.read::<i64, _>("max_hp")?,
+            })),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    pub fn set_hit_points(
+        &mut self,
+        guild_id: u64,
+        character_name: &str,
+        hit_points: HitPoints,
+    ) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO hit_points (guild_id, character_name, hp, max_hp) VALUES (:guild_id, :character_name, :hp, :max_hp) ON CONFLICT(guild_id, character_name) DO UPDATE SET hp=excluded.hp, max_hp=excluded.max_hp;")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", character_name.into()),
+                (":hp", hit_points.current.into()),
+                (":max_hp", hit_points.max.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Hit points of everyone in the guild that has taken damage or been healed, by name
+    pub fn list_hit_points(&mut self, guild_id: u64) -> Result<Vec<(String, HitPoints)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT character_name, hp, max_hp FROM hit_points WHERE guild_id=:guild_id ORDER BY character_name",
+        )?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        let mut hit_points = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            hit_points.push((
+                statement.read::<String, _>("character_name")?,
+                HitPoints {
+                    current: statement.read::<i64, _>("hp")?,
+                    max: statement.read::<i64, _>("max_hp")?,
+                },
+            ));
+        }
+        Ok(hit_points)
+    }
+
+    /// Adds a condition, replacing the duration if the character already has it
+    pub fn add_condition(&mut self, guild_id: u64, condition: &Condition) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO conditions (guild_id, character_name, name, rounds_left) VALUES (:guild_id, :character_name, :name, :rounds_left) ON CONFLICT(guild_id, character_name, name) DO UPDATE SET rounds_left=excluded.rounds_left;")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", condition.character_name.as_str().into()),
+                (":name", condition.name.as_str().into()),
+                (
+                    ":rounds_left",
+                    condition
+                        .rounds_left
+                        .map_or(sqlite::Value::Null, |rounds| rounds.into()),
+                ),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Returns whether the character had the condition
+    pub fn remove_condition(
+        &mut self,
+        guild_id: u64,
+        character_name: &str,
+        name: &str,
+    ) -> Result<bool> {
+        let mut statement = self.connection.prepare(
+            "DELETE FROM conditions WHERE guild_id=:guild_id AND character_name=:character_name AND name=:name",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", character_name.into()),
+                (":name", name.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(self.connection.change_count() > 0)
+    }
+
+    /// Conditions of everyone in the guild, by character and in the order they were added
+    pub fn list_conditions(&mut self, guild_id: u64) -> Result<Vec<Condition>> {
+        let mut statement = self.connection.prepare(
+            "SELECT character_name, name, rounds_left FROM conditions WHERE guild_id=:guild_id ORDER BY character_name, rowid",
+        )?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        let mut conditions = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            conditions.push(Condition {
+                character_name: statement.read::<String, _>("character_name")?,
+                name: statement.read::<String, _>("name")?,
+                rounds_left: statement.read::<Option<i64>, _>("rounds_left")?,
+            });
+        }
+        Ok(conditions)
+    }
+
+    /// Counts down the conditions of the characters for a new round and removes the ones that
+    /// ran out, returning them
+    pub fn tick_conditions(
+        &mut self,
+        guild_id: u64,
+        character_names: &[String],
+    ) -> Result<Vec<Condition>> {
+        self.transaction(|sheet_db| sheet_db.count_down_conditions(guild_id, character_names))
+    }
+
+    fn count_down_conditions(
+        &mut self,
+        guild_id: u64,
+        character_names: &[String],
+    ) -> Result<Vec<Condition>> {
+        let mut expired = Vec::new();
+        for character_name in character_names {
+            let mut statement = self.connection.prepare(
+                "UPDATE conditions SET rounds_left=rounds_left - 1 WHERE guild_id=:guild_id AND character_name=:character_name AND rounds_left IS NOT NULL",
+            )?;
+            statement.bind::<&[(&str, sqlite::Value)]>(
+                &[
+                    (":guild_id", (guild_id as i64).into()),
+                    (":character_name", character_name.as_str().into()),
+                ][..],
+            )?;
+            statement.next()?;
+            drop(statement);
+
+            let mut statement = self.connection.prepare(
+                "SELECT name FROM conditions WHERE guild_id=:guild_id AND character_name=:character_name AND rounds_left <= 0",
+            )?;
+            statement.bind::<&[(&str, sqlite::Value)]>(
+                &[
+                    (":guild_id", (guild_id as i64).into()),
+                    (":character_name", character_name.as_str().into()),
+                ][..],
+            )?;
+            while let sqlite::State::Row = statement.next()? {
+                expired.push(Condition {
+                    character_name: character_name.clone(),
+                    name: statement.read::<String, _>("name")?,
+                    rounds_left: Some(0),
+                });
+            }
+            drop(statement);
+
+            let mut statement = self.connection.prepare(
+                "DELETE FROM conditions WHERE guild_id=:guild_id AND character_name=:character_name AND rounds_left <= 0",
+            )?;
+            statement.bind::<&[(&str, sqlite::Value)]>(
+                &[
+                    (":guild_id", (guild_id as i64).into()),
+                    (":character_name", character_name.as_str().into()),
+                ][..],
+            )?;
+            statement.next()?;
+        }
+        Ok(expired)
+    }
+}
@@ -0,0 +1,85 @@
+use super::{Result, SheetDB};
+use crate::llm::{ChatMessage, Role};
+
+impl SheetDB {
+    /// Returns up to `limit` of the most recent turns of a channel's conversation, oldest first
+    pub fn get_conversation(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        limit: usize,
+    ) -> Result<Vec<ChatMessage>> {
+        let mut statement = self.connection.prepare(
+            "SELECT role, content FROM conversation_turns WHERE guild_id=:guild_id AND channel_id=:channel_id ORDER BY id DESC LIMIT :limit",
+        )?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+                (":limit", limit as i64),
+            ][..],
+        )?;
+
+        let mut turns = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            let role = match statement.read::<String, _>("role")?.as_str() {
+                "assistant" => Role::Assistant,
+                _ => Role::User,
+            };
+            turns.push(ChatMessage {
+                role,
+                content: statement.read::<String, _>("content")?,
+            });
+        }
+        turns.reverse();
+        Ok(turns)
+    }
+
+    /// Appends turns to a channel's conversation, keeping only the `keep` most recent ones
+    pub fn append_conversation(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        turns: &[ChatMessage],
+        keep: usize,
+    ) -> Result<()> {
+        for turn in turns {
+            let mut statement = self.connection.prepare("INSERT INTO conversation_turns (guild_id, channel_id, role, content) VALUES (:guild_id, :channel_id, :role, :content);")?;
+            statement.bind::<&[(&str, sqlite::Value)]>(
+                &[
+                    (":guild_id", (guild_id as i64).into()),
+                    (":channel_id", (channel_id as i64).into()),
+                    (":role", turn.role.as_str().into()),
+                    (":content", turn.content.as_str().into()),
+                ][..],
+            )?;
+            statement.next()?;
+        }
+
+        let mut statement = self.connection.prepare("DELETE FROM conversation_turns WHERE guild_id=:guild_id AND channel_id=:channel_id AND id NOT IN (SELECT id FROM conversation_turns WHERE guild_id=:guild_id AND channel_id=:channel_id ORDER BY id DESC LIMIT :keep);")?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+                (":keep", keep as i64),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Removes a channel's conversation, returning the number of removed turns
+    pub fn forget_conversation(&mut self, guild_id: u64, channel_id: u64) -> Result<usize> {
+        let mut statement = self.connection.prepare(
+            "DELETE FROM conversation_turns WHERE guild_id=:guild_id AND channel_id=:channel_id",
+        )?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(self.connection.change_count())
+    }
+}
@@ -0,0 +1,91 @@
+use super::{Result, SheetDB};
+
+/// An ability a character used in checks marked for advancement
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExperienceMark {
+    /// Full name of the ability as on the sheet
+    pub ability: String,
+    /// Number of sessions in which the ability was marked
+    pub sessions: i64,
+    /// Number of marked checks across those sessions
+    pub checks: i64,
+}
+
+impl SheetDB {
+    /// Marks the abilities of a check in the running session of the channel.
+    /// Returns whether a session was running.
+    pub fn mark_abilities(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        character_name: &str,
+        abilities: &[&str],
+    ) -> Result<bool> {
+        let Some(session) = self.get_open_session(guild_id, channel_id)? else {
+            return Ok(false);
+        };
+        for ability in abilities {
+            let mut statement = self.connection.prepare("INSERT INTO experience_marks (guild_id, character_name, ability, session_id, checks) VALUES (:guild_id, :character_name, :ability, :session_id, 1) ON CONFLICT(guild_id, character_name, ability, session_id) DO UPDATE SET checks=checks + 1;")?;
+            statement.bind::<&[(&str, sqlite::Value)]>(
+                &[
+                    (":guild_id", (guild_id as i64).into()),
+                    (":character_name", character_name.into()),
+                    (":ability", (*ability).into()),
+                    (":session_id", session.id.into()),
+                ][..],
+            )?;
+            statement.next()?;
+        }
+        Ok(true)
+    }
+
+    /// Marked abilities of a character, the ones marked in the most sessions first
+    pub fn list_marks(
+        &mut self,
+        guild_id: u64,
+        character_name: &str,
+    ) -> Result<Vec<ExperienceMark>> {
+        let mut statement = self.connection.prepare(
+            "SELECT ability, count(*) AS sessions, sum(checks) AS checks FROM experience_marks
+             WHERE guild_id=:guild_id AND character_name=:character_name
+             GROUP BY ability ORDER BY sessions DESC, checks DESC, ability",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", character_name.into()),
+            ][..],
+        )?;
+
+        let mut marks = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            marks.push(ExperienceMark {
+                ability: statement.read::<String, _>("ability")?,
+                sessions: statement.read::<i64, _>("sessions")?,
+                checks: statement.read::<i64, _>("checks")?,
+            });
+        }
+        Ok(marks)
+    }
+
+    /// Clears the marks of a character for one ability, across all sessions
+    pub fn clear_marks(
+        &mut self,
+        guild_id: u64,
+        character_name: &str,
+        ability: &str,
+    ) -> Result<()> {
+        let mut statement = self.connection.prepare(
+            "DELETE FROM experience_marks WHERE guild_id=:guild_id AND character_name=:character_name AND ability=:ability",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", character_name.into()),
+                (":ability", ability.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+}
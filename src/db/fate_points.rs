@@ -0,0 +1,152 @@
+use super::{Result, SheetDB};
+
+/// A change to the fate points of a character
+#[derive(Debug, Clone, PartialEq)]
+pub struct FateEntry {
+    pub created_at: String,
+    pub change: i64,
+    /// Why the points changed: `grant`, `spend`, `check` or `reset`
+    pub reason: String,
+}
+
+impl SheetDB {
+    /// Current fate points of a character, the sum of its ledger
+    pub fn get_fate_points(&mut self, guild_id: u64, character_name: &str) -> Result<i64> {
+        let mut statement = self.connection.prepare(
+            "SELECT COALESCE(SUM(change), 0) AS points FROM fate_points WHERE guild_id=:guild_id AND character_name=:character_name",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", character_name.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        statement.read::<i64, _>("points")
+    }
+
+    /// Fate points of all characters of the guild that have a ledger, by name
+    pub fn list_fate_points(&mut self, guild_id: u64) -> Result<Vec<(String, i64)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT character_name, SUM(change) AS points FROM fate_points WHERE guild_id=:guild_id GROUP BY character_name ORDER BY character_name",
+        )?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        let mut points = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            points.push((
+                statement.read::<String, _>("character_name")?,
+                statement.read::<i64, _>("points")?,
+            ));
+        }
+        Ok(points)
+    }
+
+    /// The latest changes to the fate points of a character, newest first
+    pub fn get_fate_history(
+        &mut self,
+        guild_id: u64,
+        character_name: &str,
+        limit: usize,
+    ) -> Result<Vec<FateEntry>> {
+        let mut statement = self.connection.prepare(
+            "SELECT created_at, change, reason FROM fate_points WHERE guild_id=:guild_id AND character_name=:character_name ORDER BY id DESC LIMIT :limit",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", character_name.into()),
+                (":limit", (limit as i64).into()),
+            ][..],
+        )?;
+
+        let mut entries = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            entries.push(FateEntry {
+                created_at: statement.read::<String, _>("created_at")?,
+                change: statement.read::<i64, _>("change")?,
+                reason: statement.read::<String, _>("reason")?,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Adds a change to the ledger of a character and returns its new fate points
+    pub fn add_fate_points(
+        &mut self,
+        guild_id: u64,
+        character_name: &str,
+        change: i64,
+        reason: &str,
+    ) -> Result<i64> {
+        let mut statement = self.connection.prepare("INSERT INTO fate_points (guild_id, character_name, change, reason) VALUES (:guild_id, :character_name, :change, :reason);")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", character_name.into()),
+                (":change", change.into()),
+                (":reason", reason.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        drop(statement);
+        self.get_fate_points(guild_id, character_name)
+    }
+
+    /// Spends one fate point of a character, unless it has none left.
+    /// Returns whether a point was spent.
+    pub fn spend_fate_point(
+        &mut self,
+        guild_id: u64,
+        character_name: &str,
+        reason: &str,
+    ) -> Result<bool> {
+        // A single statement, so concurrent checks cannot spend the same point twice
+        let mut statement = self.connection.prepare(
+            "INSERT INTO fate_points (guild_id, character_name, change, reason)
+             SELECT :guild_id, :character_name, -1, :reason
+             WHERE (SELECT COALESCE(SUM(change), 0) FROM fate_points WHERE guild_id=:guild_id AND character_name=:character_name) > 0",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":character_name", character_name.into()),
+                (":reason", reason.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(self.connection.change_count() > 0)
+    }
+
+    /// Sets the fate points of a character, or of every character of the guild that is claimed
+    /// or has a ledger, to `points`. Returns the number of characters whose points changed.
+    pub fn reset_fate_points(
+        &mut self,
+        guild_id: u64,
+        character_name: Option<&str>,
+        points: i64,
+    ) -> Result<usize> {
+        let mut statement = self.connection.prepare(
+            "INSERT INTO fate_points (guild_id, character_name, change, reason)
+             SELECT :guild_id, character_name, :points - SUM(change), 'reset' FROM (
+                 SELECT character_name, change FROM fate_points WHERE guild_id=:guild_id
+                 UNION ALL SELECT sheet, 0 FROM users WHERE guild_id=:guild_id
+                 UNION ALL SELECT :character_name, 0 WHERE :character_name IS NOT NULL
+             )
+             WHERE :character_name IS NULL OR character_name=:character_name
+             GROUP BY character_name HAVING SUM(change) != :points",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (
+                    ":character_name",
+                    character_name.map_or(sqlite::Value::Null, |name| name.into()),
+                ),
+                (":points", points.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(self.connection.change_count())
+    }
+}
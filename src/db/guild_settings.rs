@@ -0,0 +1,133 @@
+use super::{Result, SheetDB};
+
+impl SheetDB {
+    /// Whether check results are narrated by the LLM in this guild
+    pub fn narration_enabled(&mut self, guild_id: u64) -> Result<bool> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT narration FROM guild_settings WHERE guild_id=:guild_id")?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(statement.read::<i64, _>("narration")? != 0),
+            sqlite::State::Done => Ok(false),
+        }
+    }
+
+    pub fn set_narration_enabled(&mut self, guild_id: u64, enabled: bool) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO guild_settings (guild_id, narration) VALUES (:guild_id, :narration) ON CONFLICT(guild_id) DO UPDATE SET narration=excluded.narration;")?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":narration", enabled as i64),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Role whose members are GMs in this guild
+    pub fn get_gm_role(&mut self, guild_id: u64) -> Result<Option<u64>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT gm_role FROM guild_settings WHERE guild_id=:guild_id")?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(statement
+                .read::<Option<i64>, _>("gm_role")?
+                .map(|role| role as u64)),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    pub fn set_gm_role(&mut self, guild_id: u64, role_id: Option<u64>) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO guild_settings (guild_id, gm_role) VALUES (:guild_id, :gm_role) ON CONFLICT(guild_id) DO UPDATE SET gm_role=excluded.gm_role;")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (
+                    ":gm_role",
+                    role_id.map_or(sqlite::Value::Null, |id| (id as i64).into()),
+                ),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Whether players may roll for characters they have not claimed
+    pub fn open_rolls(&mut self, guild_id: u64) -> Result<bool> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT open_rolls FROM guild_settings WHERE guild_id=:guild_id")?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(statement.read::<i64, _>("open_rolls")? != 0),
+            sqlite::State::Done => Ok(false),
+        }
+    }
+
+    pub fn set_open_rolls(&mut self, guild_id: u64, open: bool) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO guild_settings (guild_id, open_rolls) VALUES (:guild_id, :open_rolls) ON CONFLICT(guild_id) DO UPDATE SET open_rolls=excluded.open_rolls;")?;
+        statement.bind::<&[(&str, i64)]>(
+            &[(":guild_id", guild_id as i64), (":open_rolls", open as i64)][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Whether check results are posted as plain text instead of embeds in this guild
+    pub fn plain_checks(&mut self, guild_id: u64) -> Result<bool> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT plain_checks FROM guild_settings WHERE guild_id=:guild_id")?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(statement.read::<i64, _>("plain_checks")? != 0),
+            sqlite::State::Done => Ok(false),
+        }
+    }
+
+    pub fn set_plain_checks(&mut self, guild_id: u64, plain: bool) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO guild_settings (guild_id, plain_checks) VALUES (:guild_id, :plain_checks) ON CONFLICT(guild_id) DO UPDATE SET plain_checks=excluded.plain_checks;")?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":plain_checks", plain as i64),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Language code the bot answers in on this guild, unless members picked their own
+    pub fn get_guild_language(&mut self, guild_id: u64) -> Result<Option<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT language FROM guild_settings WHERE guild_id=:guild_id")?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        match statement.next()? {
+            sqlite::State::Row => statement.read::<Option<String>, _>("language"),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    pub fn set_guild_language(&mut self, guild_id: u64, language: Option<&str>) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO guild_settings (guild_id, language) VALUES (:guild_id, :language) ON CONFLICT(guild_id) DO UPDATE SET language=excluded.language;")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (
+                    ":language",
+                    language.map_or(sqlite::Value::Null, |language| language.into()),
+                ),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+}
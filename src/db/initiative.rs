@@ -0,0 +1,140 @@
+use super::{Result, SheetDB};
+
+/// A character or NPC in the initiative order of a channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Combatant {
+    pub name: String,
+    pub initiative: i16,
+    /// Added by the GM rather than rolled for a claimed character
+    pub npc: bool,
+}
+
+/// Turn order of a fight in a channel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Initiative {
+    /// Starts at 1 and counts up whenever the order starts over
+    pub round: i64,
+    /// Index of the combatant whose turn it is
+    pub turn: usize,
+    /// Combatants in the order they act
+    pub combatants: Vec<Combatant>,
+}
+
+impl SheetDB {
+    /// Returns the initiative order of a channel, if a fight is running there
+    pub fn get_initiative(&mut self, guild_id: u64, channel_id: u64) -> Result<Option<Initiative>> {
+        let mut statement = self.connection.prepare(
+            "SELECT round, turn FROM initiative WHERE guild_id=:guild_id AND channel_id=:channel_id",
+        )?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+            ][..],
+        )?;
+        let (round, turn) = match statement.next()? {
+            sqlite::State::Row => (
+                statement.read::<i64, _>("round")?,
+                statement.read::<i64, _>("turn")? as usize,
+            ),
+            sqlite::State::Done => return Ok(None),
+        };
+        drop(statement);
+
+        let mut statement = self.connection.prepare(
+            "SELECT name, initiative, npc FROM initiative_entries WHERE guild_id=:guild_id AND channel_id=:channel_id ORDER BY position",
+        )?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+            ][..],
+        )?;
+        let mut combatants = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            combatants.push(Combatant {
+                name: statement.read::<String, _>("name")?,
+                initiative: statement.read::<i64, _>("initiative")? as i16,
+                npc: statement.read::<i64, _>("npc")? != 0,
+            });
+        }
+        Ok(Some(Initiative {
+            round,
+            turn,
+            combatants,
+        }))
+    }
+
+    /// Stores the initiative order of a channel, replacing the previous one
+    pub fn save_initiative(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        initiative: &Initiative,
+    ) -> Result<()> {
+        self.transaction(|sheet_db| sheet_db.replace_initiative(guild_id, channel_id, initiative))
+    }
+
+    fn replace_initiative(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        initiative: &Initiative,
+    ) -> Result<()> {
+        self.delete_initiative(guild_id, channel_id)?;
+
+        let mut statement = self.connection.prepare("INSERT INTO initiative (guild_id, channel_id, round, turn) VALUES (:guild_id, :channel_id, :round, :turn);")?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+                (":round", initiative.round),
+                (":turn", initiative.turn as i64),
+            ][..],
+        )?;
+        statement.next()?;
+
+        for (position, combatant) in initiative.combatants.iter().enumerate() {
+            let mut statement = self.connection.prepare("INSERT INTO initiative_entries (guild_id, channel_id, position, name, initiative, npc) VALUES (:guild_id, :channel_id, :position, :name, :initiative, :npc);")?;
+            statement.bind::<&[(&str, sqlite::Value)]>(
+                &[
+                    (":guild_id", (guild_id as i64).into()),
+                    (":channel_id", (channel_id as i64).into()),
+                    (":position", (position as i64).into()),
+                    (":name", combatant.name.as_str().into()),
+                    (":initiative", (combatant.initiative as i64).into()),
+                    (":npc", (combatant.npc as i64).into()),
+                ][..],
+            )?;
+            statement.next()?;
+        }
+        Ok(())
+    }
+
+    /// Ends the fight in a channel. Returns whether one was running.
+    pub fn delete_initiative(&mut self, guild_id: u64, channel_id: u64) -> Result<bool> {
+        let mut statement = self.connection.prepare(
+            "DELETE FROM initiative_entries WHERE guild_id=:guild_id AND channel_id=:channel_id",
+        )?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+            ][..],
+        )?;
+        statement.next()?;
+        drop(statement);
+
+        let mut statement = self.connection.prepare(
+            "DELETE FROM initiative WHERE guild_id=:guild_id AND channel_id=:channel_id",
+        )?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(self.connection.change_count() > 0)
+    }
+}
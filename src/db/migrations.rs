@@ -0,0 +1,326 @@
+//! Versioned schema migrations for the [`SheetDB`](super::SheetDB).
+//!
+//! Every change to the schema is appended to [`MIGRATIONS`] as a new step with the next
+//! version number. Steps that have already been applied are recorded in the
+//! `schema_version` table and are never run again, so existing steps must not be edited.
+
+use super::Result;
+
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    pub sql: &'static str,
+}
+
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Create users table holding sheet claims",
+        // Uses `IF NOT EXISTS`, so databases created before versioning was introduced are adopted as is
+        sql: "
+        CREATE TABLE IF NOT EXISTS users (guild_id UNSIGNED BIG INT, author_id UNSIGNED BIG INT, sheet TEXT);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_users ON users(guild_id, author_id, sheet);
+        -- Every author can only appear once per guild
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_unique_author_per_guild ON users(guild_id, author_id);
+        -- Every sheet can only appear once per guild
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_unique_sheet_per_guild ON users(guild_id, sheet);
+    ",
+    },
+    Migration {
+        version: 2,
+        description: "Create personas and guild_settings tables",
+        sql: "
+            CREATE TABLE personas (guild_id UNSIGNED BIG INT NOT NULL, name TEXT NOT NULL, system_prompt TEXT NOT NULL, model TEXT, temperature REAL, PRIMARY KEY (guild_id, name));
+            CREATE TABLE guild_settings (guild_id UNSIGNED BIG INT PRIMARY KEY, default_persona TEXT);
+        ",
+    },
+    Migration {
+        version: 3,
+        description: "Create conversation_turns table for completion memory",
+        sql: "
+            CREATE TABLE conversation_turns (id INTEGER PRIMARY KEY AUTOINCREMENT, guild_id UNSIGNED BIG INT NOT NULL, channel_id UNSIGNED BIG INT NOT NULL, role TEXT NOT NULL, content TEXT NOT NULL, created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP);
+            CREATE INDEX idx_conversation_turns_channel ON conversation_turns(guild_id, channel_id, id);
+        ",
+    },
+    Migration {
+        version: 4,
+        description: "Create rule_passages full-text index",
+        sql: "
+            CREATE VIRTUAL TABLE rule_passages USING fts5(source UNINDEXED, heading, content, tokenize = 'unicode61 remove_diacritics 2');
+        ",
+    },
+    Migration {
+        version: 5,
+        description: "Add narration setting to guild_settings",
+        sql: "
+            ALTER TABLE guild_settings ADD COLUMN narration INTEGER NOT NULL DEFAULT 0;
+        ",
+    },
+    Migration {
+        version: 6,
+        description: "Create tables for play sessions and their rolls and messages",
+        sql: "
+            CREATE TABLE sessions (id INTEGER PRIMARY KEY AUTOINCREMENT, guild_id UNSIGNED BIG INT NOT NULL, channel_id UNSIGNED BIG INT NOT NULL, capture_messages INTEGER NOT NULL DEFAULT 0, started_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP, ended_at TEXT);
+            -- Only one session can run in a channel at a time
+            CREATE UNIQUE INDEX idx_open_session_per_channel ON sessions(guild_id, channel_id) WHERE ended_at IS NULL;
+            CREATE TABLE session_rolls (id INTEGER PRIMARY KEY AUTOINCREMENT, session_id INTEGER NOT NULL REFERENCES sessions(id), character_name TEXT NOT NULL, description TEXT NOT NULL, created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP);
+            CREATE INDEX idx_session_rolls_session ON session_rolls(session_id, id);
+            CREATE TABLE session_messages (id INTEGER PRIMARY KEY AUTOINCREMENT, session_id INTEGER NOT NULL REFERENCES sessions(id), author TEXT NOT NULL, content TEXT NOT NULL, created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP);
+            CREATE INDEX idx_session_messages_session ON session_messages(session_id, id);
+        ",
+    },
+    Migration {
+        version: 7,
+        description: "Create llm_usage and completion_roles tables for completion quotas",
+        sql: "
+            CREATE TABLE llm_usage (guild_id UNSIGNED BIG INT NOT NULL, user_id UNSIGNED BIG INT NOT NULL, day TEXT NOT NULL, requests INTEGER NOT NULL, tokens INTEGER NOT NULL, PRIMARY KEY (guild_id, user_id, day));
+            CREATE TABLE completion_roles (guild_id UNSIGNED BIG INT NOT NULL, role_id UNSIGNED BIG INT NOT NULL, PRIMARY KEY (guild_id, role_id));
+        ",
+    },
+    Migration {
+        version: 8,
+        description: "Add GM role and open rolls settings to guild_settings",
+        sql: "
+            ALTER TABLE guild_settings ADD COLUMN gm_role UNSIGNED BIG INT;
+            ALTER TABLE guild_settings ADD COLUMN open_rolls INTEGER NOT NULL DEFAULT 0;
+        ",
+    },
+    Migration {
+        version: 9,
+        description: "Add language settings of guilds and users",
+        sql: "
+            ALTER TABLE guild_settings ADD COLUMN language TEXT;
+            CREATE TABLE user_settings (user_id UNSIGNED BIG INT PRIMARY KEY, language TEXT);
+        ",
+    },
+    Migration {
+        version: 10,
+        description: "Add plain text checks setting to guild_settings",
+        sql: "ALTER TABLE guild_settings ADD COLUMN plain_checks INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 11,
+        description: "Add fate point ledger",
+        sql: "
+            CREATE TABLE fate_points (
+                id INTEGER PRIMARY KEY,
+                guild_id UNSIGNED BIG INT NOT NULL,
+                character_name TEXT NOT NULL,
+                change INTEGER NOT NULL,
+                reason TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            CREATE INDEX fate_points_character ON fate_points (guild_id, character_name);
+        ",
+    },
+    Migration {
+        version: 12,
+        description: "Add initiative order per channel",
+        sql: "
+            CREATE TABLE initiative (
+                guild_id UNSIGNED BIG INT NOT NULL,
+                channel_id UNSIGNED BIG INT NOT NULL,
+                round INTEGER NOT NULL,
+                turn INTEGER NOT NULL,
+                PRIMARY KEY (guild_id, channel_id)
+            );
+            CREATE TABLE initiative_entries (
+                guild_id UNSIGNED BIG INT NOT NULL,
+                channel_id UNSIGNED BIG INT NOT NULL,
+                position INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                initiative INTEGER NOT NULL,
+                npc INTEGER NOT NULL,
+                PRIMARY KEY (guild_id, channel_id, position)
+            );
+        ",
+    },
+    Migration {
+        version: 13,
+        description: "Add hit points and conditions of characters",
+        sql: "
+            CREATE TABLE hit_points (
+                guild_id UNSIGNED BIG INT NOT NULL,
+                character_name TEXT NOT NULL,
+                hp INTEGER NOT NULL,
+                max_hp INTEGER NOT NULL,
+                PRIMARY KEY (guild_id, character_name)
+            );
+            CREATE TABLE conditions (
+                guild_id UNSIGNED BIG INT NOT NULL,
+                character_name TEXT NOT NULL,
+                name TEXT NOT NULL,
+                rounds_left INTEGER,
+                PRIMARY KEY (guild_id, character_name, name)
+            );
+        ",
+    },
+    Migration {
+        version: 14,
+        description: "Add experience marks of abilities used in sessions",
+        sql: "
+            CREATE TABLE experience_marks (
+                guild_id UNSIGNED BIG INT NOT NULL,
+                character_name TEXT NOT NULL,
+                ability TEXT NOT NULL,
+                session_id INTEGER NOT NULL REFERENCES sessions(id),
+                checks INTEGER NOT NULL,
+                PRIMARY KEY (guild_id, character_name, ability, session_id)
+            );
+        ",
+    },
+];
+
+/// Returns the latest applied schema version, or 0 if no migration has been applied yet
+pub fn current_version(connection: &sqlite::Connection) -> Result<i64> {
+    let mut statement = connection.prepare(
+        "SELECT count(*) AS n FROM sqlite_master WHERE type='table' AND name='schema_version'",
+    )?;
+    statement.next()?;
+    if statement.read::<i64, _>("n")? == 0 {
+        return Ok(0);
+    }
+
+    let mut statement =
+        connection.prepare("SELECT coalesce(max(version), 0) AS version FROM schema_version")?;
+    statement.next()?;
+    statement.read::<i64, _>("version")
+}
+
+/// Returns the migrations that have not been applied yet, without touching the database
+pub fn pending(connection: &sqlite::Connection) -> Result<Vec<&'static Migration>> {
+    pending_steps(connection, MIGRATIONS)
+}
+
+/// Applies all pending migrations in order, each in its own transaction.
+/// Returns the migrations that were applied.
+pub fn run(connection: &sqlite::Connection) -> Result<Vec<&'static Migration>> {
+    run_steps(connection, MIGRATIONS)
+}
+
+fn pending_steps<'a>(
+    connection: &sqlite::Connection,
+    steps: &'a [Migration],
+) -> Result<Vec<&'a Migration>> {
+    let version = current_version(connection)?;
+    Ok(steps.iter().filter(|m| m.version > version).collect())
+}
+
+fn run_steps<'a>(
+    connection: &sqlite::Connection,
+    steps: &'a [Migration],
+) -> Result<Vec<&'a Migration>> {
+    connection.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER PRIMARY KEY, description TEXT NOT NULL, applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP);",
+    )?;
+
+    let pending = pending_steps(connection, steps)?;
+    for migration in &pending {
+        connection.execute("BEGIN;")?;
+        if let Err(err) = apply(connection, migration) {
+            connection.execute("ROLLBACK;")?;
+            return Err(err);
+        }
+        connection.execute("COMMIT;")?;
+    }
+    Ok(pending)
+}
+
+fn apply(connection: &sqlite::Connection, migration: &Migration) -> Result<()> {
+    connection.execute(migration.sql)?;
+
+    let mut statement = connection.prepare(
+        "INSERT INTO schema_version (version, description) VALUES (:version, :description);",
+    )?;
+    statement.bind::<&[(&str, sqlite::Value)]>(
+        &[
+            (":version", migration.version.into()),
+            (":description", migration.description.into()),
+        ][..],
+    )?;
+    statement.next()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_connection() -> sqlite::Connection {
+        sqlite::Connection::open(":memory:").unwrap()
+    }
+
+    fn latest_version() -> i64 {
+        MIGRATIONS.last().unwrap().version
+    }
+
+    #[test]
+    fn versions_are_strictly_increasing_from_one() {
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i64 + 1, "{}", migration.description);
+        }
+    }
+
+    #[test]
+    fn fresh_database_applies_all_migrations() {
+        let connection = memory_connection();
+        assert_eq!(current_version(&connection).unwrap(), 0);
+        assert_eq!(pending(&connection).unwrap().len(), MIGRATIONS.len());
+
+        let applied = run(&connection).unwrap();
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        assert_eq!(current_version(&connection).unwrap(), latest_version());
+        assert!(pending(&connection).unwrap().is_empty());
+    }
+
+    #[test]
+    fn running_twice_is_a_no_op() {
+        let connection = memory_connection();
+        run(&connection).unwrap();
+        assert!(run(&connection).unwrap().is_empty());
+        assert_eq!(current_version(&connection).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn pending_does_not_modify_database() {
+        let connection = memory_connection();
+        pending(&connection).unwrap();
+        assert_eq!(current_version(&connection).unwrap(), 0);
+        assert!(connection.prepare("SELECT * FROM users").is_err());
+    }
+
+    #[test]
+    fn unversioned_database_keeps_its_claims() {
+        let connection = memory_connection();
+        // Schema as created before migrations were introduced
+        connection
+            .execute(
+                "CREATE TABLE users (guild_id UNSIGNED BIG INT, author_id UNSIGNED BIG INT, sheet TEXT);
+                 INSERT INTO users VALUES (1, 2, 'Gandalf');",
+            )
+            .unwrap();
+
+        run(&connection).unwrap();
+
+        let mut statement = connection.prepare("SELECT sheet FROM users").unwrap();
+        statement.next().unwrap();
+        assert_eq!(statement.read::<String, _>("sheet").unwrap(), "Gandalf");
+        assert_eq!(current_version(&connection).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn failing_migration_is_rolled_back() {
+        let connection = memory_connection();
+        run(&connection).unwrap();
+
+        let broken = [Migration {
+            version: latest_version() + 1,
+            description: "broken",
+            sql: "CREATE TABLE half_done (id INTEGER); SELECT * FROM does_not_exist;",
+        }];
+        assert!(run_steps(&connection, &broken).is_err());
+
+        assert!(connection.prepare("SELECT * FROM half_done").is_err());
+        assert_eq!(current_version(&connection).unwrap(), latest_version());
+    }
+}
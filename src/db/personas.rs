@@ -0,0 +1,155 @@
+use super::{Result, SheetDB};
+
+/// Named LLM configuration of a guild
+#[derive(Debug, Clone, PartialEq)]
+pub struct Persona {
+    pub name: String,
+    pub system_prompt: String,
+    /// Model to use instead of the configured default
+    pub model: Option<String>,
+    /// Temperature to use instead of the configured default
+    pub temperature: Option<f64>,
+}
+
+impl SheetDB {
+    pub fn get_persona(&mut self, guild_id: u64, name: &str) -> Result<Option<Persona>> {
+        let mut statement = self.connection.prepare(
+            "SELECT name, system_prompt, model, temperature FROM personas WHERE guild_id=:guild_id AND name=:name",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":name", name.into()),
+            ][..],
+        )?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Some(Persona {
+                name: statement.read::<String, _>("name")?,
+                system_prompt: statement.read::<String, _>("system_prompt")?,
+                model: statement.read::<Option<String>, _>("model")?,
+                temperature: statement.read::<Option<f64>, _>("temperature")?,
+            })),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    pub fn list_personas(&mut self, guild_id: u64) -> Result<Vec<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name FROM personas WHERE guild_id=:guild_id ORDER BY name")?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        let mut names = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            names.push(statement.read::<String, _>("name")?);
+        }
+        Ok(names)
+    }
+
+    pub fn store_persona(&mut self, guild_id: u64, persona: &Persona) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT OR REPLACE INTO personas (guild_id, name, system_prompt, model, temperature) VALUES (:guild_id, :name, :system_prompt, :model, :temperature);")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":name", persona.name.as_str().into()),
+                (":system_prompt", persona.system_prompt.as_str().into()),
+                (
+                    ":model",
+                    persona
+                        .model
+                        .as_deref()
+                        .map_or(sqlite::Value::Null, Into::into),
+                ),
+                (
+                    ":temperature",
+                    persona.temperature.map_or(sqlite::Value::Null, Into::into),
+                ),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Returns whether a persona was removed
+    pub fn remove_persona(&mut self, guild_id: u64, name: &str) -> Result<bool> {
+        let mut statement = self
+            .connection
+            .prepare("DELETE FROM personas WHERE guild_id=:guild_id AND name=:name")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":name", name.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(self.connection.change_count() > 0)
+    }
+
+    /// Name of the persona used when a completion does not ask for a specific one
+    pub fn get_default_persona(&mut self, guild_id: u64) -> Result<Option<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT default_persona FROM guild_settings WHERE guild_id=:guild_id")?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        match statement.next()? {
+            sqlite::State::Row => statement.read::<Option<String>, _>("default_persona"),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    pub fn set_default_persona(&mut self, guild_id: u64, name: Option<&str>) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO guild_settings (guild_id, default_persona) VALUES (:guild_id, :name) ON CONFLICT(guild_id) DO UPDATE SET default_persona=excluded.default_persona;")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (":name", name.map_or(sqlite::Value::Null, Into::into)),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn personas_round_trip_with_and_without_overrides() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        let narrator = Persona {
+            name: "narrator".to_owned(),
+            system_prompt: "Narrate".to_owned(),
+            model: Some("big-model".to_owned()),
+            temperature: Some(0.9),
+        };
+        let sage = Persona {
+            name: "sage".to_owned(),
+            system_prompt: "Advise".to_owned(),
+            model: None,
+            temperature: None,
+        };
+        sheet_db.store_persona(1, &narrator).unwrap();
+        sheet_db.store_persona(1, &sage).unwrap();
+
+        assert_eq!(sheet_db.get_persona(1, "narrator").unwrap(), Some(narrator));
+        assert_eq!(sheet_db.get_persona(1, "sage").unwrap(), Some(sage));
+        assert_eq!(sheet_db.get_persona(2, "sage").unwrap(), None);
+        assert_eq!(sheet_db.list_personas(1).unwrap(), ["narrator", "sage"]);
+
+        assert_eq!(sheet_db.get_default_persona(1).unwrap(), None);
+        sheet_db.set_default_persona(1, Some("sage")).unwrap();
+        assert_eq!(
+            sheet_db.get_default_persona(1).unwrap().as_deref(),
+            Some("sage")
+        );
+        sheet_db.set_default_persona(1, None).unwrap();
+        assert_eq!(sheet_db.get_default_persona(1).unwrap(), None);
+
+        assert!(sheet_db.remove_persona(1, "sage").unwrap());
+        assert!(!sheet_db.remove_persona(1, "sage").unwrap());
+        assert_eq!(sheet_db.list_personas(1).unwrap(), ["narrator"]);
+    }
+}
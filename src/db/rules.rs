@@ -0,0 +1,56 @@
+use super::{Result, SheetDB};
+
+/// Section of a rule document, the unit the rules index returns
+#[derive(Debug, Clone, PartialEq)]
+pub struct RulePassage {
+    /// Path of the document relative to the rules directory
+    pub source: String,
+    /// Heading of the section the passage belongs to, if any
+    pub heading: String,
+    pub content: String,
+}
+
+impl SheetDB {
+    /// Replaces the whole rules index with the given passages
+    pub fn replace_rule_passages(&mut self, passages: &[RulePassage]) -> Result<()> {
+        self.transaction(|sheet_db| sheet_db.insert_rule_passages(passages))
+    }
+
+    fn insert_rule_passages(&mut self, passages: &[RulePassage]) -> Result<()> {
+        self.connection.execute("DELETE FROM rule_passages;")?;
+        for passage in passages {
+            let mut statement = self.connection.prepare(
+                "INSERT INTO rule_passages (source, heading, content) VALUES (:source, :heading, :content);",
+            )?;
+            statement.bind::<&[(&str, &str)]>(
+                &[
+                    (":source", passage.source.as_str()),
+                    (":heading", passage.heading.as_str()),
+                    (":content", passage.content.as_str()),
+                ][..],
+            )?;
+            statement.next()?;
+        }
+        Ok(())
+    }
+
+    /// Returns the passages matching the FTS5 `query` best, ranked by BM25
+    pub fn search_rules(&mut self, query: &str, limit: usize) -> Result<Vec<RulePassage>> {
+        let mut statement = self.connection.prepare(
+            "SELECT source, heading, content FROM rule_passages WHERE rule_passages MATCH :query ORDER BY bm25(rule_passages) LIMIT :limit",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[(":query", query.into()), (":limit", (limit as i64).into())][..],
+        )?;
+
+        let mut passages = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            passages.push(RulePassage {
+                source: statement.read::<String, _>("source")?,
+                heading: statement.read::<String, _>("heading")?,
+                content: statement.read::<String, _>("content")?,
+            });
+        }
+        Ok(passages)
+    }
+}
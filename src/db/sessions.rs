@@ -0,0 +1,204 @@
+use super::{Result, SheetDB};
+
+/// A play session in a channel. Times are UTC timestamps as recorded by SQLite.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub id: i64,
+    pub started_at: String,
+    /// `None` while the session is running
+    pub ended_at: Option<String>,
+    /// Whether the channel's messages are recorded as well
+    pub capture_messages: bool,
+}
+
+/// A check rolled during a session
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRoll {
+    pub created_at: String,
+    pub character_name: String,
+    /// The check result as it was posted
+    pub description: String,
+}
+
+/// A channel message sent during a session
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionMessage {
+    pub created_at: String,
+    pub author: String,
+    pub content: String,
+}
+
+const SESSION_COLUMNS: &str = "id, started_at, ended_at, capture_messages";
+
+fn read_session(statement: &sqlite::Statement) -> Result<Session> {
+    Ok(Session {
+        id: statement.read::<i64, _>("id")?,
+        started_at: statement.read::<String, _>("started_at")?,
+        ended_at: statement.read::<Option<String>, _>("ended_at")?,
+        capture_messages: statement.read::<i64, _>("capture_messages")? != 0,
+    })
+}
+
+impl SheetDB {
+    /// Returns the running session of a channel, if any
+    pub fn get_open_session(&mut self, guild_id: u64, channel_id: u64) -> Result<Option<Session>> {
+        let mut statement = self.connection.prepare(format!(
+            "SELECT {} FROM sessions WHERE guild_id=:guild_id AND channel_id=:channel_id AND ended_at IS NULL",
+            SESSION_COLUMNS
+        ))?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+            ][..],
+        )?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Some(read_session(&statement)?)),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    /// Returns the most recently started session of a channel, running or not
+    pub fn get_latest_session(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+    ) -> Result<Option<Session>> {
+        let mut statement = self.connection.prepare(format!(
+            "SELECT {} FROM sessions WHERE guild_id=:guild_id AND channel_id=:channel_id ORDER BY id DESC LIMIT 1",
+            SESSION_COLUMNS
+        ))?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+            ][..],
+        )?;
+
+        match statement.next()? {
+            sqlite::State::Row => Ok(Some(read_session(&statement)?)),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    /// Starts a session in a channel. Fails if one is running there already.
+    pub fn start_session(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        capture_messages: bool,
+    ) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO sessions (guild_id, channel_id, capture_messages) VALUES (:guild_id, :channel_id, :capture_messages);")?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":channel_id", channel_id as i64),
+                (":capture_messages", capture_messages as i64),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Ends the running session of a channel, returning it if there was one
+    pub fn end_session(&mut self, guild_id: u64, channel_id: u64) -> Result<Option<Session>> {
+        let Some(session) = self.get_open_session(guild_id, channel_id)? else {
+            return Ok(None);
+        };
+        let mut statement = self
+            .connection
+            .prepare("UPDATE sessions SET ended_at=CURRENT_TIMESTAMP WHERE id=:id")?;
+        statement.bind((":id", session.id))?;
+        statement.next()?;
+        drop(statement);
+        self.get_latest_session(guild_id, channel_id)
+    }
+
+    /// Records a check in the running session of the channel.
+    /// Returns whether a session was running.
+    pub fn record_session_roll(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        character_name: &str,
+        description: &str,
+    ) -> Result<bool> {
+        let Some(session) = self.get_open_session(guild_id, channel_id)? else {
+            return Ok(false);
+        };
+        let mut statement = self.connection.prepare("INSERT INTO session_rolls (session_id, character_name, description) VALUES (:session_id, :character_name, :description);")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":session_id", session.id.into()),
+                (":character_name", character_name.into()),
+                (":description", description.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(true)
+    }
+
+    /// Records a message in the running session of the channel, if it captures messages.
+    /// Returns whether the message was recorded.
+    pub fn record_session_message(
+        &mut self,
+        guild_id: u64,
+        channel_id: u64,
+        author: &str,
+        content: &str,
+    ) -> Result<bool> {
+        let Some(session) = self.get_open_session(guild_id, channel_id)? else {
+            return Ok(false);
+        };
+        if !session.capture_messages {
+            return Ok(false);
+        }
+        let mut statement = self.connection.prepare("INSERT INTO session_messages (session_id, author, content) VALUES (:session_id, :author, :content);")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":session_id", session.id.into()),
+                (":author", author.into()),
+                (":content", content.into()),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(true)
+    }
+
+    /// Returns the checks of a session in the order they were rolled
+    pub fn get_session_rolls(&mut self, session_id: i64) -> Result<Vec<SessionRoll>> {
+        let mut statement = self.connection.prepare(
+            "SELECT created_at, character_name, description FROM session_rolls WHERE session_id=:session_id ORDER BY id",
+        )?;
+        statement.bind((":session_id", session_id))?;
+
+        let mut rolls = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            rolls.push(SessionRoll {
+                created_at: statement.read::<String, _>("created_at")?,
+                character_name: statement.read::<String, _>("character_name")?,
+                description: statement.read::<String, _>("description")?,
+            });
+        }
+        Ok(rolls)
+    }
+
+    /// Returns the messages of a session in the order they were sent
+    pub fn get_session_messages(&mut self, session_id: i64) -> Result<Vec<SessionMessage>> {
+        let mut statement = self.connection.prepare(
+            "SELECT created_at, author, content FROM session_messages WHERE session_id=:session_id ORDER BY id",
+        )?;
+        statement.bind((":session_id", session_id))?;
+
+        let mut messages = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            messages.push(SessionMessage {
+                created_at: statement.read::<String, _>("created_at")?,
+                author: statement.read::<String, _>("author")?,
+                content: statement.read::<String, _>("content")?,
+            });
+        }
+        Ok(messages)
+    }
+}
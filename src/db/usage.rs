@@ -0,0 +1,83 @@
+use super::{Result, SheetDB};
+
+/// Completions requested on the current (UTC) day
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    pub requests: u64,
+    pub tokens: u64,
+}
+
+impl SheetDB {
+    /// Today's completion usage of a user, or of the whole guild if no user is given
+    pub fn get_usage(&mut self, guild_id: u64, user_id: Option<u64>) -> Result<Usage> {
+        let mut statement = self.connection.prepare(
+            "SELECT coalesce(sum(requests), 0) AS requests, coalesce(sum(tokens), 0) AS tokens FROM llm_usage WHERE guild_id=:guild_id AND (:user_id IS NULL OR user_id=:user_id) AND day=date('now')",
+        )?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":guild_id", (guild_id as i64).into()),
+                (
+                    ":user_id",
+                    user_id.map_or(sqlite::Value::Null, |id| (id as i64).into()),
+                ),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(Usage {
+            requests: statement.read::<i64, _>("requests")? as u64,
+            tokens: statement.read::<i64, _>("tokens")? as u64,
+        })
+    }
+
+    /// Adds a completion using `tokens` tokens to today's usage of a user
+    pub fn record_usage(&mut self, guild_id: u64, user_id: u64, tokens: u64) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO llm_usage (guild_id, user_id, day, requests, tokens) VALUES (:guild_id, :user_id, date('now'), 1, :tokens) ON CONFLICT(guild_id, user_id, day) DO UPDATE SET requests=requests + 1, tokens=tokens + excluded.tokens;")?;
+        statement.bind::<&[(&str, i64)]>(
+            &[
+                (":guild_id", guild_id as i64),
+                (":user_id", user_id as i64),
+                (":tokens", tokens as i64),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+
+    /// Roles that may use completions in a guild. Everyone may if there are none.
+    pub fn list_completion_roles(&mut self, guild_id: u64) -> Result<Vec<u64>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT role_id FROM completion_roles WHERE guild_id=:guild_id")?;
+        statement.bind((":guild_id", guild_id as i64))?;
+
+        let mut roles = Vec::new();
+        while let sqlite::State::Row = statement.next()? {
+            roles.push(statement.read::<i64, _>("role_id")? as u64);
+        }
+        Ok(roles)
+    }
+
+    /// Returns whether the role was not allowed before
+    pub fn add_completion_role(&mut self, guild_id: u64, role_id: u64) -> Result<bool> {
+        let mut statement = self.connection.prepare(
+            "INSERT OR IGNORE INTO completion_roles (guild_id, role_id) VALUES (:guild_id, :role_id);",
+        )?;
+        statement.bind::<&[(&str, i64)]>(
+            &[(":guild_id", guild_id as i64), (":role_id", role_id as i64)][..],
+        )?;
+        statement.next()?;
+        Ok(self.connection.change_count() > 0)
+    }
+
+    /// Returns whether the role was allowed before
+    pub fn remove_completion_role(&mut self, guild_id: u64, role_id: u64) -> Result<bool> {
+        let mut statement = self.connection.prepare(
+            "DELETE FROM completion_roles WHERE guild_id=:guild_id AND role_id=:role_id",
+        )?;
+        statement.bind::<&[(&str, i64)]>(
+            &[(":guild_id", guild_id as i64), (":role_id", role_id as i64)][..],
+        )?;
+        statement.next()?;
+        Ok(self.connection.change_count() > 0)
+    }
+}
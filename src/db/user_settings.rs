@@ -0,0 +1,31 @@
+use super::{Result, SheetDB};
+
+impl SheetDB {
+    /// Language code the user picked for the bot's replies
+    pub fn get_user_language(&mut self, user_id: u64) -> Result<Option<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT language FROM user_settings WHERE user_id=:user_id")?;
+        statement.bind((":user_id", user_id as i64))?;
+
+        match statement.next()? {
+            sqlite::State::Row => statement.read::<Option<String>, _>("language"),
+            sqlite::State::Done => Ok(None),
+        }
+    }
+
+    pub fn set_user_language(&mut self, user_id: u64, language: Option<&str>) -> Result<()> {
+        let mut statement = self.connection.prepare("INSERT INTO user_settings (user_id, language) VALUES (:user_id, :language) ON CONFLICT(user_id) DO UPDATE SET language=excluded.language;")?;
+        statement.bind::<&[(&str, sqlite::Value)]>(
+            &[
+                (":user_id", (user_id as i64).into()),
+                (
+                    ":language",
+                    language.map_or(sqlite::Value::Null, |language| language.into()),
+                ),
+            ][..],
+        )?;
+        statement.next()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+//! Pictures of rolled dice, drawn on the CPU and attached to check and roll results.
+//! Without the `dice-images` feature, results are posted without them.
+
+#[cfg(feature = "dice-images")]
+mod render;
+
+use poise::serenity_prelude::CreateAttachment;
+
+use crate::config::DiceTheme;
+
+/// Name of the attachment, for referring to it from embeds
+pub const FILE_NAME: &str = "dice.png";
+
+/// A rolled die
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(not(feature = "dice-images"), allow(dead_code))]
+pub struct Die {
+    pub sides: u32,
+    pub value: u32,
+    /// Whether the die is subtracted from the total
+    pub negative: bool,
+}
+
+/// Picture of the dice as an attachment named [`FILE_NAME`], if there are any to draw
+#[cfg(feature = "dice-images")]
+pub fn attachment(dice: &[Die], theme: &DiceTheme) -> Option<CreateAttachment> {
+    let png = render::render(dice, theme)?;
+    Some(CreateAttachment::bytes(png, FILE_NAME))
+}
+
+#[cfg(not(feature = "dice-images"))]
+pub fn attachment(_dice: &[Die], _theme: &DiceTheme) -> Option<CreateAttachment> {
+    None
+}
@@ -0,0 +1,177 @@
+//! d4s are drawn as triangles, every other die as a rounded square, filled with the color of
+//! the theme for dice that add to or subtract from the total. The results are drawn as
+//! seven-segment digits, so no font has to be shipped.
+
+use tiny_skia::{FillRule, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+
+use super::Die;
+use crate::config::DiceTheme;
+
+/// Edge length of a die in pixels
+const SIZE: f32 = 96.0;
+/// Space around and between the dice in pixels
+const GAP: f32 = 12.0;
+const OUTLINE: f32 = 4.0;
+const DIGIT_WIDTH: f32 = 18.0;
+const DIGIT_HEIGHT: f32 = 32.0;
+const DIGIT_SPACING: f32 = 5.0;
+/// Thickness of a segment of a digit
+const SEGMENT: f32 = 5.0;
+
+/// Segments lit for each digit, as bits of `abcdefg` from the top clockwise and the middle
+const DIGITS: [u8; 10] = [
+    0b1111110, 0b0110000, 0b1101101, 0b1111001, 0b0110011, 0b1011011, 0b1011111, 0b1110000,
+    0b1111111, 0b1111011,
+];
+
+/// Draws the dice side by side into a PNG, or returns `None` if there are no dice
+pub fn render(dice: &[Die], theme: &DiceTheme) -> Option<Vec<u8>> {
+    if dice.is_empty() {
+        return None;
+    }
+    let width = dice.len() as f32 * (SIZE + GAP) + GAP;
+    let mut pixmap = Pixmap::new(width as u32, (SIZE + 2.0 * GAP) as u32)?;
+    for (i, die) in dice.iter().enumerate() {
+        draw_die(&mut pixmap, GAP + i as f32 * (SIZE + GAP), GAP, die, theme);
+    }
+    match pixmap.encode_png() {
+        Ok(png) => Some(png),
+        Err(err) => {
+            tracing::warn!("Failed encoding dice image: {}", err);
+            None
+        }
+    }
+}
+
+fn paint(rgb: u32, shade: f32) -> Paint<'static> {
+    let channel = |shift: u32| (((rgb >> shift) & 0xff) as f32 * shade) as u8;
+    let mut paint = Paint::default();
+    paint.set_color_rgba8(channel(16), channel(8), channel(0), 255);
+    paint.anti_alias = true;
+    paint
+}
+
+fn draw_die(pixmap: &mut Pixmap, x: f32, y: f32, die: &Die, theme: &DiceTheme) {
+    let color = if die.negative {
+        theme.negative
+    } else {
+        theme.positive
+    };
+    let inset = OUTLINE;
+    let (left, top, right, bottom) = (x + inset, y + inset, x + SIZE - inset, y + SIZE - inset);
+    let mut builder = PathBuilder::new();
+    // The number sits lower in a triangle, where it is wider
+    let number_center = if die.sides == 4 {
+        builder.move_to(x + SIZE / 2.0, top);
+        builder.line_to(right, bottom);
+        builder.line_to(left, bottom);
+        y + SIZE * 0.62
+    } else {
+        let radius = 14.0;
+        builder.move_to(left + radius, top);
+        builder.line_to(right - radius, top);
+        builder.quad_to(right, top, right, top + radius);
+        builder.line_to(right, bottom - radius);
+        builder.quad_to(right, bottom, right - radius, bottom);
+        builder.line_to(left + radius, bottom);
+        builder.quad_to(left, bottom, left, bottom - radius);
+        builder.line_to(left, top + radius);
+        builder.quad_to(left, top, left + radius, top);
+        y + SIZE / 2.0
+    };
+    builder.close();
+    let Some(path) = builder.finish() else {
+        return;
+    };
+    pixmap.fill_path(
+        &path,
+        &paint(color, 1.0),
+        FillRule::Winding,
+        Transform::identity(),
+        None,
+    );
+    let stroke = Stroke {
+        width: OUTLINE,
+        ..Default::default()
+    };
+    pixmap.stroke_path(
+        &path,
+        &paint(color, 0.6),
+        &stroke,
+        Transform::identity(),
+        None,
+    );
+
+    let digits = die.value.to_string();
+    let width = digits.len() as f32 * (DIGIT_WIDTH + DIGIT_SPACING) - DIGIT_SPACING;
+    let mut digit_x = x + (SIZE - width) / 2.0;
+    for digit in digits.bytes() {
+        draw_digit(
+            pixmap,
+            digit_x,
+            number_center - DIGIT_HEIGHT / 2.0,
+            DIGITS[(digit - b'0') as usize],
+        );
+        digit_x += DIGIT_WIDTH + DIGIT_SPACING;
+    }
+}
+
+fn draw_digit(pixmap: &mut Pixmap, x: f32, y: f32, segments: u8) {
+    let (w, h, t) = (DIGIT_WIDTH, DIGIT_HEIGHT, SEGMENT);
+    let half = (h - t) / 2.0;
+    // a to g, as x, y, width and height
+    let rects = [
+        (x, y, w, t),
+        (x + w - t, y, t, half + t),
+        (x + w - t, y + half, t, half + t),
+        (x, y + h - t, w, t),
+        (x, y + half, t, half + t),
+        (x, y, t, half + t),
+        (x, y + half, w, t),
+    ];
+    let white = paint(0xff_ff_ff, 1.0);
+    for (i, (x, y, w, h)) in rects.into_iter().enumerate() {
+        if segments & (0b1000000 >> i) == 0 {
+            continue;
+        }
+        if let Some(rect) = Rect::from_xywh(x, y, w, h) {
+            pixmap.fill_rect(rect, &white, Transform::identity(), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dice_are_drawn_in_their_theme_colors() {
+        let theme = DiceTheme {
+            positive: 0x00_80_00,
+            negative: 0x80_00_00,
+        };
+        let dice = [
+            Die {
+                sides: 4,
+                value: 3,
+                negative: false,
+            },
+            Die {
+                sides: 20,
+                value: 17,
+                negative: true,
+            },
+        ];
+        assert!(render(&[], &theme).is_none());
+        let pixmap = Pixmap::decode_png(&render(&dice, &theme).unwrap()).unwrap();
+        assert_eq!(pixmap.width(), (2.0 * SIZE + 3.0 * GAP) as u32);
+
+        // Below the number, inside the die
+        let color_at = |x: f32| {
+            let pixel = pixmap.pixel(x as u32, (GAP + SIZE - 16.0) as u32).unwrap();
+            (pixel.red(), pixel.green(), pixel.blue())
+        };
+        assert_eq!(color_at(GAP + SIZE / 2.0), (0, 0x80, 0));
+        assert_eq!(color_at(2.0 * GAP + 1.5 * SIZE), (0x80, 0, 0));
+    }
+}
@@ -0,0 +1,333 @@
+extern crate google_sheets4 as sheets4;
+
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+use poise::serenity_prelude as serenity;
+use sheets4::client;
+
+use crate::i18n::{tr, Message, Tr};
+use crate::permissions::Level;
+use crate::{db, get_ability_value};
+
+/// Error type shared by all commands of the bot
+#[derive(Debug)]
+pub enum Error {
+    /// The configuration is incomplete or unusable
+    Config(String),
+    /// A value could not be parsed
+    Parse(String),
+    /// The Google Sheets API failed
+    Sheets(Box<client::Error>),
+    /// Looking up an ability on a character sheet failed
+    Ability(Box<get_ability_value::Error>),
+    /// Accessing the DB failed
+    Db(db::Error),
+    /// The LLM backend failed or answered with something unusable
+    Llm(String),
+    /// The LLM backend did not answer in time
+    LlmTimeout(Duration),
+    /// The embedded python interpreter raised an exception
+    #[cfg(feature = "python")]
+    Python(pyo3::PyErr),
+    /// Talking to Discord failed
+    Discord(Box<serenity::Error>),
+    /// The author has not claimed a character in this guild
+    NoClaimedCharacter,
+    /// The character has no sheet in the spreadsheet
+    InvalidCharacter(String),
+    /// The guild has no persona with this name
+    UnknownPersona(String),
+    /// A command argument is out of range, the message explains why
+    InvalidArgument(Message),
+    /// The author lacks a role needed for the command
+    NotAllowed,
+    /// A daily completion quota is used up, identified by the ID of its message
+    QuotaExceeded(&'static str),
+    /// A prompt or response contains a blocked term
+    ContentBlocked,
+    /// The command needs a higher permission level than the author has
+    MissingLevel(Level),
+    /// A player tried to roll for a character they have not claimed
+    NotYourCharacter(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Whether the error is caused by the user's input rather than by the bot or its backends
+    pub fn is_user_error(&self) -> bool {
+        use get_ability_value::Error as AbilityError;
+        match self {
+            Error::NoClaimedCharacter
+            | Error::InvalidCharacter(_)
+            | Error::UnknownPersona(_)
+            | Error::InvalidArgument(_)
+            | Error::NotAllowed
+            | Error::QuotaExceeded(_)
+            | Error::ContentBlocked
+            | Error::MissingLevel(_)
+            | Error::NotYourCharacter(_) => true,
+            Error::Ability(err) => matches!(
+                **err,
+                AbilityError::NoAbilityError(_) | AbilityError::AbilityUniquenessError(_, _)
+            ),
+            _ => false,
+        }
+    }
+
+    /// Message that is safe and helpful to show in Discord, in the reader's language.
+    /// Details of internal errors only go to the logs.
+    pub fn user_message(&self, tr: Tr) -> String {
+        use get_ability_value::Error as AbilityError;
+        match self {
+            Error::NoClaimedCharacter => tr.get("error-no-claimed-character"),
+            Error::InvalidCharacter(name) => {
+                tr!(tr, "error-invalid-character", name = name.as_str())
+            }
+            Error::UnknownPersona(name) => tr!(tr, "error-unknown-persona", name = name.as_str()),
+            Error::InvalidArgument(message) => tr.message(message),
+            Error::NotAllowed => tr.get("error-not-allowed"),
+            Error::QuotaExceeded(quota) => tr.get(quota),
+            Error::ContentBlocked => tr.get("error-content-blocked"),
+            Error::MissingLevel(Level::Admin) => tr.get("error-missing-level-admin"),
+            Error::MissingLevel(_) => tr.get("error-missing-level-gm"),
+            Error::NotYourCharacter(name) => {
+                tr!(tr, "error-not-your-character", name = name.as_str())
+            }
+            Error::Ability(err) => match err.as_ref() {
+                AbilityError::NoAbilityError(ability) => {
+                    tr!(tr, "error-no-ability", ability = ability.as_str())
+                }
+                AbilityError::AbilityUniquenessError(ability, found) => tr!(
+                    tr,
+                    "error-ambiguous-ability",
+                    ability = ability.as_str(),
+                    found = found.join(", ")
+                ),
+                AbilityError::CsvError(_) | AbilityError::CsvAbilityRecordError(_) => {
+                    tr.get("error-sheet-value")
+                }
+                AbilityError::ClientError(_)
+                | AbilityError::TimeoutError(_)
+                | AbilityError::RequestError(_) => tr.get("error-sheets-unreachable"),
+            },
+            Error::Sheets(_) => tr.get("error-sheets-unreachable"),
+            Error::Db(_) => tr.get("error-db"),
+            Error::Llm(_) => tr.get("error-llm"),
+            Error::LlmTimeout(_) => tr.get("error-llm-timeout"),
+            #[cfg(feature = "python")]
+            Error::Python(_) => tr.get("error-llm"),
+            Error::Config(_) | Error::Parse(_) | Error::Discord(_) => tr.get("error-internal"),
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Config(msg) => write!(f, "Configuration error: {}", msg),
+            Error::Parse(msg) => write!(f, "Parse error: {}", msg),
+            Error::Sheets(err) => write!(f, "Sheets API error: {}", err),
+            Error::Ability(err) => write!(f, "Ability lookup error: {}", err),
+            Error::Db(err) => write!(f, "DB error: {}", err),
+            Error::Llm(msg) => write!(f, "LLM error: {}", msg),
+            Error::LlmTimeout(timeout) => write!(f, "LLM timed out after {:?}", timeout),
+            #[cfg(feature = "python")]
+            Error::Python(err) => write!(f, "Python error: {}", err),
+            Error::Discord(err) => write!(f, "Discord error: {}", err),
+            Error::NoClaimedCharacter => write!(f, "No character claimed"),
+            Error::InvalidCharacter(name) => write!(f, "No sheet for character '{}'", name),
+            Error::UnknownPersona(name) => write!(f, "No persona '{}'", name),
+            Error::InvalidArgument(message) => {
+                write!(f, "Invalid argument: {} {:?}", message.id, message.args)
+            }
+            Error::NotAllowed => write!(f, "Missing an allowed role"),
+            Error::QuotaExceeded(quota) => write!(f, "Exceeded the {}", quota),
+            Error::ContentBlocked => write!(f, "Content contains a blocked term"),
+            Error::MissingLevel(level) => write!(f, "Needs {} level", level),
+            Error::NotYourCharacter(name) => write!(f, "Not allowed to roll for '{}'", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<client::Error> for Error {
+    fn from(err: client::Error) -> Self {
+        Error::Sheets(Box::new(err))
+    }
+}
+
+impl From<get_ability_value::Error> for Error {
+    fn from(err: get_ability_value::Error) -> Self {
+        Error::Ability(Box::new(err))
+    }
+}
+
+impl From<db::Error> for Error {
+    fn from(err: db::Error) -> Self {
+        Error::Db(err)
+    }
+}
+
+#[cfg(feature = "python")]
+impl From<pyo3::PyErr> for Error {
+    fn from(err: pyo3::PyErr) -> Self {
+        Error::Python(err)
+    }
+}
+
+impl From<serenity::Error> for Error {
+    fn from(err: serenity::Error) -> Self {
+        Error::Discord(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::i18n::{Language, Translations};
+
+    /// Text only internal errors carry, which must not reach Discord
+    const SECRET: &str = "secret-detail";
+
+    #[test]
+    fn user_messages_only_explain_user_errors() {
+        use get_ability_value::Error as AbilityError;
+        let translations = Translations::load().unwrap();
+        for language in [Language::English, Language::German] {
+            let tr = Tr::new(&translations, language);
+            // Each error with whether it is a user error and the message shown for it
+            #[cfg_attr(not(feature = "python"), allow(unused_mut))]
+            let mut table = vec![
+                (
+                    Error::NoClaimedCharacter,
+                    true,
+                    tr.get("error-no-claimed-character"),
+                ),
+                (
+                    Error::InvalidCharacter("Gandalf".to_owned()),
+                    true,
+                    tr!(tr, "error-invalid-character", name = "Gandalf"),
+                ),
+                (
+                    Error::UnknownPersona("bard".to_owned()),
+                    true,
+                    tr!(tr, "error-unknown-persona", name = "bard"),
+                ),
+                (
+                    Error::InvalidArgument(Message::new("claim-taken").arg("character", "Gandalf")),
+                    true,
+                    tr!(tr, "claim-taken", character = "Gandalf"),
+                ),
+                (Error::NotAllowed, true, tr.get("error-not-allowed")),
+                (
+                    Error::QuotaExceeded("error-quota-user-tokens"),
+                    true,
+                    tr.get("error-quota-user-tokens"),
+                ),
+                (Error::ContentBlocked, true, tr.get("error-content-blocked")),
+                (
+                    Error::MissingLevel(Level::Gm),
+                    true,
+                    tr.get("error-missing-level-gm"),
+                ),
+                (
+                    Error::MissingLevel(Level::Admin),
+                    true,
+                    tr.get("error-missing-level-admin"),
+                ),
+                (
+                    Error::NotYourCharacter("Gandalf".to_owned()),
+                    true,
+                    tr!(tr, "error-not-your-character", name = "Gandalf"),
+                ),
+                (
+                    AbilityError::NoAbilityError("Zaubern".to_owned()).into(),
+                    true,
+                    tr!(tr, "error-no-ability", ability = "Zaubern"),
+                ),
+                (
+                    AbilityError::AbilityUniquenessError(
+                        "st".to_owned(),
+                        vec!["Stärke".to_owned(), "Stehlen".to_owned()],
+                    )
+                    .into(),
+                    true,
+                    tr!(
+                        tr,
+                        "error-ambiguous-ability",
+                        ability = "st",
+                        found = "Stärke, Stehlen"
+                    ),
+                ),
+                (
+                    AbilityError::CsvAbilityRecordError(csv::StringRecord::from(vec![SECRET]))
+                        .into(),
+                    false,
+                    tr.get("error-sheet-value"),
+                ),
+                (
+                    AbilityError::ClientError(client::Error::FieldClash(SECRET)).into(),
+                    false,
+                    tr.get("error-sheets-unreachable"),
+                ),
+                (
+                    AbilityError::TimeoutError(Duration::from_secs(5)).into(),
+                    false,
+                    tr.get("error-sheets-unreachable"),
+                ),
+                (
+                    client::Error::FieldClash(SECRET).into(),
+                    false,
+                    tr.get("error-sheets-unreachable"),
+                ),
+                (
+                    db::Error {
+                        code: Some(1),
+                        message: Some(SECRET.to_owned()),
+                    }
+                    .into(),
+                    false,
+                    tr.get("error-db"),
+                ),
+                (Error::Llm(SECRET.to_owned()), false, tr.get("error-llm")),
+                (
+                    Error::LlmTimeout(Duration::from_secs(30)),
+                    false,
+                    tr.get("error-llm-timeout"),
+                ),
+                (
+                    Error::Config(SECRET.to_owned()),
+                    false,
+                    tr.get("error-internal"),
+                ),
+                (
+                    Error::Parse(SECRET.to_owned()),
+                    false,
+                    tr.get("error-internal"),
+                ),
+                (
+                    serenity::Error::Other(SECRET).into(),
+                    false,
+                    tr.get("error-internal"),
+                ),
+            ];
+            #[cfg(feature = "python")]
+            table.push((
+                pyo3::exceptions::PyValueError::new_err(SECRET).into(),
+                false,
+                tr.get("error-llm"),
+            ));
+
+            for (err, is_user_error, message) in table {
+                assert_eq!(err.is_user_error(), is_user_error, "{}", err);
+                assert_eq!(err.user_message(tr), message, "{}", err);
+                if !is_user_error {
+                    assert!(!err.user_message(tr).contains(SECRET), "{}", err);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Learn-by-doing advancement: the abilities of checks rolled with `mark` are recorded per
+//! character and session, until the GM lets the character advance.
+
+use crate::db::{ExperienceMark, SheetDB};
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message};
+use crate::reply;
+use crate::sheets::{a1_range, assert_character_name, read_values, write_cells};
+use crate::PoiseContext;
+
+/// Column of the ability values on a character sheet
+const VALUE_COLUMN: usize = 6;
+
+/// An ability to raise by one on the character sheet
+#[derive(Debug, PartialEq)]
+struct Improvement {
+    ability: String,
+    /// 1-based row of the ability on the sheet
+    row: usize,
+    value: u8,
+}
+
+/// Marks the abilities of a check in the channel's running session, failing if there is none
+pub fn mark_abilities(
+    sheet_db: &mut SheetDB,
+    guild_id: u64,
+    channel_id: u64,
+    character_name: &str,
+    abilities: &[(String, u8)],
+) -> Result<(), Error> {
+    let abilities: Vec<&str> = abilities.iter().map(|(name, _)| name.as_str()).collect();
+    if sheet_db.mark_abilities(guild_id, channel_id, character_name, &abilities)? {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(Message::new("marks-no-session")))
+    }
+}
+
+/// Finds the rows of the marked abilities in the columns A to G of a character sheet.
+/// Abilities that are missing or have no numeric value are left out.
+fn improvements(rows: &[Vec<String>], marks: &[ExperienceMark]) -> Vec<Improvement> {
+    marks
+        .iter()
+        .filter_map(|mark| {
+            let (index, row) = rows
+                .iter()
+                .enumerate()
+                .find(|(_, row)| row.first() == Some(&mark.ability))?;
+            Some(Improvement {
+                ability: mark.ability.clone(),
+                row: index + 1,
+                value: row.get(VALUE_COLUMN)?.trim().parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Marks matching the given ability, by its full name or the start of it
+fn matching_marks(marks: Vec<ExperienceMark>, ability: &str) -> Vec<ExperienceMark> {
+    let ability = ability.to_lowercase();
+    if let Some(exact) = marks
+        .iter()
+        .find(|mark| mark.ability.to_lowercase() == ability)
+    {
+        return vec![exact.clone()];
+    }
+    marks
+        .into_iter()
+        .filter(|mark| mark.ability.to_lowercase().starts_with(&ability))
+        .collect()
+}
+
+/// Show the abilities a character marked for advancement
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn marks(
+    ctx: PoiseContext<'_>,
+    #[description = "Character to show, defaults to your claimed one"] character_name: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let character_name = match character_name {
+        Some(character_name) => character_name,
+        None => ctx
+            .data()
+            .sheet_db()
+            .get_sheet(guild_id, ctx.author().id.get())?
+            .ok_or(Error::NoClaimedCharacter)?,
+    };
+    let marks = ctx
+        .data()
+        .sheet_db()
+        .list_marks(guild_id, &character_name)?;
+
+    let tr = translator(ctx);
+    if marks.is_empty() {
+        return reply::say(
+            ctx,
+            tr!(tr, "marks-none", character = character_name.as_str()),
+        )
+        .await;
+    }
+    let mut text = tr!(tr, "marks-heading", character = character_name.as_str());
+    for mark in marks {
+        text.push_str("\n- ");
+        text.push_str(&tr!(
+            tr,
+            "marks-entry",
+            ability = mark.ability,
+            sessions = mark.sessions,
+            checks = mark.checks
+        ));
+    }
+    reply::say(ctx, text).await
+}
+
+/// Clear the marks of a character, optionally raising the marked abilities on their sheet
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    check = "crate::permissions::gm"
+)]
+pub async fn advance(
+    ctx: PoiseContext<'_>,
+    #[description = "Character advancing"] character_name: String,
+    #[description = "Only clear this ability, leave empty for all marked abilities"]
+    ability: Option<String>,
+    #[description = "Raise each cleared ability by one on the sheet, defaults to no"]
+    improve: Option<bool>,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+    let spreadsheet_id = &data.config.character_spreadsheet_id;
+    assert_character_name(&data.sheets, spreadsheet_id, &character_name).await?;
+
+    let mut marks = data.sheet_db().list_marks(guild_id, &character_name)?;
+    if let Some(ability) = &ability {
+        marks = matching_marks(marks, ability);
+        if marks.len() > 1 {
+            let names: Vec<&str> = marks.iter().map(|mark| mark.ability.as_str()).collect();
+            return Err(Error::InvalidArgument(
+                Message::new("marks-ambiguous")
+                    .arg("ability", ability)
+                    .arg("names", names.join(", ")),
+            ));
+        }
+    }
+    if marks.is_empty() {
+        return Err(Error::InvalidArgument(
+            Message::new("marks-none").arg("character", &character_name),
+        ));
+    }
+
+    // Write to the sheet first, so the marks are kept if that fails
+    let improved = if improve.unwrap_or(false) {
+        let rows = read_values(
+            &data.sheets,
+            spreadsheet_id,
+            &a1_range(&character_name, "A:G"),
+        )
+        .await?;
+        let improved = improvements(&rows, &marks);
+        let cells = improved
+            .iter()
+            .map(|improvement| {
+                (
+                    a1_range(&character_name, &format!("G{}", improvement.row)),
+                    (improvement.value.saturating_add(1)).to_string(),
+                )
+            })
+            .collect();
+        write_cells(&data.sheets, spreadsheet_id, cells).await?;
+        improved
+    } else {
+        Vec::new()
+    };
+
+    data.sheet_db().transaction(|sheet_db| {
+        marks
+            .iter()
+            .try_for_each(|mark| sheet_db.clear_marks(guild_id, &character_name, &mark.ability))
+    })?;
+
+    let tr = translator(ctx);
+    let names: Vec<&str> = marks.iter().map(|mark| mark.ability.as_str()).collect();
+    let mut text = tr!(
+        tr,
+        "marks-cleared",
+        character = character_name.as_str(),
+        abilities = names.join(", ")
+    );
+    for improvement in improved {
+        text.push_str("\n- ");
+        text.push_str(&tr!(
+            tr,
+            "marks-improved",
+            ability = improvement.ability,
+            old = improvement.value,
+            new = improvement.value.saturating_add(1)
+        ));
+    }
+    reply::say(ctx, text).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockGoogle, SPREADSHEET_ID};
+
+    fn mark(ability: &str, sessions: i64, checks: i64) -> ExperienceMark {
+        ExperienceMark {
+            ability: ability.to_owned(),
+            sessions,
+            checks,
+        }
+    }
+
+    #[test]
+    fn abilities_are_only_marked_in_a_running_session() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        assert!(!sheet_db
+            .mark_abilities(1, 2, "Gandalf", &["Zaubern"])
+            .unwrap());
+        assert!(sheet_db.list_marks(1, "Gandalf").unwrap().is_empty());
+
+        sheet_db.start_session(1, 2, false).unwrap();
+        assert!(sheet_db
+            .mark_abilities(1, 2, "Gandalf", &["Zaubern"])
+            .unwrap());
+        assert_eq!(
+            sheet_db.list_marks(1, "Gandalf").unwrap(),
+            vec![mark("Zaubern", 1, 1)]
+        );
+    }
+
+    #[test]
+    fn marks_count_sessions_and_checks() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        for _ in 0..2 {
+            sheet_db.start_session(1, 2, false).unwrap();
+            sheet_db
+                .mark_abilities(1, 2, "Gandalf", &["Zaubern", "Charisma"])
+                .unwrap();
+            sheet_db.end_session(1, 2).unwrap();
+        }
+        sheet_db.start_session(1, 2, false).unwrap();
+        for _ in 0..2 {
+            sheet_db
+                .mark_abilities(1, 2, "Gandalf", &["Zaubern"])
+                .unwrap();
+        }
+
+        assert_eq!(
+            sheet_db.list_marks(1, "Gandalf").unwrap(),
+            vec![mark("Zaubern", 3, 4), mark("Charisma", 2, 2)]
+        );
+        assert!(sheet_db.list_marks(1, "Bilbo").unwrap().is_empty());
+    }
+
+    #[test]
+    fn clearing_an_ability_keeps_the_other_marks() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        sheet_db.start_session(1, 2, false).unwrap();
+        sheet_db
+            .mark_abilities(1, 2, "Gandalf", &["Zaubern", "Charisma"])
+            .unwrap();
+
+        sheet_db.clear_marks(1, "Gandalf", "Charisma").unwrap();
+        assert_eq!(
+            sheet_db.list_marks(1, "Gandalf").unwrap(),
+            vec![mark("Zaubern", 1, 1)]
+        );
+    }
+
+    #[test]
+    fn marks_match_by_full_name_before_prefix() {
+        let marks = vec![mark("Stärke", 1, 1), mark("Stärkere Magie", 1, 1)];
+        assert_eq!(
+            matching_marks(marks.clone(), "stärke"),
+            vec![mark("Stärke", 1, 1)]
+        );
+        assert_eq!(matching_marks(marks.clone(), "stä").len(), 2);
+        assert!(matching_marks(marks, "zau").is_empty());
+    }
+
+    #[tokio::test]
+    async fn improvements_are_found_on_the_sheet() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+        let rows = read_values(&data.sheets, SPREADSHEET_ID, &a1_range("Gandalf", "A:G"))
+            .await
+            .unwrap();
+        let marks = vec![
+            mark("Zaubern", 3, 3),
+            mark("Charisma", 2, 2),
+            mark("Fliegen", 1, 1),
+        ];
+        assert_eq!(
+            improvements(&rows, &marks),
+            vec![
+                Improvement {
+                    ability: "Zaubern".to_owned(),
+                    row: 5,
+                    value: 5
+                },
+                Improvement {
+                    ability: "Charisma".to_owned(),
+                    row: 1,
+                    value: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn no_fate_point_is_spent_when_marking_fails() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        sheet_db.reset_fate_points(1, Some("Gandalf"), 1).unwrap();
+        let abilities = vec![("Zaubern".to_owned(), 5)];
+
+        // Without a running session, nothing can be marked
+        let result = sheet_db.transaction(|sheet_db| {
+            crate::fate::spend_for_check(sheet_db, 1, "Gandalf")?;
+            mark_abilities(sheet_db, 1, 2, "Gandalf", &abilities)
+        });
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+        assert_eq!(sheet_db.get_fate_points(1, "Gandalf").unwrap(), 1);
+    }
+}
@@ -0,0 +1,275 @@
+//! Fate points: a per-character resource that can cancel the negative d4 of a check.
+//!
+//! Every grant, spend and reset is a row in a ledger, so the points of a character are the sum
+//! of its ledger and its history can be shown.
+
+use crate::db::SheetDB;
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message};
+use crate::permissions;
+use crate::reply;
+use crate::sheets::assert_character_name;
+use crate::PoiseContext;
+
+/// Number of ledger entries shown for a single character
+const HISTORY_ENTRIES: usize = 5;
+
+/// What a fate point spent on a check does to the negative d4
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum FateUse {
+    /// The negative d4 does not count
+    #[name = "drop"]
+    Drop,
+    /// The negative d4 is rolled again
+    #[name = "reroll"]
+    Reroll,
+}
+
+/// Spends a fate point of the character for a check, failing if it has none left
+pub fn spend_for_check(
+    sheet_db: &mut SheetDB,
+    guild_id: u64,
+    character_name: &str,
+) -> Result<(), Error> {
+    if sheet_db.spend_fate_point(guild_id, character_name, "check")? {
+        Ok(())
+    } else {
+        Err(Error::InvalidArgument(
+            Message::new("fate-none-left").arg("character", character_name),
+        ))
+    }
+}
+
+/// The given character, or the author's claimed one
+fn character_or_claimed(
+    ctx: PoiseContext<'_>,
+    character_name: Option<String>,
+) -> Result<String, Error> {
+    match character_name {
+        Some(character_name) => Ok(character_name),
+        None => ctx
+            .data()
+            .sheet_db()
+            .get_sheet(ctx.guild_id().unwrap().get(), ctx.author().id.get())?
+            .ok_or(Error::NoClaimedCharacter),
+    }
+}
+
+async fn ensure_character(ctx: PoiseContext<'_>, character_name: &str) -> Result<(), Error> {
+    let data = ctx.data();
+    assert_character_name(
+        &data.sheets,
+        &data.config.character_spreadsheet_id,
+        character_name,
+    )
+    .await
+}
+
+/// Grant, spend and view the fate points of characters
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("fate_show", "fate_grant", "fate_spend", "fate_reset"),
+    subcommand_required
+)]
+pub async fn fate(_ctx: PoiseContext<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Show the fate points of a character and their latest changes, or of all characters
+#[poise::command(prefix_command, slash_command, guild_only, rename = "show")]
+async fn fate_show(
+    ctx: PoiseContext<'_>,
+    #[description = "Character to show, leave empty for all characters"] character_name: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let tr = translator(ctx);
+    let Some(character_name) = character_name else {
+        let points = ctx.data().sheet_db().list_fate_points(guild_id)?;
+        if points.is_empty() {
+            return reply::say(ctx, tr.get("fate-none-yet")).await;
+        }
+        let lines: Vec<String> = points
+            .iter()
+            .map(|(character, points)| {
+                format!(
+                    "- {}",
+                    tr!(
+                        tr,
+                        "fate-points",
+                        character = character.as_str(),
+                        points = *points
+                    )
+                )
+            })
+            .collect();
+        return reply::say(ctx, lines.join("\n")).await;
+    };
+
+    let (points, history) = {
+        let mut sheet_db = ctx.data().sheet_db();
+        (
+            sheet_db.get_fate_points(guild_id, &character_name)?,
+            sheet_db.get_fate_history(guild_id, &character_name, HISTORY_ENTRIES)?,
+        )
+    };
+    let mut text = tr!(
+        tr,
+        "fate-points",
+        character = character_name.as_str(),
+        points = points
+    );
+    for entry in history {
+        text.push_str(&format!(
+            "\n- `{}` {:+} {}",
+            entry.created_at,
+            entry.change,
+            tr.get(&format!("fate-reason-{}", entry.reason))
+        ));
+    }
+    reply::say(ctx, text).await
+}
+
+/// Grant fate points to a character
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "grant",
+    check = "crate::permissions::gm"
+)]
+async fn fate_grant(
+    ctx: PoiseContext<'_>,
+    #[description = "Character to grant the points to"] character_name: String,
+    #[description = "Number of points, defaults to 1"] points: Option<i64>,
+) -> Result<(), Error> {
+    let points = points.unwrap_or(1);
+    if points < 1 {
+        return Err(Error::InvalidArgument(Message::new("fate-invalid-grant")));
+    }
+    ensure_character(ctx, &character_name).await?;
+    let guild_id = ctx.guild_id().unwrap().get();
+    let total =
+        ctx.data()
+            .sheet_db()
+            .add_fate_points(guild_id, &character_name, points, "grant")?;
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr!(
+            tr,
+            "fate-points",
+            character = character_name.as_str(),
+            points = total
+        ),
+    )
+    .await
+}
+
+/// Spend a fate point outside of a check
+#[poise::command(prefix_command, slash_command, guild_only, rename = "spend")]
+async fn fate_spend(
+    ctx: PoiseContext<'_>,
+    #[description = "Character spending the point, defaults to your claimed one"]
+    character_name: Option<String>,
+) -> Result<(), Error> {
+    let character_name = character_or_claimed(ctx, character_name)?;
+    permissions::ensure_may_roll_for(ctx, &character_name).await?;
+    let guild_id = ctx.guild_id().unwrap().get();
+    let points = {
+        let mut sheet_db = ctx.data().sheet_db();
+        if !sheet_db.spend_fate_point(guild_id, &character_name, "spend")? {
+            return Err(Error::InvalidArgument(
+                Message::new("fate-none-left").arg("character", &character_name),
+            ));
+        }
+        sheet_db.get_fate_points(guild_id, &character_name)?
+    };
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr!(
+            tr,
+            "fate-points",
+            character = character_name.as_str(),
+            points = points
+        ),
+    )
+    .await
+}
+
+/// Set the fate points of a character, or of all characters, e.g. at the start of a session
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "reset",
+    check = "crate::permissions::gm"
+)]
+async fn fate_reset(
+    ctx: PoiseContext<'_>,
+    #[description = "Points every character starts with"] points: i64,
+    #[description = "Character to reset, leave empty for all claimed characters"]
+    character_name: Option<String>,
+) -> Result<(), Error> {
+    if points < 0 {
+        return Err(Error::InvalidArgument(Message::new("fate-invalid-reset")));
+    }
+    if let Some(character_name) = &character_name {
+        ensure_character(ctx, character_name).await?;
+    }
+    let guild_id = ctx.guild_id().unwrap().get();
+    ctx.data()
+        .sheet_db()
+        .reset_fate_points(guild_id, character_name.as_deref(), points)?;
+    let tr = translator(ctx);
+    let text = match character_name {
+        Some(character_name) => tr!(
+            tr,
+            "fate-points",
+            character = character_name,
+            points = points
+        ),
+        None => tr!(tr, "fate-reset-all", points = points),
+    };
+    reply::say(ctx, text).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::SheetDB;
+
+    #[test]
+    fn ledger_spends_only_available_points() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        sheet_db.store_sheet(1, 10, "Gandalf").unwrap();
+        assert!(!sheet_db.spend_fate_point(1, "Gandalf", "check").unwrap());
+
+        assert_eq!(
+            sheet_db.add_fate_points(1, "Gandalf", 2, "grant").unwrap(),
+            2
+        );
+        assert!(sheet_db.spend_fate_point(1, "Gandalf", "check").unwrap());
+        assert!(sheet_db.spend_fate_point(1, "Gandalf", "spend").unwrap());
+        assert!(!sheet_db.spend_fate_point(1, "Gandalf", "check").unwrap());
+        assert_eq!(sheet_db.get_fate_points(1, "Gandalf").unwrap(), 0);
+
+        // Resetting all covers claimed characters without a ledger as well
+        sheet_db.store_sheet(1, 11, "Bilbo").unwrap();
+        assert_eq!(sheet_db.reset_fate_points(1, None, 3).unwrap(), 2);
+        assert_eq!(sheet_db.reset_fate_points(1, Some("Bilbo"), 3).unwrap(), 0);
+        assert_eq!(
+            sheet_db.list_fate_points(1).unwrap(),
+            vec![("Bilbo".to_owned(), 3), ("Gandalf".to_owned(), 3)]
+        );
+        let history = sheet_db.get_fate_history(1, "Gandalf", 2).unwrap();
+        assert_eq!(
+            history.iter().map(|entry| entry.change).collect::<Vec<_>>(),
+            vec![3, -1]
+        );
+        assert_eq!(history[0].reason, "reset");
+    }
+}
@@ -2,14 +2,16 @@ extern crate google_sheets4 as sheets4;
 
 use std::fmt::{self, Display};
 
-use sheets4::client::DefaultDelegate;
-use sheets4::{api, client, hyper, Delegate, Sheets};
+use sheets4::{api, client, hyper, Sheets};
 
-use hyper::Uri;
+use hyper::{StatusCode, Uri};
 use std::error::Error as StdError;
+use std::time::Duration;
 use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::{sleep, timeout};
 
-use std::thread::sleep;
+use crate::config::GvizConfig;
+use crate::retry::RetryPolicy;
 
 #[derive(Debug)]
 #[allow(clippy::enum_variant_names)]
@@ -24,6 +26,10 @@ pub enum Error {
     NoAbilityError(String),
     /// Multiple abilities that could fit were found
     AbilityUniquenessError(String, Vec<String>),
+    /// The request including all retries did not finish in time
+    TimeoutError(Duration),
+    /// The request could not be built, e.g. from an invalid base URL
+    RequestError(hyper::http::Error),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -40,14 +46,19 @@ impl Display for Error {
             Error::AbilityUniquenessError(ref ability, ref found) => {
                 writeln!(f, "Multiple abilities {:?} match {}.", found, ability)
             }
+            Error::TimeoutError(ref duration) => {
+                writeln!(f, "Request timed out after {:?}", duration)
+            }
+            Error::RequestError(ref err) => err.fmt(f),
         }
     }
 }
 
-use hyper::header::{AUTHORIZATION, USER_AGENT};
+use hyper::header::{AUTHORIZATION, RETRY_AFTER, USER_AGENT};
 
 pub async fn get_ability_value<S>(
     hub: &Sheets<S>,
+    gviz: &GvizConfig,
     spreadsheet_id: &str,
     character_name: &str,
     ability: &str,
@@ -60,9 +71,8 @@ where
     S::Error: Into<Box<dyn StdError + Send + Sync>>,
 {
     const LIMIT: usize = 3;
-    let csv_string = get_ability_value_csv(hub, spreadsheet_id, character_name, ability, LIMIT)
-        .await
-        .map_err(Error::ClientError)?;
+    let csv_string =
+        get_ability_value_csv(hub, gviz, spreadsheet_id, character_name, ability, LIMIT).await?;
 
     let mut reader = csv::ReaderBuilder::new()
         .has_headers(false)
@@ -95,11 +105,12 @@ where
 
 async fn get_ability_value_csv<S>(
     hub: &Sheets<S>,
+    gviz: &GvizConfig,
     spreadsheet_id: &str,
     character_name: &str,
     ability: &str,
     limit: usize,
-) -> client::Result<String>
+) -> Result<String>
 where
     S: tower_service::Service<Uri> + Clone + Send + Sync + 'static,
     S::Response:
@@ -107,79 +118,236 @@ where
     S::Future: Send + Unpin + 'static,
     S::Error: Into<Box<dyn StdError + Send + Sync>>,
 {
-    let uri: String = format!("https://docs.google.com/spreadsheets/d/{}/gviz/tq?tq=select+A+,+G+where+lower(A)+starts+with+'{}'+limit+{}&sheet={}&tqx=out:csv", spreadsheet_id, ability.to_lowercase(), &limit, character_name);
-    let mut dlg = DefaultDelegate;
-    dlg.begin(client::MethodInfo {
-        id: "sheets.spreadsheets.get",
-        http_method: hyper::Method::GET,
-    });
+    let query = format!(
+        "select A, G where lower(A) starts with {} limit {}",
+        string_literal(&ability.to_lowercase()),
+        limit
+    );
+    let uri: String = format!(
+        "{}spreadsheets/d/{}/gviz/tq?tq={}&sheet={}&tqx=out:csv",
+        gviz.base_url,
+        spreadsheet_id,
+        percent_encode(&query),
+        percent_encode(character_name)
+    );
 
+    let retry = &gviz.retry;
+    timeout(retry.timeout, request_with_retry(hub, &uri, retry))
+        .await
+        .unwrap_or(Err(Error::TimeoutError(retry.timeout)))
+}
+
+async fn request_with_retry<S>(hub: &Sheets<S>, uri: &str, retry: &RetryPolicy) -> Result<String>
+where
+    S: tower_service::Service<Uri> + Clone + Send + Sync + 'static,
+    S::Response:
+        hyper::client::connect::Connection + AsyncRead + AsyncWrite + Send + Unpin + 'static,
+    S::Future: Send + Unpin + 'static,
+    S::Error: Into<Box<dyn StdError + Send + Sync>>,
+{
+    let mut attempt = 0;
     loop {
-        let token = match hub
+        attempt += 1;
+        let token = hub
             .auth
             .token(&[api::Scope::DriveReadonly.as_ref().to_string()])
             .await
-        {
-            Ok(token) => token.clone(),
-            Err(err) => match dlg.token(&err) {
-                Some(token) => token,
-                None => {
-                    dlg.finished(false);
-                    return Err(client::Error::MissingToken(err));
-                }
-            },
-        };
-        let req_result = {
-            let client = &hub.client;
-            dlg.pre_request();
-            let req_builder = hyper::Request::builder()
-                .method(hyper::Method::GET)
-                .uri(uri.clone())
-                .header(USER_AGENT, "google-api-rust-client/4.0.1".to_string())
-                .header(AUTHORIZATION, format!("Bearer {}", token.as_str()));
-
-            let request = req_builder.body(hyper::body::Body::empty());
-
-            client.request(request.unwrap()).await
-        };
+            .map_err(|err| Error::ClientError(client::Error::MissingToken(err)))?;
 
-        match req_result {
+        let request = hyper::Request::builder()
+            .method(hyper::Method::GET)
+            .uri(uri)
+            .header(USER_AGENT, "google-api-rust-client/4.0.1".to_string())
+            .header(AUTHORIZATION, format!("Bearer {}", token.as_str()))
+            .body(hyper::body::Body::empty())
+            .map_err(Error::RequestError)?;
+
+        let mut res = match hub.client.request(request).await {
+            Ok(res) => res,
             Err(err) => {
-                if let client::Retry::After(d) = dlg.http_error(&err) {
-                    sleep(d);
+                if retry.should_retry(attempt) {
+                    sleep(retry.backoff(attempt)).await;
                     continue;
                 }
-                dlg.finished(false);
-                return Err(client::Error::HttpError(err));
+                return Err(Error::ClientError(client::Error::HttpError(err)));
             }
-            Ok(mut res) => {
-                let res_body_string = client::get_body_as_string(res.body_mut()).await;
-                if !res.status().is_success() {
-                    let (parts, _) = res.into_parts();
-                    let body = hyper::Body::from(res_body_string.clone());
-                    let restored_response = hyper::Response::from_parts(parts, body);
-
-                    let server_response =
-                        serde_json::from_str::<serde_json::Value>(&res_body_string).ok();
-
-                    if let client::Retry::After(d) =
-                        dlg.http_failure(&restored_response, server_response.clone())
-                    {
-                        sleep(d);
-                        continue;
-                    }
-
-                    dlg.finished(false);
-
-                    return match server_response {
-                        Some(error_value) => Err(client::Error::BadRequest(error_value)),
-                        None => Err(client::Error::Failure(restored_response)),
-                    };
-                }
+        };
+
+        let status = res.status();
+        if is_transient(status) && retry.should_retry(attempt) {
+            // Prefer the delay requested by the server over our own backoff
+            let delay = retry_after(&res).unwrap_or_else(|| retry.backoff(attempt));
+            sleep(delay.min(retry.max_delay)).await;
+            continue;
+        }
+
+        let res_body_string = client::get_body_as_string(res.body_mut()).await;
+        if !status.is_success() {
+            let (parts, _) = res.into_parts();
+            let body = hyper::Body::from(res_body_string.clone());
+            let restored_response = hyper::Response::from_parts(parts, body);
+
+            return Err(Error::ClientError(
+                match serde_json::from_str::<serde_json::Value>(&res_body_string) {
+                    Ok(error_value) => client::Error::BadRequest(error_value),
+                    Err(_) => client::Error::Failure(restored_response),
+                },
+            ));
+        }
+
+        return Ok(res_body_string);
+    }
+}
 
-                dlg.finished(true);
-                return Ok(res_body_string);
+/// Quotes a string for the query language. It has no escape sequences, so strings containing
+/// single quotes are put in double quotes instead, dropping any double quotes from them.
+fn string_literal(value: &str) -> String {
+    if value.contains('\'') {
+        format!("\"{}\"", value.replace('"', ""))
+    } else {
+        format!("'{}'", value)
+    }
+}
+
+/// Percent-encodes a query parameter, keeping only unreserved characters as they are
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
             }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
         }
     }
+    encoded
+}
+
+/// Responses that are worth retrying: rate limiting and server-side errors
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parses the `Retry-After` header, if it specifies a delay in seconds
+fn retry_after<T>(res: &hyper::Response<T>) -> Option<Duration> {
+    res.headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockGoogle, SPREADSHEET_ID};
+
+    #[tokio::test]
+    async fn abilities_and_sheets_are_encoded_in_queries() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+        let gviz = &data.config.gviz;
+
+        let value = get_ability_value(&data.sheets, gviz, SPREADSHEET_ID, "Gandalf", "stär").await;
+        assert_eq!(value.unwrap(), ("Stärke".to_owned(), 1));
+        let value = get_ability_value(
+            &data.sheets,
+            gviz,
+            SPREADSHEET_ID,
+            "Grimbold the Grey",
+            "Stärke",
+        )
+        .await;
+        assert_eq!(value.unwrap(), ("Stärke".to_owned(), 4));
+        let value = get_ability_value(
+            &data.sheets,
+            gviz,
+            SPREADSHEET_ID,
+            "Grimbold the Grey",
+            "o'hara",
+        )
+        .await;
+        assert_eq!(value.unwrap(), ("O'Hara's Kniff".to_owned(), 2));
+
+        assert_eq!(string_literal("o'hara \"x\""), "\"o'hara x\"");
+        assert_eq!(percent_encode("Stärke & co"), "St%C3%A4rke%20%26%20co");
+    }
+
+    #[tokio::test]
+    async fn rate_limits_and_server_errors_are_retried() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+        let gviz = &data.config.gviz;
+
+        mock.fail_next_gviz_requests_with(1, StatusCode::TOO_MANY_REQUESTS, None);
+        let value = get_ability_value(&data.sheets, gviz, SPREADSHEET_ID, "Bilbo", "cha").await;
+        assert_eq!(value.unwrap(), ("Charisma".to_owned(), 2));
+        assert_eq!(mock.gviz_requests(), 2);
+
+        mock.fail_next_gviz_requests_with(2, StatusCode::INTERNAL_SERVER_ERROR, None);
+        let value = get_ability_value(&data.sheets, gviz, SPREADSHEET_ID, "Bilbo", "cha").await;
+        assert_eq!(value.unwrap(), ("Charisma".to_owned(), 2));
+        assert_eq!(mock.gviz_requests(), 5);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+
+        mock.fail_next_gviz_requests_with(1, StatusCode::BAD_REQUEST, None);
+        let value = get_ability_value(
+            &data.sheets,
+            &data.config.gviz,
+            SPREADSHEET_ID,
+            "Bilbo",
+            "cha",
+        )
+        .await;
+        assert!(matches!(value, Err(Error::ClientError(_))));
+        assert_eq!(mock.gviz_requests(), 1);
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_honoured_up_to_the_maximum_delay() {
+        let mock = MockGoogle::start().await;
+        let mut config = mock.config();
+        config.gviz.retry.max_delay = Duration::from_millis(200);
+        let data = mock.data_with_config(config).await;
+
+        // Our own backoff would wait at most a millisecond before the retry
+        mock.fail_next_gviz_requests_with(1, StatusCode::TOO_MANY_REQUESTS, Some(60));
+        let start = tokio::time::Instant::now();
+        let value = get_ability_value(
+            &data.sheets,
+            &data.config.gviz,
+            SPREADSHEET_ID,
+            "Bilbo",
+            "cha",
+        )
+        .await;
+        let elapsed = start.elapsed();
+        assert_eq!(value.unwrap(), ("Charisma".to_owned(), 2));
+        assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(5), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn retry_after_is_read_in_seconds() {
+        let response = |value: &str| {
+            hyper::Response::builder()
+                .header(RETRY_AFTER, value)
+                .body(())
+                .unwrap()
+        };
+        assert_eq!(retry_after(&response("2")), Some(Duration::from_secs(2)));
+        // Dates are not supported and fall back to the backoff
+        assert_eq!(
+            retry_after(&response("Wed, 21 Oct 2015 07:28:00 GMT")),
+            None
+        );
+        assert_eq!(retry_after(&hyper::Response::new(())), None);
+    }
 }
@@ -0,0 +1,338 @@
+//! Localization of the bot's replies and slash commands.
+//!
+//! Messages live in the Fluent catalogs in `translations/`, which are compiled into the bot.
+//! English is the fallback for missing translations. Replies use the language the author
+//! picked for themselves, else the one picked for the guild, else their Discord client's.
+
+use fluent::{FluentArgs, FluentResource, FluentValue};
+
+use crate::error::Error;
+use crate::reply;
+use crate::PoiseContext;
+
+type Bundle =
+    fluent::bundle::FluentBundle<FluentResource, intl_memoizer::concurrent::IntlLangMemoizer>;
+
+const ENGLISH: &str = include_str!("../translations/en-US.ftl");
+const GERMAN: &str = include_str!("../translations/de.ftl");
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Language {
+    #[name = "English"]
+    English,
+    #[name = "Deutsch"]
+    German,
+}
+
+impl Language {
+    /// Locale as used by Discord
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en-US",
+            Language::German => "de",
+        }
+    }
+
+    /// Matches a locale such as `de` or `en-GB` to the supported languages
+    pub fn from_code(code: &str) -> Option<Language> {
+        match code.split('-').next()? {
+            "de" => Some(Language::German),
+            "en" => Some(Language::English),
+            _ => None,
+        }
+    }
+}
+
+/// A translatable message, formatted once the reader's language is known
+#[derive(Debug, Clone, PartialEq)]
+pub struct Message {
+    pub id: &'static str,
+    pub args: Vec<(&'static str, String)>,
+}
+
+impl Message {
+    pub fn new(id: &'static str) -> Message {
+        Message {
+            id,
+            args: Vec::new(),
+        }
+    }
+
+    pub fn arg(mut self, name: &'static str, value: impl ToString) -> Message {
+        self.args.push((name, value.to_string()));
+        self
+    }
+}
+
+/// The parsed message catalogs of all languages
+pub struct Translations {
+    english: Bundle,
+    german: Bundle,
+}
+
+fn bundle(language: Language, source: &str) -> Result<Bundle, Error> {
+    let resource = FluentResource::try_new(source.to_owned()).map_err(|(_, errors)| {
+        Error::Config(format!(
+            "Invalid {} translations: {:?}",
+            language.code(),
+            errors
+        ))
+    })?;
+    let locale = language
+        .code()
+        .parse()
+        .map_err(|err| Error::Config(format!("Invalid locale {}: {}", language.code(), err)))?;
+    let mut bundle = Bundle::new_concurrent(vec![locale]);
+    // Discord does not render the Unicode isolation marks around arguments
+    bundle.set_use_isolating(false);
+    bundle.add_resource(resource).map_err(|errors| {
+        Error::Config(format!(
+            "Conflicting {} translations: {:?}",
+            language.code(),
+            errors
+        ))
+    })?;
+    Ok(bundle)
+}
+
+fn format(
+    bundle: &Bundle,
+    id: &str,
+    attribute: Option<&str>,
+    args: Option<&FluentArgs>,
+) -> Option<String> {
+    let message = bundle.get_message(id)?;
+    let pattern = match attribute {
+        Some(attribute) => message.get_attribute(attribute)?.value(),
+        None => message.value()?,
+    };
+    Some(
+        bundle
+            .format_pattern(pattern, args, &mut Vec::new())
+            .into_owned(),
+    )
+}
+
+impl Translations {
+    pub fn load() -> Result<Translations, Error> {
+        Ok(Translations {
+            english: bundle(Language::English, ENGLISH)?,
+            german: bundle(Language::German, GERMAN)?,
+        })
+    }
+
+    fn bundle(&self, language: Language) -> &Bundle {
+        match language {
+            Language::English => &self.english,
+            Language::German => &self.german,
+        }
+    }
+
+    /// Formats a message in the language, falling back to English and then to its ID
+    pub fn format(&self, language: Language, id: &str, args: Option<&FluentArgs>) -> String {
+        format(self.bundle(language), id, None, args)
+            .or_else(|| format(&self.english, id, None, args))
+            .unwrap_or_else(|| {
+                tracing::warn!("Missing translation {}", id);
+                id.to_owned()
+            })
+    }
+
+    /// Adds the German names and descriptions of commands, their subcommands and parameters.
+    /// The English ones come from the commands' doc comments.
+    pub fn apply_to_commands<U, E>(&self, commands: &mut [poise::Command<U, E>]) {
+        let language = Language::German;
+        let bundle = self.bundle(language);
+        let locale = language.code().to_owned();
+        for command in commands {
+            self.apply_to_commands(&mut command.subcommands);
+
+            let id = command.identifying_name.clone();
+            let Some(name) = format(bundle, &id, None, None) else {
+                continue;
+            };
+            command.name_localizations.insert(locale.clone(), name);
+            if let Some(description) = format(bundle, &id, Some("description"), None) {
+                command
+                    .description_localizations
+                    .insert(locale.clone(), description);
+            }
+            for parameter in &mut command.parameters {
+                if let Some(name) = format(bundle, &id, Some(&parameter.name), None) {
+                    parameter.name_localizations.insert(locale.clone(), name);
+                }
+                let description = format!("{}-description", parameter.name);
+                if let Some(description) = format(bundle, &id, Some(&description), None) {
+                    parameter
+                        .description_localizations
+                        .insert(locale.clone(), description);
+                }
+                for choice in &mut parameter.choices {
+                    if let Some(name) =
+                        format(bundle, &format!("choice-{}", choice.name), None, None)
+                    {
+                        choice.localizations.insert(locale.clone(), name);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Formats messages in one language
+#[derive(Clone, Copy)]
+pub struct Tr<'a> {
+    translations: &'a Translations,
+    pub language: Language,
+}
+
+impl<'a> Tr<'a> {
+    pub fn new(translations: &'a Translations, language: Language) -> Tr<'a> {
+        Tr {
+            translations,
+            language,
+        }
+    }
+
+    pub fn get(&self, id: &str) -> String {
+        self.translations.format(self.language, id, None)
+    }
+
+    pub fn with_args(&self, id: &str, args: &FluentArgs) -> String {
+        self.translations.format(self.language, id, Some(args))
+    }
+
+    pub fn message(&self, message: &Message) -> String {
+        let mut args = FluentArgs::new();
+        for (name, value) in &message.args {
+            args.set(*name, FluentValue::from(value.as_str()));
+        }
+        self.with_args(message.id, &args)
+    }
+}
+
+/// Formats a message with arguments, e.g. `tr!(tr, "claim-done", character = name)`
+macro_rules! tr {
+    ($tr:expr, $id:expr $(, $name:ident = $value:expr)* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut args = fluent::FluentArgs::new();
+        $( args.set(stringify!($name), $value); )*
+        $tr.with_args($id, &args)
+    }};
+}
+pub(crate) use tr;
+
+/// Language to reply to the author of the command in
+pub fn language(ctx: PoiseContext<'_>) -> Language {
+    let user_id = ctx.author().id.get();
+    let stored = {
+        let mut sheet_db = ctx.data().sheet_db();
+        sheet_db
+            .get_user_language(user_id)
+            .and_then(|language| match language {
+                Some(language) => Ok(Some(language)),
+                None => match ctx.guild_id() {
+                    Some(guild_id) => sheet_db.get_guild_language(guild_id.get()),
+                    None => Ok(None),
+                },
+            })
+    };
+    let stored = stored.unwrap_or_else(|err| {
+        tracing::warn!("Failed reading language setting: {}", err);
+        None
+    });
+    stored
+        .as_deref()
+        .or(ctx.locale())
+        .and_then(Language::from_code)
+        .unwrap_or(Language::English)
+}
+
+/// Translator for replies to the author of the command
+pub fn translator(ctx: PoiseContext<'_>) -> Tr<'_> {
+    Tr::new(&ctx.data().translations, language(ctx))
+}
+
+/// Choose the language the bot answers in
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "language",
+    subcommands("language_user", "language_server"),
+    subcommand_required
+)]
+pub async fn language_setting(_ctx: PoiseContext<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Choose the language the bot answers you in
+#[poise::command(prefix_command, slash_command, guild_only, rename = "user")]
+async fn language_user(
+    ctx: PoiseContext<'_>,
+    #[description = "Your language, leave empty to use the server's"] language: Option<Language>,
+) -> Result<(), Error> {
+    ctx.data()
+        .sheet_db()
+        .set_user_language(ctx.author().id.get(), language.map(Language::code))?;
+    let tr = translator(ctx);
+    reply::say(ctx, tr.get("language-user-set")).await
+}
+
+/// Choose the language the bot answers in on this server
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "server",
+    check = "crate::permissions::admin"
+)]
+async fn language_server(
+    ctx: PoiseContext<'_>,
+    #[description = "Language of the server, leave empty to use each member's Discord language"]
+    language: Option<Language>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    ctx.data()
+        .sheet_db()
+        .set_guild_language(guild_id, language.map(Language::code))?;
+    let tr = translator(ctx);
+    reply::say(ctx, tr.get("language-server-set")).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// IDs of all messages and their attributes in a catalog
+    fn ids(source: &str) -> Vec<String> {
+        let resource = FluentResource::try_new(source.to_owned()).unwrap();
+        let mut ids: Vec<String> = resource
+            .entries()
+            .filter_map(|entry| match entry {
+                fluent_syntax::ast::Entry::Message(message) => Some(message.id.name.to_owned()),
+                _ => None,
+            })
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn every_english_message_is_translated() {
+        let translations = Translations::load().unwrap();
+        let german = ids(GERMAN);
+        let missing: Vec<String> = ids(ENGLISH)
+            .into_iter()
+            .filter(|id| !german.contains(id))
+            .collect();
+        assert!(missing.is_empty(), "untranslated messages: {:?}", missing);
+
+        let tr = Tr::new(&translations, Language::German);
+        assert_eq!(
+            tr.message(&Message::new("error-unknown-persona").arg("name", "Erzähler")),
+            "Es gibt keine Persona namens 'Erzähler'."
+        );
+        assert_eq!(Language::from_code("en-GB"), Some(Language::English));
+    }
+}
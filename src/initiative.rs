@@ -0,0 +1,517 @@
+//! Initiative order for fights: every combatant rolls a check on the configured ability, and
+//! the order of each channel is kept in the DB with its current turn and round.
+
+use crate::check::{resolve_abilities, CheckResult};
+use crate::combat;
+use crate::db::{Combatant, Initiative};
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message, Tr};
+use crate::permissions;
+use crate::reply;
+use crate::PoiseContext;
+
+impl Initiative {
+    pub fn new() -> Initiative {
+        Initiative {
+            round: 1,
+            turn: 0,
+            combatants: Vec::new(),
+        }
+    }
+
+    /// Index of the combatant with this name, ignoring case
+    pub fn position(&self, name: &str) -> Option<usize> {
+        self.combatants
+            .iter()
+            .position(|combatant| combatant.name.to_lowercase() == name.to_lowercase())
+    }
+
+    pub fn current(&self) -> Option<&Combatant> {
+        self.combatants.get(self.turn)
+    }
+
+    /// Adds a combatant after everyone with the same or a higher initiative, replacing an
+    /// earlier entry of the same name. The current turn stays with the same combatant.
+    pub fn add(&mut self, combatant: Combatant) {
+        if let Some(index) = self.position(&combatant.name) {
+            self.remove(index);
+        }
+        let index = self
+            .combatants
+            .iter()
+            .position(|other| other.initiative < combatant.initiative)
+            .unwrap_or(self.combatants.len());
+        if index <= self.turn && !self.combatants.is_empty() {
+            self.turn += 1;
+        }
+        self.combatants.insert(index, combatant);
+    }
+
+    /// Removes a combatant. If it was their turn, the next combatant's turn begins.
+    pub fn remove(&mut self, index: usize) -> Combatant {
+        let combatant = self.combatants.remove(index);
+        if index < self.turn {
+            self.turn -= 1;
+        } else if self.turn >= self.combatants.len() && self.turn > 0 {
+            self.turn = 0;
+            self.round += 1;
+        }
+        combatant
+    }
+
+    /// Passes the turn to the next combatant, starting a new round after the last one
+    pub fn next(&mut self) {
+        if self.combatants.is_empty() {
+            return;
+        }
+        self.turn += 1;
+        if self.turn >= self.combatants.len() {
+            self.turn = 0;
+            self.round += 1;
+        }
+    }
+
+    /// Lets a combatant who has not acted yet this round act after the one following them.
+    /// Returns false if nobody follows them this round.
+    pub fn delay(&mut self, index: usize) -> bool {
+        if index < self.turn || index + 1 >= self.combatants.len() {
+            return false;
+        }
+        self.combatants.swap(index, index + 1);
+        true
+    }
+}
+
+/// The order as a numbered list, marking whose turn it is
+fn render(tr: Tr, initiative: &Initiative) -> String {
+    let mut text = format!("## {}\n", tr!(tr, "init-round", round = initiative.round));
+    if initiative.combatants.is_empty() {
+        text.push_str(&tr.get("init-empty"));
+    }
+    for (i, combatant) in initiative.combatants.iter().enumerate() {
+        let line = format!("{} ({})", combatant.name, combatant.initiative);
+        if i == initiative.turn {
+            text.push_str(&format!("{}. ▶ **{}**\n", i + 1, line));
+        } else {
+            text.push_str(&format!("{}. {}\n", i + 1, line));
+        }
+    }
+    text
+}
+
+fn load(ctx: PoiseContext<'_>) -> Result<Initiative, Error> {
+    ctx.data()
+        .sheet_db()
+        .get_initiative(ctx.guild_id().unwrap().get(), ctx.channel_id().get())?
+        .ok_or_else(|| Error::InvalidArgument(Message::new("init-not-running")))
+}
+
+/// Changes the order of the channel's fight within one critical section of the database, so
+/// that commands running at the same time do not overwrite each other's changes
+fn update(
+    ctx: PoiseContext<'_>,
+    change: impl FnOnce(&mut Initiative) -> Result<(), Error>,
+) -> Result<Initiative, Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let channel_id = ctx.channel_id().get();
+    let mut sheet_db = ctx.data().sheet_db();
+    let mut initiative = sheet_db
+        .get_initiative(guild_id, channel_id)?
+        .ok_or_else(|| Error::InvalidArgument(Message::new("init-not-running")))?;
+    change(&mut initiative)?;
+    sheet_db.save_initiative(guild_id, channel_id, &initiative)?;
+    Ok(initiative)
+}
+
+/// Index of a combatant, failing if they are not in the order
+fn find(initiative: &Initiative, name: &str) -> Result<usize, Error> {
+    initiative
+        .position(name)
+        .ok_or_else(|| Error::InvalidArgument(Message::new("init-unknown").arg("name", name)))
+}
+
+/// Rolls initiative like a check on the configured ability, taken from the character's sheet
+/// unless a value is given
+async fn roll(ctx: PoiseContext<'_>, name: &str, value: Option<u8>) -> Result<CheckResult, Error> {
+    let data = ctx.data();
+    let ability = data.config.initiative_ability.as_str();
+    let abilities = match value {
+        Some(value) => vec![(ability.to_owned(), value)],
+        None => resolve_abilities(data, name, &[ability]).await?,
+    };
+    let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_entropy();
+    Ok(CheckResult::roll(name, abilities, None, &mut rng))
+}
+
+/// Rolls for a combatant, adds them to the order and shows the roll and the new order
+async fn join(
+    ctx: PoiseContext<'_>,
+    name: &str,
+    value: Option<u8>,
+    npc: bool,
+) -> Result<(), Error> {
+    // Fail before rolling, the order itself is only changed once the roll is done
+    load(ctx)?;
+    let result = roll(ctx, name, value).await?;
+    let initiative = update(ctx, |initiative| {
+        initiative.add(Combatant {
+            name: name.to_owned(),
+            initiative: result.total(),
+            npc,
+        });
+        Ok(())
+    })?;
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        format!("{}\n\n{}", result.render(tr), render(tr, &initiative)),
+    )
+    .await
+}
+
+/// Track the turn order of a fight in this channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands(
+        "init_start",
+        "init_join",
+        "init_npc",
+        "init_next",
+        "init_delay",
+        "init_remove",
+        "init_show",
+        "init_end"
+    ),
+    subcommand_required
+)]
+pub async fn init(_ctx: PoiseContext<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Start a fight in this channel, rolling initiative for all claimed characters
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "start",
+    check = "crate::permissions::gm"
+)]
+async fn init_start(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let channel_id = ctx.channel_id().get();
+    let characters = {
+        let mut sheet_db = ctx.data().sheet_db();
+        if sheet_db.get_initiative(guild_id, channel_id)?.is_some() {
+            return Err(Error::InvalidArgument(Message::new("init-running")));
+        }
+        sheet_db.list_sheets(guild_id)?
+    };
+
+    let mut initiative = Initiative::new();
+    let mut skipped = Vec::new();
+    for character in characters {
+        match roll(ctx, &character, None).await {
+            Ok(result) => initiative.add(Combatant {
+                name: character,
+                initiative: result.total(),
+                npc: false,
+            }),
+            Err(err) => {
+                tracing::warn!("Failed rolling initiative for {}: {}", character, err);
+                skipped.push(character);
+            }
+        }
+    }
+    // Nobody has acted yet, so the fight starts with the highest initiative
+    initiative.turn = 0;
+    {
+        // Another fight may have been started while rolling
+        let mut sheet_db = ctx.data().sheet_db();
+        if sheet_db.get_initiative(guild_id, channel_id)?.is_some() {
+            return Err(Error::InvalidArgument(Message::new("init-running")));
+        }
+        sheet_db.save_initiative(guild_id, channel_id, &initiative)?;
+    }
+
+    let tr = translator(ctx);
+    let mut text = render(tr, &initiative);
+    if !skipped.is_empty() {
+        text.push_str("\n-# ");
+        text.push_str(&tr!(tr, "init-skipped", names = skipped.join(", ")));
+    }
+    reply::say(ctx, text).await
+}
+
+/// Roll initiative for a character and join the fight
+#[poise::command(prefix_command, slash_command, guild_only, rename = "join")]
+async fn init_join(
+    ctx: PoiseContext<'_>,
+    #[description = "Character joining the fight, defaults to your claimed one"]
+    character_name: Option<String>,
+) -> Result<(), Error> {
+    let character_name = match character_name {
+        Some(character_name) => {
+            permissions::ensure_may_roll_for(ctx, &character_name).await?;
+            character_name
+        }
+        None => ctx
+            .data()
+            .sheet_db()
+            .get_sheet(ctx.guild_id().unwrap().get(), ctx.author().id.get())?
+            .ok_or(Error::NoClaimedCharacter)?,
+    };
+    join(ctx, &character_name, None, false).await
+}
+
+/// Roll initiative for an NPC and add it to the fight
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "npc",
+    check = "crate::permissions::gm"
+)]
+async fn init_npc(
+    ctx: PoiseContext<'_>,
+    #[description = "Name of the NPC"] name: String,
+    #[description = "The NPC's value in the initiative ability, leave empty to read its sheet"]
+    value: Option<u8>,
+) -> Result<(), Error> {
+    join(ctx, &name, value, true).await
+}
+
+/// Replies with the order, counting down conditions if a new round started since `round`
+async fn say_order(
+    ctx: PoiseContext<'_>,
+    initiative: &Initiative,
+    round: i64,
+) -> Result<(), Error> {
+    let tr = translator(ctx);
+    let mut text = render(tr, initiative);
+    if initiative.round > round {
+        if let Some(expired) = combat::new_round(ctx, initiative)? {
+            text.push('\n');
+            text.push_str(&expired);
+        }
+    }
+    reply::say(ctx, text).await
+}
+
+/// End the current turn and pass it to the next combatant
+#[poise::command(prefix_command, slash_command, guild_only, rename = "next")]
+async fn init_next(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let snapshot = load(ctx)?;
+    let round = snapshot.round;
+    let current = snapshot.current().cloned();
+    // Players may end their own turn, everyone else's is up to the GM
+    match &current {
+        Some(current) if !current.npc => {
+            permissions::ensure_may_roll_for(ctx, &current.name).await?
+        }
+        _ => permissions::ensure_level(ctx, permissions::Level::Gm).await?,
+    }
+    let initiative = update(ctx, |initiative| {
+        // The turn may have passed on while the permissions were checked
+        if initiative.round != round || initiative.current() != current.as_ref() {
+            return Err(Error::InvalidArgument(Message::new("init-changed")));
+        }
+        initiative.next();
+        Ok(())
+    })?;
+    say_order(ctx, &initiative, round).await
+}
+
+/// Act after the next combatant instead
+#[poise::command(prefix_command, slash_command, guild_only, rename = "delay")]
+async fn init_delay(
+    ctx: PoiseContext<'_>,
+    #[description = "Combatant delaying, defaults to the one whose turn it is"] name: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let snapshot = load(ctx)?;
+    let index = match &name {
+        Some(name) => find(&snapshot, name)?,
+        None => snapshot.turn,
+    };
+    let Some(combatant) = snapshot.combatants.get(index).cloned() else {
+        return Err(Error::InvalidArgument(Message::new("init-empty")));
+    };
+    if combatant.npc {
+        permissions::ensure_level(ctx, permissions::Level::Gm).await?;
+    } else {
+        permissions::ensure_may_roll_for(ctx, &combatant.name).await?;
+    }
+    let initiative = update(ctx, |initiative| {
+        // The order may have changed while the permissions were checked
+        let index = find(initiative, &combatant.name)?;
+        if name.is_none() && index != initiative.turn {
+            return Err(Error::InvalidArgument(Message::new("init-changed")));
+        }
+        if initiative.delay(index) {
+            Ok(())
+        } else {
+            Err(Error::InvalidArgument(
+                Message::new("init-cannot-delay").arg("name", &combatant.name),
+            ))
+        }
+    })?;
+    let tr = translator(ctx);
+    reply::say(ctx, render(tr, &initiative)).await
+}
+
+/// Remove a combatant from the fight
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "remove",
+    check = "crate::permissions::gm"
+)]
+async fn init_remove(
+    ctx: PoiseContext<'_>,
+    #[description = "Combatant to remove"] name: String,
+) -> Result<(), Error> {
+    let mut round = 0;
+    let initiative = update(ctx, |initiative| {
+        let index = find(initiative, &name)?;
+        round = initiative.round;
+        initiative.remove(index);
+        Ok(())
+    })?;
+    say_order(ctx, &initiative, round).await
+}
+
+/// Show the turn order of the fight in this channel
+#[poise::command(prefix_command, slash_command, guild_only, rename = "show")]
+async fn init_show(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let initiative = load(ctx)?;
+    let tr = translator(ctx);
+    reply::say(ctx, render(tr, &initiative)).await
+}
+
+/// End the fight in this channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "end",
+    check = "crate::permissions::gm"
+)]
+async fn init_end(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let ended = ctx
+        .data()
+        .sheet_db()
+        .delete_initiative(ctx.guild_id().unwrap().get(), ctx.channel_id().get())?;
+    if !ended {
+        return Err(Error::InvalidArgument(Message::new("init-not-running")));
+    }
+    let tr = translator(ctx);
+    reply::say(ctx, tr.get("init-ended")).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SheetDB;
+
+    fn combatant(name: &str, initiative: i16) -> Combatant {
+        Combatant {
+            name: name.to_owned(),
+            initiative,
+            npc: false,
+        }
+    }
+
+    fn names(initiative: &Initiative) -> Vec<&str> {
+        initiative
+            .combatants
+            .iter()
+            .map(|combatant| combatant.name.as_str())
+            .collect()
+    }
+
+    /// Bilbo (10), Gandalf (8) and Orc (8), with Bilbo's turn
+    fn fight() -> Initiative {
+        let mut initiative = Initiative::new();
+        initiative.add(combatant("Gandalf", 8));
+        initiative.add(combatant("Bilbo", 10));
+        initiative.add(combatant("Orc", 8));
+        initiative.turn = 0;
+        initiative
+    }
+
+    #[test]
+    fn combatants_are_ordered_by_initiative_then_arrival() {
+        let mut initiative = fight();
+        assert_eq!(names(&initiative), ["Bilbo", "Gandalf", "Orc"]);
+
+        // Rolling again replaces the earlier entry
+        initiative.add(combatant("Gandalf", 11));
+        assert_eq!(names(&initiative), ["Gandalf", "Bilbo", "Orc"]);
+    }
+
+    #[test]
+    fn joining_ahead_keeps_the_current_turn() {
+        let mut initiative = fight();
+        initiative.next();
+        initiative.add(combatant("Troll", 12));
+        assert_eq!(names(&initiative), ["Troll", "Bilbo", "Gandalf", "Orc"]);
+        assert_eq!(initiative.current().unwrap().name, "Gandalf");
+    }
+
+    #[test]
+    fn delaying_swaps_with_the_next_combatant() {
+        let mut initiative = fight();
+        initiative.next();
+        assert!(initiative.delay(initiative.turn));
+        assert_eq!(names(&initiative), ["Bilbo", "Orc", "Gandalf"]);
+        assert_eq!(initiative.current().unwrap().name, "Orc");
+
+        // Nobody follows the last combatant, and Bilbo already acted
+        assert!(!initiative.delay(2));
+        assert!(!initiative.delay(0));
+    }
+
+    #[test]
+    fn rounds_are_counted_when_the_order_starts_over() {
+        let mut initiative = fight();
+        for _ in 0..3 {
+            initiative.next();
+        }
+        assert_eq!(initiative.round, 2);
+        assert_eq!(initiative.current().unwrap().name, "Bilbo");
+    }
+
+    #[test]
+    fn removing_the_last_combatant_on_turn_starts_a_new_round() {
+        let mut initiative = fight();
+        initiative.next();
+        initiative.next();
+        initiative.remove(initiative.turn);
+        assert_eq!(initiative.round, 2);
+        assert_eq!(initiative.current().unwrap().name, "Bilbo");
+
+        // Removing someone before the current turn keeps the turn with the same combatant
+        initiative.next();
+        initiative.remove(0);
+        assert_eq!(initiative.current().unwrap().name, "Gandalf");
+    }
+
+    #[test]
+    fn order_round_trips_through_the_db() {
+        let mut initiative = fight();
+        initiative.next();
+        initiative.combatants[2].npc = true;
+
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        sheet_db.save_initiative(1, 2, &initiative).unwrap();
+        assert_eq!(sheet_db.get_initiative(1, 2).unwrap(), Some(initiative));
+        assert_eq!(sheet_db.get_initiative(1, 3).unwrap(), None);
+        assert!(sheet_db.delete_initiative(1, 2).unwrap());
+        assert_eq!(sheet_db.get_initiative(1, 2).unwrap(), None);
+        assert!(!sheet_db.delete_initiative(1, 2).unwrap());
+    }
+}
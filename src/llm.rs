@@ -0,0 +1,137 @@
+//! Backends that produce chat completions for the bot's LLM features
+
+mod openai;
+#[cfg(feature = "python")]
+mod python;
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use poise::serenity_prelude::async_trait;
+
+use crate::config::LlmConfig;
+use crate::error::Error;
+use crate::persona::CompletionSettings;
+
+pub use openai::OpenAiBackend;
+#[cfg(feature = "python")]
+pub use python::PythonBackend;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::System => "system",
+            Role::User => "user",
+            Role::Assistant => "assistant",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChatMessage {
+    pub role: Role,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn user<S: Into<String>>(content: S) -> ChatMessage {
+        ChatMessage {
+            role: Role::User,
+            content: content.into(),
+        }
+    }
+
+    pub fn assistant<S: Into<String>>(content: S) -> ChatMessage {
+        ChatMessage {
+            role: Role::Assistant,
+            content: content.into(),
+        }
+    }
+
+    /// Rough token count, good enough for budgeting without the model's tokenizer
+    pub fn estimated_tokens(&self) -> usize {
+        self.content.chars().count().div_ceil(4) + 4
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompletionRequest {
+    pub settings: CompletionSettings,
+    /// Conversation so far, oldest first and without the system prompt
+    pub messages: Vec<ChatMessage>,
+}
+
+impl CompletionRequest {
+    /// All messages to send, starting with the system prompt
+    pub fn prompt(&self) -> impl Iterator<Item = (Role, &str)> {
+        std::iter::once((Role::System, self.settings.system_prompt.as_str())).chain(
+            self.messages
+                .iter()
+                .map(|message| (message.role, message.content.as_str())),
+        )
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    /// Tokens used by prompt and answer, if the backend reports them
+    pub tokens_used: Option<u32>,
+}
+
+#[async_trait]
+pub trait LlmBackend: Send + Sync {
+    async fn complete(&self, request: CompletionRequest) -> Result<Completion, Error>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackendKind {
+    /// Any endpoint speaking the OpenAI chat completions API
+    OpenAi,
+    /// The LangChain app in `python/main.py`, run in an embedded interpreter
+    Python,
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "openai" => Ok(BackendKind::OpenAi),
+            "python" => Ok(BackendKind::Python),
+            _ => Err(format!("unknown LLM backend '{}'", s)),
+        }
+    }
+}
+
+impl Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackendKind::OpenAi => write!(f, "openai"),
+            BackendKind::Python => write!(f, "python"),
+        }
+    }
+}
+
+/// Creates the backend selected in the config
+pub fn build_backend(config: &LlmConfig) -> Result<Box<dyn LlmBackend>, Error> {
+    match config.backend {
+        BackendKind::OpenAi => Ok(Box::new(OpenAiBackend::new(
+            &config.base_url,
+            config.api_key.clone(),
+        ))),
+        #[cfg(feature = "python")]
+        BackendKind::Python => Ok(Box::new(PythonBackend::new(&config.python_site_packages)?)),
+        #[cfg(not(feature = "python"))]
+        BackendKind::Python => Err(Error::Config(
+            "LLM_BACKEND=python requires building with the `python` feature".to_owned(),
+        )),
+    }
+}
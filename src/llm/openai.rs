@@ -0,0 +1,125 @@
+extern crate google_sheets4 as sheets4;
+
+use poise::serenity_prelude::async_trait;
+use serde_json::{json, Value};
+use sheets4::hyper::client::HttpConnector;
+use sheets4::hyper::header::{AUTHORIZATION, CONTENT_TYPE};
+use sheets4::hyper_rustls::HttpsConnector;
+use sheets4::{hyper, hyper_rustls};
+
+use super::{Completion, CompletionRequest, LlmBackend};
+use crate::error::Error;
+
+/// Talks to any endpoint implementing the OpenAI chat completions API,
+/// e.g. OpenAI, xAI or a local llama.cpp or Ollama server
+pub struct OpenAiBackend {
+    client: hyper::Client<HttpsConnector<HttpConnector>>,
+    /// Endpoint serving `chat/completions`, with trailing slash
+    base_url: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiBackend {
+    pub fn new(base_url: &str, api_key: Option<String>) -> OpenAiBackend {
+        let base_url = if base_url.ends_with('/') {
+            base_url.to_owned()
+        } else {
+            format!("{}/", base_url)
+        };
+        OpenAiBackend {
+            client: hyper::Client::builder().build(
+                hyper_rustls::HttpsConnectorBuilder::new()
+                    .with_native_roots()
+                    .https_or_http()
+                    .enable_http1()
+                    .enable_http2()
+                    .build(),
+            ),
+            base_url,
+            api_key,
+        }
+    }
+}
+
+#[async_trait]
+impl LlmBackend for OpenAiBackend {
+    async fn complete(&self, request: CompletionRequest) -> Result<Completion, Error> {
+        let messages: Vec<Value> = request
+            .prompt()
+            .map(|(role, content)| json!({"role": role.as_str(), "content": content}))
+            .collect();
+        let body = json!({
+            "model": request.settings.model,
+            "temperature": request.settings.temperature,
+            "messages": messages,
+        });
+
+        let mut builder = hyper::Request::builder()
+            .method(hyper::Method::POST)
+            .uri(format!("{}chat/completions", self.base_url))
+            .header(CONTENT_TYPE, "application/json");
+        if let Some(api_key) = &self.api_key {
+            builder = builder.header(AUTHORIZATION, format!("Bearer {}", api_key));
+        }
+        let http_request = builder
+            .body(hyper::Body::from(body.to_string()))
+            .map_err(|err| Error::Llm(err.to_string()))?;
+
+        let response = self
+            .client
+            .request(http_request)
+            .await
+            .map_err(|err| Error::Llm(err.to_string()))?;
+        let status = response.status();
+        let bytes = hyper::body::to_bytes(response.into_body())
+            .await
+            .map_err(|err| Error::Llm(err.to_string()))?;
+        if !status.is_success() {
+            return Err(Error::Llm(format!(
+                "{}: {}",
+                status,
+                String::from_utf8_lossy(&bytes)
+            )));
+        }
+
+        let response: Value =
+            serde_json::from_slice(&bytes).map_err(|err| Error::Llm(err.to_string()))?;
+        let text = response["choices"][0]["message"]["content"]
+            .as_str()
+            .ok_or_else(|| Error::Llm(format!("Unexpected response {}", response)))?;
+        Ok(Completion {
+            text: text.to_owned(),
+            tokens_used: response["usage"]["total_tokens"]
+                .as_u64()
+                .map(|tokens| tokens as u32),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ChatMessage;
+    use crate::persona::CompletionSettings;
+    use crate::test_support::MockGoogle;
+
+    #[tokio::test]
+    async fn sends_system_prompt_and_messages() {
+        let mock = MockGoogle::start().await;
+        let backend = OpenAiBackend::new(&format!("{}llm", mock.base_url), None);
+
+        let completion = backend
+            .complete(CompletionRequest {
+                settings: CompletionSettings {
+                    system_prompt: "Be brief".to_owned(),
+                    model: "local-model".to_owned(),
+                    temperature: 0.2,
+                },
+                messages: vec![ChatMessage::user("Hello")],
+            })
+            .await
+            .unwrap();
+        assert_eq!(completion.text, "local-model (2 messages): Hello");
+        assert_eq!(completion.tokens_used, Some(42));
+    }
+}
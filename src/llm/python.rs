@@ -0,0 +1,199 @@
+use std::sync::mpsc;
+use std::thread;
+
+use poise::serenity_prelude::async_trait;
+use pyo3::ffi::c_str;
+use pyo3::prelude::*;
+use tokio::sync::oneshot;
+
+use super::{Completion, CompletionRequest, LlmBackend};
+use crate::error::Error;
+
+struct Job {
+    request: CompletionRequest,
+    reply: oneshot::Sender<Result<String, Error>>,
+}
+
+/// Runs the LangChain app in `python/main.py` in an embedded interpreter.
+///
+/// All calls into Python happen on a dedicated worker thread, so the blocking HTTP call in
+/// LangChain does not stall the async runtime. Requests are handled one after the other;
+/// requests whose caller has given up before they are started are skipped.
+pub struct PythonBackend {
+    jobs: mpsc::Sender<Job>,
+}
+
+impl PythonBackend {
+    /// Starts the worker, making the packages in `site_packages` importable and loading the app
+    pub fn new(site_packages: &str) -> Result<PythonBackend, Error> {
+        pyo3::prepare_freethreaded_python();
+
+        let site_packages = site_packages.to_owned();
+        let jobs = spawn_worker(move || {
+            let app = load_app(&site_packages)?;
+            Ok(move |request: &CompletionRequest| call_app(&app, request).map_err(Error::from))
+        })?;
+        Ok(PythonBackend { jobs })
+    }
+}
+
+/// Starts the worker thread, which runs `setup` and then handles the jobs sent to it in order
+fn spawn_worker<F>(
+    setup: impl FnOnce() -> Result<F, Error> + Send + 'static,
+) -> Result<mpsc::Sender<Job>, Error>
+where
+    F: FnMut(&CompletionRequest) -> Result<String, Error>,
+{
+    let (jobs, receiver) = mpsc::channel::<Job>();
+    let (ready, ready_receiver) = mpsc::channel::<Result<(), Error>>();
+    thread::Builder::new()
+        .name("python-llm".to_owned())
+        .spawn(move || {
+            let mut handle = match setup() {
+                Ok(handle) => {
+                    ready.send(Ok(())).ok();
+                    handle
+                }
+                Err(err) => {
+                    ready.send(Err(err)).ok();
+                    return;
+                }
+            };
+            for job in receiver {
+                if job.reply.is_closed() {
+                    tracing::debug!("Skipping cancelled python completion");
+                    continue;
+                }
+                job.reply.send(handle(&job.request)).ok();
+            }
+        })
+        .map_err(|err| Error::Config(format!("Failed starting python worker: {}", err)))?;
+
+    ready_receiver
+        .recv()
+        .map_err(|_| Error::Config("Python worker exited during startup".to_owned()))??;
+    Ok(jobs)
+}
+
+fn load_app(site_packages: &str) -> PyResult<Py<PyAny>> {
+    let py_app = c_str!(include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/python/main.py"
+    )));
+    Python::with_gil(|py| {
+        py.import("sys")?
+            .getattr("path")?
+            .call_method1("append", (site_packages,))?;
+        Ok(
+            PyModule::from_code(py, py_app, c_str!("main.py"), c_str!("main"))?
+                .getattr("run")?
+                .into(),
+        )
+    })
+}
+
+fn call_app(app: &Py<PyAny>, request: &CompletionRequest) -> PyResult<String> {
+    let messages: Vec<(&str, &str)> = request
+        .messages
+        .iter()
+        .map(|message| (message.role.as_str(), message.content.as_str()))
+        .collect();
+    let settings = &request.settings;
+    Python::with_gil(|py| {
+        app.call1(
+            py,
+            (
+                messages,
+                settings.system_prompt.as_str(),
+                settings.model.as_str(),
+                settings.temperature,
+            ),
+        )?
+        .extract(py)
+    })
+}
+
+#[async_trait]
+impl LlmBackend for PythonBackend {
+    async fn complete(&self, request: CompletionRequest) -> Result<Completion, Error> {
+        let (reply, result) = oneshot::channel();
+        self.jobs
+            .send(Job { request, reply })
+            .map_err(|_| Error::Llm("Python worker is not running".to_owned()))?;
+        let text = result
+            .await
+            .map_err(|_| Error::Llm("Python worker dropped the request".to_owned()))??;
+        Ok(Completion {
+            text,
+            tokens_used: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::completion::complete_within;
+    use crate::llm::ChatMessage;
+    use crate::persona::CompletionSettings;
+
+    fn request(prompt: &str) -> CompletionRequest {
+        CompletionRequest {
+            settings: CompletionSettings {
+                system_prompt: "You are a test".to_owned(),
+                model: "test".to_owned(),
+                temperature: 0.0,
+            },
+            messages: vec![ChatMessage::user(prompt)],
+        }
+    }
+
+    /// Worker that answers with the prompt, taking long for prompts starting with "slow"
+    fn slow_backend(handled: Arc<Mutex<Vec<String>>>) -> PythonBackend {
+        let jobs = spawn_worker(move || {
+            Ok(move |request: &CompletionRequest| {
+                let prompt = request.messages[0].content.clone();
+                if prompt.starts_with("slow") {
+                    thread::sleep(Duration::from_millis(300));
+                }
+                handled.lock().unwrap().push(prompt.clone());
+                Ok(prompt)
+            })
+        })
+        .unwrap();
+        PythonBackend { jobs }
+    }
+
+    #[tokio::test]
+    async fn worker_skips_cancelled_jobs_and_keeps_serving() {
+        let handled = Arc::new(Mutex::new(Vec::new()));
+        let backend = slow_backend(handled.clone());
+        let timeout = Duration::from_millis(50);
+
+        // Times out while the worker is still busy with it
+        let slow = complete_within(&backend, request("slow"), timeout).await;
+        assert!(matches!(slow, Err(Error::LlmTimeout(after)) if after == timeout));
+        // Queued behind the slow job and given up before the worker gets to it
+        let queued = complete_within(&backend, request("queued"), timeout).await;
+        assert!(matches!(queued, Err(Error::LlmTimeout(_))));
+
+        let later = complete_within(&backend, request("later"), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(later.text, "later");
+        assert_eq!(*handled.lock().unwrap(), vec!["slow", "later"]);
+    }
+
+    #[test]
+    fn failed_setup_is_reported() {
+        let result = spawn_worker(|| {
+            Err::<fn(&CompletionRequest) -> Result<String, Error>, _>(Error::Config(
+                "no app".to_owned(),
+            ))
+        });
+        assert!(matches!(result, Err(Error::Config(message)) if message == "no app"));
+    }
+}
@@ -1,62 +1,99 @@
+mod check;
+mod combat;
+mod completion;
+mod config;
 mod db;
+mod dice_image;
+mod error;
+mod experience;
+mod fate;
 mod get_ability_value;
+mod i18n;
+mod initiative;
+mod llm;
+mod narration;
+mod npc;
+mod permissions;
+mod persona;
+mod quota;
+mod reply;
+mod retry;
+mod roll;
+mod rules;
+mod session;
+mod sheets;
+mod table;
+#[cfg(test)]
+mod test_support;
 
 extern crate google_sheets4 as sheets4;
 
-use rand::Rng;
-
-use pyo3::prelude::*;
-use pyo3::ffi::c_str;
-
 use poise::serenity_prelude as serenity;
 
-use sheets4::hyper::client::HttpConnector;
-use sheets4::hyper_rustls::HttpsConnector;
-use sheets4::{hyper, hyper_rustls, oauth2, Sheets};
+use sheets4::oauth2;
 
 use serenity::async_trait;
 
 use serenity::model::gateway::{GatewayIntents, Ready};
 use serenity::prelude::*;
 
+use crate::check::{resolve_abilities, CheckResult};
+use crate::config::Config;
 use crate::db::SheetDB;
-use crate::get_ability_value::get_ability_value;
-
-use once_cell::sync::OnceCell;
-
-struct Data {} // User data, which is stored and accessible in all command invocations
-type Error = Box<dyn std::error::Error + Send + Sync>;
-type PoiseContext<'a> = poise::Context<'a, Data, Error>;
-
-#[derive(Debug)]
-struct Config {
-    google_application_credentials: String,
-    character_spreadsheet_id: String,
-    discord_bot_token: String,
+use crate::error::Error;
+use crate::fate::FateUse;
+use crate::i18n::{tr, translator, Message, Translations};
+use crate::llm::{build_backend, LlmBackend};
+use crate::narration::RateLimiter;
+use crate::sheets::{assert_character_name, build_sheets, SheetsAPI};
+
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use tracing_subscriber::EnvFilter;
+
+/// User data, which is stored and accessible in all command invocations
+struct Data {
+    config: Config,
+    sheets: SheetsAPI,
+    sheet_db: Mutex<SheetDB>,
+    llm: Box<dyn LlmBackend>,
+    narration_limiter: RateLimiter,
+    translations: Translations,
 }
-impl Config {
-    pub fn load() -> Config {
-        let expect_var =
-            |x: &str| dotenv::var(x).unwrap_or_else(|_| panic!("{} env var must be defined!", x));
-        Config {
-            google_application_credentials: expect_var("GOOGLE_APPLICATION_CREDENTIALS"),
-            character_spreadsheet_id: expect_var("CHARACTER_SPREADSHEET_ID"),
-            discord_bot_token: expect_var("DISCORD_BOT_TOKEN"),
+impl Data {
+    fn new(
+        config: Config,
+        sheets: SheetsAPI,
+        sheet_db: SheetDB,
+        llm: Box<dyn LlmBackend>,
+        translations: Translations,
+    ) -> Data {
+        Data {
+            translations,
+            narration_limiter: RateLimiter::new(config.narration_cooldown),
+            config,
+            sheets,
+            sheet_db: Mutex::new(sheet_db),
+            llm,
         }
     }
-}
 
-type SheetsAPI = Sheets<HttpsConnector<HttpConnector>>;
+    /// Locks the DB for the current command. The guard must not be held across `.await` points.
+    fn sheet_db(&self) -> MutexGuard<'_, SheetDB> {
+        // A panic while holding the lock cannot leave the DB in an inconsistent state, since
+        // single statements are atomic on the SQLite side and transactions are rolled back
+        // when a panic interrupts them
+        self.sheet_db.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
 
-static CONFIG: OnceCell<Config> = OnceCell::new();
-static SHEETS: OnceCell<SheetsAPI> = OnceCell::new();
-static mut SHEET_DB: OnceCell<SheetDB> = OnceCell::new();
+type PoiseContext<'a> = poise::Context<'a, Data, Error>;
 
 struct Handler;
 #[async_trait]
 impl EventHandler for Handler {
     async fn ready(&self, _: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
+        tracing::info!("{} is connected!", ready.user.name);
     }
 }
 
@@ -66,174 +103,148 @@ async fn claim(
     ctx: PoiseContext<'_>,
     #[description = "Character you want to claim"] character_name: Option<String>,
 ) -> Result<(), Error> {
+    let tr = translator(ctx);
     let character_name = match character_name {
         Some(character_name) => character_name,
         None => {
-            ctx.say("Please specify a character name as the first argument").await?;
+            reply::say(ctx, tr.get("claim-missing-name")).await?;
             return Ok(());
         }
     };
 
-    let sheets_api = SHEETS.get().unwrap();
-    let spreadsheet_id = &CONFIG.get().unwrap().character_spreadsheet_id;
-
-    match assert_character_name(sheets_api, spreadsheet_id, &character_name).await {
-        Ok(()) => {}
-        Err(m) => {
-            ctx.say(m).await?;
-            return Ok(());
+    let data = ctx.data();
+    let sheets_api = &data.sheets;
+    let spreadsheet_id = &data.config.character_spreadsheet_id;
+
+    assert_character_name(sheets_api, spreadsheet_id, &character_name).await?;
+
+    let guild_id = ctx.guild_id().unwrap().get();
+    let author_id = ctx.author().id.get();
+    let mut stored = data
+        .sheet_db()
+        .claim_sheet(guild_id, author_id, &character_name);
+    // Only GMs may take over a character another player claimed
+    if matches!(stored, Ok(false)) {
+        if permissions::level(ctx).await? < permissions::Level::Gm {
+            return Err(Error::InvalidArgument(
+                Message::new("claim-taken").arg("character", &character_name),
+            ));
         }
+        stored = data
+            .sheet_db()
+            .store_sheet(guild_id, author_id, &character_name)
+            .map(|()| true);
     }
 
-    let guild_id = ctx.guild_id().unwrap();
-    let sheet_db = unsafe { SHEET_DB.get_mut() }.unwrap();
-
-    match sheet_db.store_sheet(guild_id.into(), ctx.author().id.into(), &character_name) {
-        Ok(()) => {
-            ctx.say(format!("Claimed sheet {}", character_name))
-                .await?
+    match stored {
+        Ok(_) => {
+            reply::say(
+                ctx,
+                tr!(tr, "claim-done", character = character_name.as_str()),
+            )
+            .await?
         }
-        Err(_) => {
-            ctx.say(format!("Failed claiming sheet {}", character_name))
-                .await?
+        Err(err) => {
+            tracing::error!("Failed claiming sheet {}: {}", character_name, err);
+            reply::say(
+                ctx,
+                tr!(tr, "claim-failed", character = character_name.as_str()),
+            )
+            .await?
         }
     };
     Ok(())
 }
 
 /// Check which character you have claimed
-async fn my_character_impl(ctx: &PoiseContext<'_>) -> Result<String, String> {
+async fn my_character_impl(ctx: &PoiseContext<'_>) -> Result<String, Error> {
     let guild_id = ctx.guild_id().unwrap();
     let author_id = ctx.author().id;
-    let sheet_db = unsafe { SHEET_DB.get_mut() }.unwrap();
+    let sheet = ctx
+        .data()
+        .sheet_db()
+        .get_sheet(guild_id.into(), author_id.into())?;
 
-    match sheet_db.get_sheet(guild_id.into(), author_id.into()) {
-        Ok(name) => name.ok_or("You have not claimed a character yet!".to_owned()),
-        Err(err) => Err(format!("Failed fetching your character: {}", err)),
-    }
+    sheet.ok_or(Error::NoClaimedCharacter)
 }
 
 #[poise::command(prefix_command, slash_command, guild_only)]
 async fn my_character(ctx: PoiseContext<'_>) -> Result<(), Error> {
-    match my_character_impl(&ctx).await {
-        Ok(name) => {
-            ctx.say(format!("Your claimed character is {}", name)).await?
-        }
-        Err(err) => ctx.say(err).await?,
-    };
+    let name = my_character_impl(&ctx).await?;
+    let tr = translator(ctx);
+    reply::say(ctx, tr!(tr, "my-character", character = name)).await?;
     Ok(())
 }
 
-async fn assert_character_name(
-    sheets_api: &SheetsAPI,
-    spreadsheet_id: &str,
-    character_name: &str,
-) -> Result<(), String> {
-    let (_, spreadsheet) = sheets_api
-        .spreadsheets()
-        .get(spreadsheet_id)
-        .param("fields", "sheets.properties.title")
-        .doit()
-        .await
-        .expect("Unable to fetch sheet");
-
-    if match &spreadsheet.sheets {
-        Some(sheets) => !sheets
-            .iter()
-            .filter_map(|s| s.properties.as_ref())
-            .filter_map(|p| p.title.as_ref())
-            .any(|t| t == character_name),
-        None => true,
-    } {
-        Err(format!(
-            "ERROR: character name '{}' does not correspond to a valid sheet in spreadsheet!",
-            character_name
-        ))
-    } else {
-        Ok(())
-    }
-}
-
-
-
 async fn check_impl(
     ctx: &PoiseContext<'_>,
     character_name: &str,
     first_ability: &str,
     second_ability: Option<&str>,
+    target: Option<i16>,
+    fate: Option<FateUse>,
+    mark: bool,
 ) -> Result<(), Error> {
-    let spreadsheet_id = &CONFIG.get().unwrap().character_spreadsheet_id;
+    let data = ctx.data();
+    let abilities: Vec<&str> = [Some(first_ability), second_ability]
+        .into_iter()
+        .flatten()
+        .collect();
+    let abilities = resolve_abilities(data, character_name, &abilities).await?;
+
+    // Only spend the point and mark once the abilities are known to exist, and do both or
+    // neither
+    let guild_id = ctx.guild_id().unwrap().get();
+    data.sheet_db().transaction(|sheet_db| {
+        if fate.is_some() {
+            fate::spend_for_check(sheet_db, guild_id, character_name)?;
+        }
+        if mark {
+            experience::mark_abilities(
+                sheet_db,
+                guild_id,
+                ctx.channel_id().get(),
+                character_name,
+                &abilities,
+            )?;
+        }
+        Ok::<_, Error>(())
+    })?;
+
+    let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_entropy();
+    let mut result = CheckResult::roll(character_name, abilities, target, &mut rng);
+    if let Some(fate) = fate {
+        result.use_fate(fate, &mut rng);
+    }
 
-    let sheets_api = SHEETS.get().unwrap();
+    let posted = check::post_result(*ctx, &result).await?;
 
-    match assert_character_name(sheets_api, spreadsheet_id, character_name).await {
-        Ok(()) => {}
-        Err(m) => {
-            ctx.say(m).await?;
-            return Ok(());
-        }
+    let recorded = data.sheet_db().record_session_roll(
+        ctx.guild_id().unwrap().into(),
+        ctx.channel_id().into(),
+        character_name,
+        &posted.text,
+    );
+    if let Err(err) = recorded {
+        tracing::warn!("Failed recording check in session: {}", err);
     }
 
-    // get full ability names and ability values from spreadsheets
-    let mut ability_value_pairs: [Option<(String, u8)>; 2] = [None, None];
-    for (ability, pair) in [Some(first_ability), second_ability]
-        .iter()
-        .zip(ability_value_pairs.iter_mut())
-    {
-        let ability = match ability {
-            Some(ability) => ability,
-            None => break,
-        };
-        *pair = match get_ability_value(sheets_api, spreadsheet_id, character_name, ability).await {
-            Ok(res) => Some(res),
+    let narrate = async {
+        match narration::narrate(*ctx, &result).await {
+            Ok(Some(narration)) => reply::say(*ctx, format!("*{}*", narration)).await,
+            Ok(None) => Ok(()),
+            // The roll stands even if the narration fails
             Err(err) => {
-                ctx.say(
-                    format!("ERROR fetching value for ability {}: {}", &ability, err),
-                )
-                .await?;
-                return Ok(());
+                tracing::warn!("Failed narrating check: {}", err);
+                Ok(())
             }
-        };
-    }
-
-    // roll
-    let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_entropy();
-    let dist: rand::distributions::Uniform<u8> =
-        rand::distributions::Uniform::<u8>::new_inclusive(1, 4);
-    let pos_roll = rng.sample(dist);
-    let neg_roll = rng.sample(dist);
-    let (first_ability, second_ability) = (
-        ability_value_pairs[0].as_ref().unwrap(),
-        &ability_value_pairs[1],
-    );
-    let expression = match second_ability {
-        Some(second_ability) => format!(
-            "**{}** rolls **{}**({}) + **{}**({}) + d4({}) - d4({}) = **{}**",
-            character_name,
-            first_ability.0,
-            first_ability.1,
-            second_ability.0,
-            second_ability.1,
-            pos_roll,
-            neg_roll,
-            first_ability.1 + second_ability.1 + pos_roll - neg_roll
-        ),
-        None => format!(
-            "**{}** rolls **{}**(2x{}) + d4({}) - d4({}) = **{}**",
-            character_name,
-            first_ability.0,
-            first_ability.1,
-            pos_roll,
-            neg_roll,
-            2 * first_ability.1 + pos_roll - neg_roll
-        ),
+        }
     };
-
-    ctx.say(expression).await?;
-    Ok(())
+    // The buttons work right away, while the narration may take until the LLM times out
+    let (narrated, adjusted) = tokio::join!(narrate, posted.adjust(*ctx, result.clone()));
+    narrated.and(adjusted)
 }
 
-
 /// Roll a value on the character sheet of a given character
 #[poise::command(prefix_command, slash_command, guild_only)]
 async fn check_character(
@@ -241,41 +252,21 @@ async fn check_character(
     #[description = "Character you want to roll for"] character_name: String,
     #[description = "First ability you want to roll"] first_ability: String,
     #[description = "Second ability you want to roll"] second_ability: Option<String>,
+    #[description = "Total needed for the check to succeed"] target: Option<i16>,
+    #[description = "Spend a fate point to drop or reroll the negative die"] fate: Option<FateUse>,
+    #[description = "Mark the abilities for advancement in the running session"] mark: Option<bool>,
 ) -> Result<(), Error> {
-    check_impl(&ctx, &character_name, &first_ability, second_ability.as_ref().map(|x| x.as_str())).await
-}
-
-/// Run a chat completion
-#[poise::command(prefix_command, slash_command, guild_only)]
-async fn completion(
-    ctx: PoiseContext<'_>,
-    #[description = "Message you want a completion on"] message: String,
-) -> Result<(), Error> {
-    let py_app = c_str!(include_str!(concat!(
-        env!("CARGO_MANIFEST_DIR"), "/python/main.py"
-    )));
-    let from_python = Python::with_gil(|py| -> PyResult<Py<PyAny>> {
-        let sys = py.import("sys")?;
-        let path = sys.getattr("path")?;
-        path.call_method1("append", (".venv/lib/python3.12/site-packages",))?;  // append my venv path
-        let app: Py<PyAny> = PyModule::from_code(py, py_app, c_str!(""), c_str!(""))?
-        
-            .getattr("run")?
-            .into();
-        app.call1(py, (message,))
-    });
-    let res = match from_python {
-        Ok(res) => {res.to_string()},
-        Err(err) => {
-            let err_str = err.to_string();
-            println!("Error: {}", err_str);
-            ctx.say("Internal Error").await?;
-            return Ok(());
-        }
-    };
-
-    ctx.say(&res).await?;
-    Ok(())
+    permissions::ensure_may_roll_for(ctx, &character_name).await?;
+    check_impl(
+        &ctx,
+        &character_name,
+        &first_ability,
+        second_ability.as_deref(),
+        target,
+        fate,
+        mark.unwrap_or(false),
+    )
+    .await
 }
 
 /// Roll a value for your claimed character
@@ -284,14 +275,21 @@ async fn check(
     ctx: PoiseContext<'_>,
     #[description = "First ability you want to roll"] first_ability: String,
     #[description = "Second ability you want to roll"] second_ability: Option<String>,
+    #[description = "Total needed for the check to succeed"] target: Option<i16>,
+    #[description = "Spend a fate point to drop or reroll the negative die"] fate: Option<FateUse>,
+    #[description = "Mark the abilities for advancement in the running session"] mark: Option<bool>,
 ) -> Result<(), Error> {
-    match my_character_impl(&ctx).await {
-        Ok(name) => check_impl(&ctx, &name, &first_ability, second_ability.as_ref().map(|x| x.as_str())).await,
-        Err(err) => {
-            ctx.say(err).await?;
-            Ok(())
-        }
-    }
+    let name = my_character_impl(&ctx).await?;
+    check_impl(
+        &ctx,
+        &name,
+        &first_ability,
+        second_ability.as_deref(),
+        target,
+        fate,
+        mark.unwrap_or(false),
+    )
+    .await
 }
 
 /// Show this menu
@@ -310,42 +308,159 @@ You can edit your message to the bot and the bot will edit its response.",
     Ok(())
 }
 
+/// All commands of the bot, with their German names and descriptions
+fn commands(translations: &Translations) -> Vec<poise::Command<Data, Error>> {
+    let mut commands = vec![
+        i18n::language_setting(),
+        claim(),
+        my_character(),
+        check(),
+        check_character(),
+        check::plain_checks(),
+        fate::fate(),
+        initiative::init(),
+        combat::hp(),
+        combat::condition(),
+        combat::status(),
+        experience::marks(),
+        experience::advance(),
+        table::table(),
+        roll::roll(),
+        completion::completion(),
+        completion::forget(),
+        quota::usage(),
+        quota::completion_roles(),
+        rules::ask_rules(),
+        rules::reindex_rules(),
+        narration::narration(),
+        session::session(),
+        permissions::permissions(),
+        npc::npc(),
+        persona::persona(),
+        help(),
+    ];
+    translations.apply_to_commands(&mut commands);
+    commands
+}
+
+/// Reports command errors to the user and logs their details
+async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
+    match error {
+        poise::FrameworkError::Command { error, ctx, .. }
+        | poise::FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+            ..
+        } => {
+            let command = &ctx.command().qualified_name;
+            if error.is_user_error() {
+                tracing::info!("Command {} rejected: {}", command, error);
+            } else {
+                tracing::error!("Command {} failed: {} ({:?})", command, error, error);
+            }
+            let message = error.user_message(translator(ctx));
+            if let Err(err) = reply::say(ctx, message).await {
+                tracing::error!("Failed reporting error to user: {}", err);
+            }
+        }
+        error => {
+            if let Err(err) = poise::builtins::on_error(error).await {
+                tracing::error!("Failed handling framework error: {}", err);
+            }
+        }
+    }
+}
+
+/// Handles gateway events besides commands
+async fn on_event(event: &serenity::FullEvent, data: &Data) -> Result<(), Error> {
+    if let serenity::FullEvent::Message { new_message } = event {
+        if let Err(err) = session::record_message(data, new_message) {
+            tracing::warn!("Failed recording message in session: {}", err);
+        }
+    }
+    Ok(())
+}
+
+/// Prints the DB migrations that are pending for the default DB and whether they would run
+fn dry_run_migrations() {
+    match SheetDB::pending_migrations(db::DEFAULT_PATH) {
+        Ok(pending) if pending.is_empty() => println!("DB schema is up to date"),
+        Ok(pending) => {
+            println!("{} pending DB migration(s):", pending.len());
+            for migration in pending {
+                println!("  {}: {}", migration.version, migration.description);
+            }
+        }
+        Err(err) => println!("Failed inspecting DB: {}", err),
+    }
+}
+
 #[tokio::main]
 async fn main() {
-    // Prepare python interpreter for python calls
-    pyo3::prepare_freethreaded_python();
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+
+    // Only report pending migrations instead of running the bot
+    if std::env::args().any(|arg| arg == "--dry-run-migrations") {
+        dry_run_migrations();
+        return;
+    }
+
+    if let Err(err) = run().await {
+        tracing::error!("{}", err);
+        std::process::exit(1);
+    }
+}
 
+async fn run() -> Result<(), Error> {
     // Load configuration from env, failing if if it is incomplete
-    let config = CONFIG.get_or_init(Config::load);
+    let config = Config::load()?;
+
+    // Set up the LLM backend for completions
+    let llm = build_backend(&config.llm)?;
+    tracing::info!("Using {} LLM backend", config.llm.backend);
 
     // Set up Google Sheets API
     let service_account_key =
         oauth2::read_service_account_key(&config.google_application_credentials)
             .await
-            .expect("Unable to read application credentials file");
+            .map_err(|err| {
+                Error::Config(format!(
+                    "Unable to read application credentials file: {}",
+                    err
+                ))
+            })?;
 
     let auth = oauth2::ServiceAccountAuthenticator::builder(service_account_key)
         .build()
         .await
-        .expect("Failed to create authenticator");
-
-    SHEETS
-        .set(Sheets::new(
-            hyper::Client::builder().build(
-                hyper_rustls::HttpsConnectorBuilder::new()
-                    .with_native_roots()
-                    .https_or_http()
-                    .enable_http1()
-                    .enable_http2()
-                    .build(),
-            ),
-            auth,
-        ))
-        .ok();
+        .map_err(|err| Error::Config(format!("Failed to create authenticator: {}", err)))?;
+
+    let sheets = build_sheets(auth, config.sheets_base_url.as_deref());
 
     // Set up DB to store sheet mappings
-    let sheet_db = SheetDB::open().expect("Failed to open DB");
-    unsafe { SHEET_DB.set(sheet_db) }.ok();
+    let mut sheet_db = SheetDB::open()?;
+
+    // Index the rule documents for rule questions
+    if config.rules.dir.is_dir() {
+        match rules::index_rules(&mut sheet_db, &config.rules.dir) {
+            Ok(count) => tracing::info!("Indexed {} rule passages", count),
+            Err(err) => tracing::warn!("Failed indexing rules: {}", err),
+        }
+    } else {
+        tracing::info!(
+            "No rules directory {:?}, skipping rules index",
+            config.rules.dir
+        );
+    }
+
+    let translations = Translations::load()?;
+    let commands = commands(&translations);
+
+    let discord_bot_token = config.discord_bot_token.clone();
 
     // Set up serenity bot
     let framework = poise::Framework::builder()
@@ -355,26 +470,57 @@ async fn main() {
                 case_insensitive_commands: true,
                 ..Default::default()
             },
-            commands: vec![claim(), my_character(), check(), check_character(), completion(), help()],
+            commands,
+            on_error: |error| Box::pin(on_error(error)),
+            event_handler: |_ctx, event, _framework, data| Box::pin(on_event(event, data)),
             ..Default::default()
         })
         .setup(|ctx, _ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data {})
+                Ok(Data::new(config, sheets, sheet_db, llm, translations))
             })
         })
         .build();
 
     let intents = GatewayIntents::non_privileged() | GatewayIntents::MESSAGE_CONTENT;
-    let mut client = Client::builder(&config.discord_bot_token, intents)
+    let mut client = Client::builder(&discord_bot_token, intents)
         .event_handler(Handler)
         .framework(framework)
-        .await
-        .expect("Error creating client");
+        .await?;
 
     // start listening for events by starting a single shard
-    if let Err(why) = client.start().await {
-        println!("An error occurred while running the client: {:?}", why);
+    client.start().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_are_localized_in_german() {
+        let translations = Translations::load().unwrap();
+        let commands = commands(&translations);
+        let claim = commands
+            .iter()
+            .find(|command| command.name == "claim")
+            .unwrap();
+        assert_eq!(claim.name_localizations["de"], "beanspruchen");
+        assert_eq!(
+            claim.description_localizations["de"],
+            "Beanspruche einen Charakterbogen"
+        );
+        assert_eq!(claim.parameters[0].name_localizations["de"], "charakter");
+
+        // Subcommands are localized as well
+        let init = commands
+            .iter()
+            .find(|command| command.name == "init")
+            .unwrap();
+        assert!(init
+            .subcommands
+            .iter()
+            .all(|command| command.name_localizations.contains_key("de")));
     }
 }
@@ -0,0 +1,181 @@
+//! Short in-fiction narration of check results, written by the LLM
+
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::check::CheckResult;
+use crate::completion::complete;
+use crate::error::Error;
+use crate::i18n::translator;
+use crate::llm::{ChatMessage, CompletionRequest};
+use crate::persona::{self, CompletionSettings};
+use crate::reply;
+use crate::PoiseContext;
+
+/// Persona used for narration if the guild has one with this name
+pub const NARRATOR_PERSONA: &str = "narrator";
+
+const DEFAULT_NARRATOR_PROMPT: &str =
+    "You are the narrator of a game of the tabletop role playing game Burgen & Bullywugs. \
+You are given the result of an ability check. Describe what happens in one or two vivid sentences, \
+in the language of the ability names. Do not mention numbers or dice.";
+
+/// Allows an action at most once per interval for each key
+pub struct RateLimiter {
+    interval: Duration,
+    last: Mutex<HashMap<u64, Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> RateLimiter {
+        RateLimiter {
+            interval,
+            last: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns whether the action may happen now, and if so, starts a new interval
+    pub fn try_acquire(&self, key: u64) -> bool {
+        let mut last = self.last.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        match last.get(&key) {
+            Some(previous) if now.duration_since(*previous) < self.interval => false,
+            _ => {
+                last.insert(key, now);
+                true
+            }
+        }
+    }
+}
+
+/// Structured description of the check handed to the narrator
+fn describe(result: &CheckResult) -> String {
+    let abilities: Vec<String> = result
+        .abilities
+        .iter()
+        .map(|(name, value)| format!("{} ({})", name, value))
+        .collect();
+    let mut description = format!(
+        "Character: {}\nAbilities: {}\nTotal: {}",
+        result.character_name,
+        abilities.join(", "),
+        result.total()
+    );
+    if let (Some(target), Some(margin), Some(success)) =
+        (result.target, result.margin(), result.is_success())
+    {
+        let outcome = if success { "success" } else { "failure" };
+        description.push_str(&format!(
+            "\nTarget: {}\nOutcome: {} by {}",
+            target,
+            outcome,
+            margin.abs()
+        ));
+    }
+    description
+}
+
+/// Narrates the check if narration is enabled for the guild and not rate limited
+pub async fn narrate(ctx: PoiseContext<'_>, result: &CheckResult) -> Result<Option<String>, Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+    if !data.sheet_db().narration_enabled(guild_id)?
+        || !data.narration_limiter.try_acquire(guild_id)
+    {
+        return Ok(None);
+    }
+
+    let settings = match persona::resolve(data, guild_id, Some(NARRATOR_PERSONA)) {
+        Ok(settings) => settings,
+        Err(Error::UnknownPersona(_)) => CompletionSettings {
+            system_prompt: DEFAULT_NARRATOR_PROMPT.to_owned(),
+            model: data.config.llm.model.clone(),
+            temperature: data.config.llm.temperature,
+        },
+        Err(err) => return Err(err),
+    };
+    let request = CompletionRequest {
+        settings,
+        messages: vec![ChatMessage::user(describe(result))],
+    };
+    Ok(Some(complete(ctx, request).await?.text))
+}
+
+/// Turn narration of check results by the LLM on or off
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    check = "crate::permissions::gm"
+)]
+pub async fn narration(
+    ctx: PoiseContext<'_>,
+    #[description = "Whether checks should be narrated"] enabled: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    ctx.data()
+        .sheet_db()
+        .set_narration_enabled(guild_id.into(), enabled)?;
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr.get(if enabled {
+            "narration-enabled"
+        } else {
+            "narration-disabled"
+        }),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(target: Option<i16>) -> CheckResult {
+        CheckResult {
+            character_name: "Gandalf".to_owned(),
+            abilities: vec![("Charisma".to_owned(), 3), ("Zaubern".to_owned(), 5)],
+            pos_roll: 4,
+            neg_roll: 1,
+            target,
+            modifier: 0,
+            fate: None,
+        }
+    }
+
+    #[test]
+    fn rate_limiter_allows_one_action_per_interval_and_guild() {
+        let limiter = RateLimiter::new(Duration::from_millis(100));
+        assert!(limiter.try_acquire(1));
+        assert!(!limiter.try_acquire(1));
+        // Other guilds have their own budget
+        assert!(limiter.try_acquire(2));
+        assert!(!limiter.try_acquire(2));
+
+        std::thread::sleep(Duration::from_millis(150));
+        assert!(limiter.try_acquire(1));
+        assert!(!limiter.try_acquire(1));
+        assert!(limiter.try_acquire(2));
+    }
+
+    #[test]
+    fn describe_includes_outcome_only_with_target() {
+        assert_eq!(
+            describe(&result(None)),
+            "Character: Gandalf\nAbilities: Charisma (3), Zaubern (5)\nTotal: 11"
+        );
+        assert_eq!(
+            describe(&result(Some(9))),
+            "Character: Gandalf\nAbilities: Charisma (3), Zaubern (5)\nTotal: 11\n\
+Target: 9\nOutcome: success by 2"
+        );
+        assert_eq!(
+            describe(&result(Some(14))),
+            "Character: Gandalf\nAbilities: Charisma (3), Zaubern (5)\nTotal: 11\n\
+Target: 14\nOutcome: failure by 3"
+        );
+    }
+}
@@ -0,0 +1,275 @@
+//! Improvised non-player characters, rolled from a template sheet in the character spreadsheet.
+//!
+//! The template sheet is laid out like a character sheet: ability names in column A and, in
+//! place of the values in column G, the range an ability's value is rolled from, e.g. `1-4`.
+//! Saving an NPC copies the template sheet and fills in the rolled values, so the NPC can be
+//! rolled for with `check_character` right away.
+
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::completion::complete;
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message};
+use crate::llm::{ChatMessage, CompletionRequest};
+use crate::persona;
+use crate::reply;
+use crate::sheets::{
+    a1_range, assert_character_name, duplicate_sheet, read_values, sheet_id, write_cells,
+};
+use crate::PoiseContext;
+
+/// An ability of the template sheet with the range its value is rolled from
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateAbility {
+    /// 1-based row of the ability on the sheet
+    pub row: usize,
+    pub name: String,
+    pub min: u8,
+    pub max: u8,
+}
+
+/// Parses `2-4`, `2–4` or a fixed value `3`
+pub fn parse_range<T: FromStr + PartialOrd + Copy>(value: &str) -> Option<(T, T)> {
+    let value = value.trim();
+    let (min, max) = match value.split_once(['-', '–']) {
+        Some((min, max)) => (min.trim().parse().ok()?, max.trim().parse().ok()?),
+        None => {
+            let fixed = value.parse().ok()?;
+            (fixed, fixed)
+        }
+    };
+    (min <= max).then_some((min, max))
+}
+
+/// Reads the abilities from the rows of columns A to G of a template sheet.
+/// Rows without a name or a range, such as headings, are skipped.
+pub fn parse_template(rows: &[Vec<String>]) -> Vec<TemplateAbility> {
+    rows.iter()
+        .enumerate()
+        .filter_map(|(i, row)| {
+            let name = row.first()?.trim();
+            let (min, max) = parse_range(row.get(6)?)?;
+            (!name.is_empty()).then(|| TemplateAbility {
+                row: i + 1,
+                name: name.to_owned(),
+                min,
+                max,
+            })
+        })
+        .collect()
+}
+
+/// Rolls a value for every ability of the template
+pub fn roll_abilities<R: Rng>(template: &[TemplateAbility], rng: &mut R) -> Vec<u8> {
+    template
+        .iter()
+        .map(|ability| rng.gen_range(ability.min..=ability.max))
+        .collect()
+}
+
+/// Asks the LLM for a name, personality and appearance of the NPC
+async fn describe_npc(
+    ctx: PoiseContext<'_>,
+    name: Option<&str>,
+    abilities: &str,
+) -> Result<String, Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let mut settings = persona::resolve(ctx.data(), guild_id, None)?;
+    settings.system_prompt.push_str(
+        "\n\nInvent a non-player character with the given abilities. Answer in exactly three lines:\n\
+         Name: <name>\nPersonality: <one sentence>\nAppearance: <one sentence>",
+    );
+    let mut prompt = format!("Abilities: {}", abilities);
+    if let Some(name) = name {
+        prompt.push_str(&format!("\nName: {}", name));
+    }
+    let request = CompletionRequest {
+        settings,
+        messages: vec![ChatMessage::user(prompt)],
+    };
+    Ok(complete(ctx, request).await?.text)
+}
+
+/// The name from the `Name:` line of an NPC description
+fn described_name(description: &str) -> Option<String> {
+    description.lines().find_map(|line| {
+        let line = line.trim().trim_start_matches(['*', '-', ' ']);
+        let name = line.strip_prefix("Name:")?.trim().trim_matches('*').trim();
+        (!name.is_empty()).then(|| name.to_owned())
+    })
+}
+
+/// Create an NPC from a template sheet
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    check = "crate::permissions::gm"
+)]
+pub async fn npc(
+    ctx: PoiseContext<'_>,
+    #[description = "Name of the NPC, leave empty to let the LLM pick one if describe is set"]
+    name: Option<String>,
+    #[description = "Let the LLM describe the NPC's personality and appearance"] describe: Option<
+        bool,
+    >,
+    #[description = "Save the NPC as a new character sheet"] save: Option<bool>,
+    #[description = "Template sheet to use instead of the default"] template: Option<String>,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let spreadsheet_id = &data.config.character_spreadsheet_id;
+    let template_sheet = template.unwrap_or_else(|| data.config.npc_template_sheet.clone());
+
+    let template = parse_template(
+        &read_values(
+            &data.sheets,
+            spreadsheet_id,
+            &a1_range(&template_sheet, "A:G"),
+        )
+        .await?,
+    );
+    if template.is_empty() {
+        return Err(Error::InvalidArgument(
+            Message::new("npc-empty-template").arg("sheet", &template_sheet),
+        ));
+    }
+    let values = {
+        let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_entropy();
+        roll_abilities(&template, &mut rng)
+    };
+    let abilities: Vec<String> = template
+        .iter()
+        .zip(&values)
+        .map(|(ability, value)| format!("{} {}", ability.name, value))
+        .collect();
+    let abilities = abilities.join(" · ");
+
+    let tr = translator(ctx);
+    let description = if describe.unwrap_or(false) {
+        let placeholder = ctx.say(tr.get("thinking")).await?;
+        let description = describe_npc(ctx, name.as_deref(), &abilities).await;
+        reply::delete_placeholder(ctx, placeholder).await;
+        Some(description?)
+    } else {
+        None
+    };
+    let name = name.or_else(|| description.as_deref().and_then(described_name));
+
+    let title = name.clone().unwrap_or_else(|| tr.get("npc-unnamed"));
+    let mut text = format!("**{}**\n", title);
+    if let Some(description) = &description {
+        text.push_str(description.trim());
+        text.push('\n');
+    }
+    text.push_str(&abilities);
+
+    if save.unwrap_or(false) {
+        let name = name.ok_or_else(|| Error::InvalidArgument(Message::new("npc-needs-name")))?;
+        match assert_character_name(&data.sheets, spreadsheet_id, &name).await {
+            Ok(()) => {
+                return Err(Error::InvalidArgument(
+                    Message::new("npc-sheet-exists").arg("name", &name),
+                ))
+            }
+            Err(Error::InvalidCharacter(_)) => {}
+            Err(err) => return Err(err),
+        }
+        let source = sheet_id(&data.sheets, spreadsheet_id, &template_sheet)
+            .await?
+            .ok_or_else(|| Error::InvalidCharacter(template_sheet.clone()))?;
+        duplicate_sheet(&data.sheets, spreadsheet_id, source, &name).await?;
+        let cells = template
+            .iter()
+            .zip(&values)
+            .map(|(ability, value)| {
+                (
+                    a1_range(&name, &format!("G{}", ability.row)),
+                    value.to_string(),
+                )
+            })
+            .collect();
+        write_cells(&data.sheets, spreadsheet_id, cells).await?;
+        text.push_str("\n-# ");
+        text.push_str(&tr!(tr, "npc-saved", name = name));
+    }
+
+    reply::say(ctx, text).await
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::test_support::{MockGoogle, SPREADSHEET_ID};
+
+    #[tokio::test]
+    async fn template_rows_without_a_range_are_skipped() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+
+        let rows = read_values(
+            &data.sheets,
+            SPREADSHEET_ID,
+            &a1_range("NPC Template", "A:G"),
+        )
+        .await
+        .unwrap();
+        let names: Vec<(String, usize)> = parse_template(&rows)
+            .into_iter()
+            .map(|ability| (ability.name, ability.row))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Charisma".to_owned(), 2),
+                ("Stärke".to_owned(), 3),
+                ("Zaubern".to_owned(), 4)
+            ]
+        );
+    }
+
+    #[test]
+    fn abilities_are_rolled_within_their_ranges() {
+        let template = vec![
+            TemplateAbility {
+                row: 2,
+                name: "Charisma".to_owned(),
+                min: 1,
+                max: 3,
+            },
+            TemplateAbility {
+                row: 3,
+                name: "Stärke".to_owned(),
+                min: 2,
+                max: 2,
+            },
+        ];
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        for _ in 0..20 {
+            let values = roll_abilities(&template, &mut rng);
+            assert!((1..=3).contains(&values[0]));
+            assert_eq!(values[1], 2);
+        }
+    }
+
+    #[test]
+    fn ranges_are_single_values_or_ascending_spans() {
+        assert_eq!(parse_range::<u8>("1-3"), Some((1, 3)));
+        assert_eq!(parse_range::<u8>(" 2 – 4 "), Some((2, 4)));
+        assert_eq!(parse_range::<u8>("2"), Some((2, 2)));
+        assert_eq!(parse_range::<u8>("4–2"), None);
+        assert_eq!(parse_range::<u8>("a-b"), None);
+    }
+
+    #[test]
+    fn described_names_are_read_from_the_name_line() {
+        assert_eq!(
+            described_name("**Name:** Grimbold\nPersonality: Grumpy"),
+            Some("Grimbold".to_owned())
+        );
+        assert_eq!(described_name("Personality: Grumpy"), None);
+    }
+}
@@ -0,0 +1,264 @@
+//! Permission levels of guild members.
+//!
+//! Admins are members with the *Manage Server* permission, GMs are members with the guild's GM
+//! role, and everyone else is a player. Commands that need more than the player level use
+//! [`gm`] or [`admin`] as poise checks.
+
+use std::fmt::{self, Display};
+
+use poise::serenity_prelude as serenity;
+
+use crate::error::Error;
+use crate::i18n::{tr, translator};
+use crate::reply;
+use crate::PoiseContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Player,
+    Gm,
+    Admin,
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Level::Player => "player",
+            Level::Gm => "GM",
+            Level::Admin => "admin",
+        })
+    }
+}
+
+/// Determines the level of the command's author
+pub async fn level(ctx: PoiseContext<'_>) -> Result<Level, Error> {
+    level_of(ctx, ctx.author().id).await
+}
+
+/// Determines the level of a member in the guild and channel of the command
+pub async fn level_of(ctx: PoiseContext<'_>, user_id: serenity::UserId) -> Result<Level, Error> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(Level::Player);
+    };
+    let guild = guild_id.to_partial_guild(ctx).await?;
+    let member = guild.member(ctx, user_id).await?;
+    if let Some(channel) = ctx.guild_channel().await {
+        if guild.user_permissions_in(&channel, &member).manage_guild() {
+            return Ok(Level::Admin);
+        }
+    }
+
+    let gm_role = ctx.data().sheet_db().get_gm_role(guild_id.get())?;
+    match gm_role {
+        Some(gm_role) if member.roles.iter().any(|role| role.get() == gm_role) => Ok(Level::Gm),
+        _ => Ok(Level::Player),
+    }
+}
+
+/// Fails unless the author has at least the given level
+pub async fn ensure_level(ctx: PoiseContext<'_>, required: Level) -> Result<(), Error> {
+    if level(ctx).await? >= required {
+        Ok(())
+    } else {
+        Err(Error::MissingLevel(required))
+    }
+}
+
+/// Check for commands reserved to GMs and admins
+pub async fn gm(ctx: PoiseContext<'_>) -> Result<bool, Error> {
+    ensure_level(ctx, Level::Gm).await?;
+    Ok(true)
+}
+
+/// Check for commands reserved to admins
+pub async fn admin(ctx: PoiseContext<'_>) -> Result<bool, Error> {
+    ensure_level(ctx, Level::Admin).await?;
+    Ok(true)
+}
+
+/// Whether a member of the given level may roll for the character: GMs may roll for every
+/// character, players only for their claimed one unless the GM has opened rolls for all
+fn may_roll_for(
+    level: Level,
+    claimed: Option<&str>,
+    open_rolls: bool,
+    character_name: &str,
+) -> bool {
+    level >= Level::Gm || open_rolls || claimed == Some(character_name)
+}
+
+/// Fails if the author is a player who may not roll for the character, because they have not
+/// claimed it and the GM has not opened rolls for all characters
+pub async fn ensure_may_roll_for(ctx: PoiseContext<'_>, character_name: &str) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let (claimed, open_rolls) = {
+        let mut sheet_db = ctx.data().sheet_db();
+        (
+            sheet_db.get_sheet(guild_id, ctx.author().id.get())?,
+            sheet_db.open_rolls(guild_id)?,
+        )
+    };
+    let may_roll = |level| may_roll_for(level, claimed.as_deref(), open_rolls, character_name);
+    // Determining the level asks Discord, so only do it if being a player is not enough
+    if may_roll(Level::Player) || may_roll(level(ctx).await?) {
+        Ok(())
+    } else {
+        Err(Error::NotYourCharacter(character_name.to_owned()))
+    }
+}
+
+/// Manage who may do what on this server
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("permissions_gm_role", "permissions_open_rolls", "permissions_show"),
+    subcommand_required
+)]
+pub async fn permissions(_ctx: PoiseContext<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Set the role whose members are GMs, or remove it
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "gm_role",
+    check = "admin"
+)]
+async fn permissions_gm_role(
+    ctx: PoiseContext<'_>,
+    #[description = "GM role, leave empty to have no GMs besides admins"] role: Option<
+        serenity::Role,
+    >,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    ctx.data()
+        .sheet_db()
+        .set_gm_role(guild_id, role.as_ref().map(|role| role.id.get()))?;
+    let tr = translator(ctx);
+    match role {
+        Some(role) => reply::say(ctx, tr!(tr, "gm-role-set", role = role.name)).await,
+        None => reply::say(ctx, tr.get("gm-role-removed")).await,
+    }
+}
+
+/// Allow or forbid players to roll for characters they have not claimed
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "open_rolls",
+    check = "gm"
+)]
+async fn permissions_open_rolls(
+    ctx: PoiseContext<'_>,
+    #[description = "Whether players may roll for any character"] open: bool,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    ctx.data().sheet_db().set_open_rolls(guild_id, open)?;
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr.get(if open {
+            "open-rolls-enabled"
+        } else {
+            "open-rolls-disabled"
+        }),
+    )
+    .await
+}
+
+/// Show the GM role and your own permission level
+#[poise::command(prefix_command, slash_command, guild_only, rename = "show")]
+async fn permissions_show(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let (gm_role, open_rolls) = {
+        let mut sheet_db = ctx.data().sheet_db();
+        (
+            sheet_db.get_gm_role(guild_id)?,
+            sheet_db.open_rolls(guild_id)?,
+        )
+    };
+    let level = level(ctx).await?;
+    let tr = translator(ctx);
+    let gm_role = gm_role.map_or_else(
+        || tr.get("permissions-no-gm-role"),
+        |role| format!("<@&{}>", role),
+    );
+    let rolls = tr.get(if open_rolls {
+        "permissions-rolls-any"
+    } else {
+        "permissions-rolls-claimed"
+    });
+    let level = tr.get(match level {
+        Level::Player => "level-player",
+        Level::Gm => "level-gm",
+        Level::Admin => "level-admin",
+    });
+    ctx.send(
+        poise::CreateReply::default()
+            .content(tr!(
+                tr,
+                "permissions-show",
+                gm_role = gm_role,
+                rolls = rolls,
+                level = level
+            ))
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SheetDB;
+
+    #[test]
+    fn players_may_roll_for_their_claimed_character() {
+        assert!(may_roll_for(Level::Player, Some("Bilbo"), false, "Bilbo"));
+        assert!(!may_roll_for(
+            Level::Player,
+            Some("Bilbo"),
+            false,
+            "Gandalf"
+        ));
+        assert!(!may_roll_for(Level::Player, None, false, "Gandalf"));
+    }
+
+    #[test]
+    fn open_rolls_let_players_roll_for_any_character() {
+        assert!(may_roll_for(Level::Player, Some("Bilbo"), true, "Gandalf"));
+        assert!(may_roll_for(Level::Player, None, true, "Gandalf"));
+    }
+
+    #[test]
+    fn gms_may_roll_for_any_character() {
+        assert!(may_roll_for(Level::Gm, None, false, "Gandalf"));
+        assert!(may_roll_for(Level::Admin, Some("Bilbo"), false, "Gandalf"));
+    }
+
+    #[test]
+    fn gm_role_and_open_rolls_are_stored_per_guild() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        assert_eq!(sheet_db.get_gm_role(1).unwrap(), None);
+        assert!(!sheet_db.open_rolls(1).unwrap());
+
+        sheet_db.set_gm_role(1, Some(42)).unwrap();
+        sheet_db.set_open_rolls(1, true).unwrap();
+        assert_eq!(sheet_db.get_gm_role(1).unwrap(), Some(42));
+        assert!(sheet_db.open_rolls(1).unwrap());
+        assert_eq!(sheet_db.get_gm_role(2).unwrap(), None);
+        assert!(!sheet_db.open_rolls(2).unwrap());
+
+        // Both live in the same row of the guild's settings, changing one keeps the other
+        sheet_db.set_gm_role(1, None).unwrap();
+        assert_eq!(sheet_db.get_gm_role(1).unwrap(), None);
+        assert!(sheet_db.open_rolls(1).unwrap());
+        sheet_db.set_open_rolls(1, false).unwrap();
+        assert!(!sheet_db.open_rolls(1).unwrap());
+    }
+}
@@ -0,0 +1,266 @@
+use crate::db::Persona;
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message};
+use crate::reply;
+use crate::{Data, PoiseContext};
+
+/// Everything the LLM needs to know besides the messages themselves
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionSettings {
+    pub system_prompt: String,
+    pub model: String,
+    pub temperature: f64,
+}
+
+/// Resolves the persona with the given name, or the guild's default persona if no name is
+/// given. Settings a persona leaves open fall back to the configured defaults.
+pub fn resolve(
+    data: &Data,
+    guild_id: u64,
+    name: Option<&str>,
+) -> Result<CompletionSettings, Error> {
+    let defaults = &data.config.llm;
+    let persona = {
+        let mut sheet_db = data.sheet_db();
+        match name {
+            Some(name) => Some(
+                sheet_db
+                    .get_persona(guild_id, name)?
+                    .ok_or_else(|| Error::UnknownPersona(name.to_owned()))?,
+            ),
+            None => match sheet_db.get_default_persona(guild_id)? {
+                Some(name) => sheet_db.get_persona(guild_id, &name)?,
+                None => None,
+            },
+        }
+    };
+
+    Ok(match persona {
+        Some(persona) => CompletionSettings {
+            system_prompt: persona.system_prompt,
+            model: persona.model.unwrap_or_else(|| defaults.model.clone()),
+            temperature: persona.temperature.unwrap_or(defaults.temperature),
+        },
+        None => CompletionSettings {
+            system_prompt: defaults.system_prompt.clone(),
+            model: defaults.model.clone(),
+            temperature: defaults.temperature,
+        },
+    })
+}
+
+/// Manage the personas used for completions
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands(
+        "persona_set",
+        "persona_list",
+        "persona_show",
+        "persona_remove",
+        "persona_default"
+    ),
+    subcommand_required
+)]
+pub async fn persona(_ctx: PoiseContext<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Create or replace a persona
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "set",
+    check = "crate::permissions::gm"
+)]
+async fn persona_set(
+    ctx: PoiseContext<'_>,
+    #[description = "Name of the persona, e.g. narrator"] name: String,
+    #[description = "System prompt that tells the LLM how to behave"] system_prompt: String,
+    #[description = "Model to use instead of the default"] model: Option<String>,
+    #[description = "Sampling temperature between 0 and 2"] temperature: Option<f64>,
+) -> Result<(), Error> {
+    if let Some(temperature) = temperature {
+        if !(0.0..=2.0).contains(&temperature) {
+            return Err(Error::InvalidArgument(Message::new(
+                "persona-invalid-temperature",
+            )));
+        }
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let persona = Persona {
+        name,
+        system_prompt,
+        model,
+        temperature,
+    };
+    ctx.data()
+        .sheet_db()
+        .store_persona(guild_id.into(), &persona)?;
+    let tr = translator(ctx);
+    reply::say(ctx, tr!(tr, "persona-stored", name = persona.name.as_str())).await?;
+    Ok(())
+}
+
+/// List the personas of this server
+#[poise::command(prefix_command, slash_command, guild_only, rename = "list")]
+async fn persona_list(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let (names, default) = {
+        let mut sheet_db = ctx.data().sheet_db();
+        (
+            sheet_db.list_personas(guild_id.into())?,
+            sheet_db.get_default_persona(guild_id.into())?,
+        )
+    };
+
+    let tr = translator(ctx);
+    if names.is_empty() {
+        reply::say(ctx, tr.get("persona-none")).await?;
+        return Ok(());
+    }
+    let lines: Vec<String> = names
+        .iter()
+        .map(|name| match &default {
+            Some(default) if default == name => {
+                format!(
+                    "- {}",
+                    tr!(tr, "persona-default-marker", name = name.as_str())
+                )
+            }
+            _ => format!("- {}", name),
+        })
+        .collect();
+    reply::say(ctx, lines.join("\n")).await?;
+    Ok(())
+}
+
+/// Show the settings of a persona
+#[poise::command(prefix_command, slash_command, guild_only, rename = "show")]
+async fn persona_show(
+    ctx: PoiseContext<'_>,
+    #[description = "Name of the persona"] name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let settings = resolve(ctx.data(), guild_id.into(), Some(&name))?;
+    let tr = translator(ctx);
+    let header = tr!(
+        tr,
+        "persona-settings",
+        name = name,
+        model = settings.model,
+        temperature = settings.temperature
+    );
+    reply::say(ctx, format!("{}\n>>> {}", header, settings.system_prompt)).await?;
+    Ok(())
+}
+
+/// Remove a persona
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "remove",
+    check = "crate::permissions::gm"
+)]
+async fn persona_remove(
+    ctx: PoiseContext<'_>,
+    #[description = "Name of the persona"] name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    if !ctx
+        .data()
+        .sheet_db()
+        .remove_persona(guild_id.into(), &name)?
+    {
+        return Err(Error::UnknownPersona(name));
+    }
+    let tr = translator(ctx);
+    reply::say(ctx, tr!(tr, "persona-removed", name = name)).await?;
+    Ok(())
+}
+
+/// Set the persona used when a completion names none, or reset it to the built-in one
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "default",
+    check = "crate::permissions::gm"
+)]
+async fn persona_default(
+    ctx: PoiseContext<'_>,
+    #[description = "Name of the persona, leave empty for the built-in default"] name: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    {
+        let mut sheet_db = ctx.data().sheet_db();
+        if let Some(name) = &name {
+            if sheet_db.get_persona(guild_id.into(), name)?.is_none() {
+                return Err(Error::UnknownPersona(name.clone()));
+            }
+        }
+        sheet_db.set_default_persona(guild_id.into(), name.as_deref())?;
+    }
+
+    let tr = translator(ctx);
+    match name {
+        Some(name) => reply::say(ctx, tr!(tr, "persona-default-set", name = name)).await?,
+        None => reply::say(ctx, tr.get("persona-default-reset")).await?,
+    };
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::MockGoogle;
+
+    #[tokio::test]
+    async fn settings_fall_back_to_the_configured_defaults() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+        let defaults = CompletionSettings {
+            system_prompt: "You are a test".to_owned(),
+            model: "test-model".to_owned(),
+            temperature: 0.0,
+        };
+        assert_eq!(resolve(&data, 1, None).unwrap(), defaults);
+        assert!(matches!(
+            resolve(&data, 1, Some("sage")),
+            Err(Error::UnknownPersona(_))
+        ));
+
+        data.sheet_db()
+            .store_persona(
+                1,
+                &Persona {
+                    name: "sage".to_owned(),
+                    system_prompt: "Advise".to_owned(),
+                    model: None,
+                    temperature: Some(0.5),
+                },
+            )
+            .unwrap();
+        let sage = CompletionSettings {
+            system_prompt: "Advise".to_owned(),
+            model: "test-model".to_owned(),
+            temperature: 0.5,
+        };
+        assert_eq!(resolve(&data, 1, Some("sage")).unwrap(), sage);
+        // Without a default persona, the guild still gets the configured settings
+        assert_eq!(resolve(&data, 1, None).unwrap(), defaults);
+        data.sheet_db()
+            .set_default_persona(1, Some("sage"))
+            .unwrap();
+        assert_eq!(resolve(&data, 1, None).unwrap(), sage);
+        // A removed default persona falls back as well
+        data.sheet_db().remove_persona(1, "sage").unwrap();
+        assert_eq!(resolve(&data, 1, None).unwrap(), defaults);
+    }
+}
@@ -0,0 +1,259 @@
+//! Limits on who may use completions and how much: a per-guild role allowlist, daily quotas
+//! per user and per guild, and a blocklist for prompts and responses.
+
+use poise::serenity_prelude as serenity;
+
+use crate::config::QuotaConfig;
+use crate::db::Usage;
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message, Tr};
+use crate::reply;
+use crate::{Data, PoiseContext};
+
+/// Fails unless the author has one of the guild's completion roles, or the guild has none
+pub async fn ensure_allowed(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap();
+    let allowed = ctx
+        .data()
+        .sheet_db()
+        .list_completion_roles(guild_id.get())?;
+    if allowed.is_empty() {
+        return Ok(());
+    }
+    let member = ctx.author_member().await.ok_or(Error::NotAllowed)?;
+    if member
+        .roles
+        .iter()
+        .any(|role| allowed.contains(&role.get()))
+    {
+        Ok(())
+    } else {
+        Err(Error::NotAllowed)
+    }
+}
+
+/// Message ID of the first limit that today's usage has reached, if any
+fn exceeded_quota(quota: &QuotaConfig, user: Usage, guild: Usage) -> Option<&'static str> {
+    let reached = |used: u64, limit: u64| limit > 0 && used >= limit;
+    if reached(user.requests, quota.user_requests) {
+        Some("error-quota-user-requests")
+    } else if reached(user.tokens, quota.user_tokens) {
+        Some("error-quota-user-tokens")
+    } else if reached(guild.requests, quota.guild_requests) {
+        Some("error-quota-guild-requests")
+    } else if reached(guild.tokens, quota.guild_tokens) {
+        Some("error-quota-guild-tokens")
+    } else {
+        None
+    }
+}
+
+/// Fails if the user or the guild have used up one of their daily quotas
+pub fn ensure_within_quota(data: &Data, guild_id: u64, user_id: u64) -> Result<(), Error> {
+    let (user, guild) = {
+        let mut sheet_db = data.sheet_db();
+        (
+            sheet_db.get_usage(guild_id, Some(user_id))?,
+            sheet_db.get_usage(guild_id, None)?,
+        )
+    };
+    match exceeded_quota(&data.config.quota, user, guild) {
+        Some(quota) => Err(Error::QuotaExceeded(quota)),
+        None => Ok(()),
+    }
+}
+
+/// Fails if the text contains one of the blocked terms
+pub fn ensure_not_blocked(quota: &QuotaConfig, text: &str) -> Result<(), Error> {
+    let text = text.to_lowercase();
+    if quota
+        .blocklist
+        .iter()
+        .any(|term| text.contains(term.as_str()))
+    {
+        Err(Error::ContentBlocked)
+    } else {
+        Ok(())
+    }
+}
+
+fn format_usage(tr: Tr, usage: Usage, requests: u64, tokens: u64) -> String {
+    let limit = |limit: u64| {
+        if limit == 0 {
+            tr.get("usage-unlimited")
+        } else {
+            limit.to_string()
+        }
+    };
+    tr!(
+        tr,
+        "usage-line",
+        requests = usage.requests,
+        request_limit = limit(requests),
+        tokens = usage.tokens,
+        token_limit = limit(tokens)
+    )
+}
+
+/// Show today's completion usage of you and this server
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn usage(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+    let (user, guild) = {
+        let mut sheet_db = data.sheet_db();
+        (
+            sheet_db.get_usage(guild_id, Some(ctx.author().id.get()))?,
+            sheet_db.get_usage(guild_id, None)?,
+        )
+    };
+    let quota = &data.config.quota;
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr!(
+            tr,
+            "usage-summary",
+            user = format_usage(tr, user, quota.user_requests, quota.user_tokens),
+            server = format_usage(tr, guild, quota.guild_requests, quota.guild_tokens)
+        ),
+    )
+    .await
+}
+
+/// Manage the roles that may use completions
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands(
+        "completion_roles_allow",
+        "completion_roles_disallow",
+        "completion_roles_list"
+    ),
+    subcommand_required
+)]
+pub async fn completion_roles(_ctx: PoiseContext<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Allow a role to use completions. Once a role is allowed, members without one may not.
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "allow",
+    check = "crate::permissions::admin"
+)]
+async fn completion_roles_allow(
+    ctx: PoiseContext<'_>,
+    #[description = "Role that may use completions"] role: serenity::Role,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    ctx.data()
+        .sheet_db()
+        .add_completion_role(guild_id, role.id.get())?;
+    let tr = translator(ctx);
+    reply::say(ctx, tr!(tr, "completion-role-allowed", role = role.name)).await
+}
+
+/// Take back a role's permission to use completions
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "disallow",
+    check = "crate::permissions::admin"
+)]
+async fn completion_roles_disallow(
+    ctx: PoiseContext<'_>,
+    #[description = "Role that may no longer use completions"] role: serenity::Role,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    if !ctx
+        .data()
+        .sheet_db()
+        .remove_completion_role(guild_id, role.id.get())?
+    {
+        return Err(Error::InvalidArgument(
+            Message::new("completion-role-not-allowed").arg("role", &role.name),
+        ));
+    }
+    let tr = translator(ctx);
+    reply::say(ctx, tr!(tr, "completion-role-disallowed", role = role.name)).await
+}
+
+/// List the roles that may use completions
+#[poise::command(prefix_command, slash_command, guild_only, rename = "list")]
+async fn completion_roles_list(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let roles = ctx.data().sheet_db().list_completion_roles(guild_id)?;
+    let tr = translator(ctx);
+    if roles.is_empty() {
+        return reply::say(ctx, tr.get("completion-roles-everyone")).await;
+    }
+    let mentions: Vec<String> = roles.iter().map(|role| format!("<@&{}>", role)).collect();
+    ctx.send(
+        poise::CreateReply::default()
+            .content(tr!(
+                tr,
+                "completion-roles-list",
+                roles = mentions.join(", ")
+            ))
+            .allowed_mentions(serenity::CreateAllowedMentions::new()),
+    )
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SheetDB;
+
+    #[test]
+    fn quotas_count_daily_usage_per_user_and_guild() {
+        let quota = QuotaConfig {
+            user_requests: 2,
+            user_tokens: 0,
+            guild_requests: 0,
+            guild_tokens: 100,
+            blocklist: vec!["forbidden".to_owned()],
+        };
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        let usage = |sheet_db: &mut SheetDB, user_id| {
+            (
+                sheet_db.get_usage(1, Some(user_id)).unwrap(),
+                sheet_db.get_usage(1, None).unwrap(),
+            )
+        };
+
+        sheet_db.record_usage(1, 10, 20).unwrap();
+        let (user, guild) = usage(&mut sheet_db, 10);
+        assert_eq!(exceeded_quota(&quota, user, guild), None);
+
+        sheet_db.record_usage(1, 10, 20).unwrap();
+        let (user, guild) = usage(&mut sheet_db, 10);
+        assert_eq!(
+            user,
+            Usage {
+                requests: 2,
+                tokens: 40
+            }
+        );
+        assert_eq!(
+            exceeded_quota(&quota, user, guild),
+            Some("error-quota-user-requests")
+        );
+
+        sheet_db.record_usage(1, 11, 60).unwrap();
+        let (user, guild) = usage(&mut sheet_db, 11);
+        assert_eq!(
+            exceeded_quota(&quota, user, guild),
+            Some("error-quota-guild-tokens")
+        );
+
+        assert!(ensure_not_blocked(&quota, "Something FORBIDDEN").is_err());
+        assert!(ensure_not_blocked(&quota, "Something allowed").is_ok());
+    }
+}
@@ -0,0 +1,209 @@
+//! Replies that respect Discord's message size limit.
+//!
+//! Long texts are split into several messages at paragraph, line or sentence boundaries,
+//! closing and reopening Markdown code fences where a split falls inside one. Texts above the
+//! configured attachment threshold are sent as a Markdown file instead.
+
+use poise::serenity_prelude::CreateAttachment;
+use poise::{CreateReply, ReplyHandle};
+
+use crate::error::Error;
+use crate::i18n::translator;
+use crate::PoiseContext;
+
+/// Maximum number of characters Discord accepts in a message
+pub const MESSAGE_LIMIT: usize = 2000;
+
+const FENCE: &str = "```";
+/// Places to split at, from most to least preferred
+const SEPARATORS: [&str; 6] = ["\n\n", "\n", ". ", "! ", "? ", " "];
+const ATTACHMENT_NAME: &str = "reply.md";
+
+/// Replies with `text`, split into several messages or sent as a file if it is too long
+pub async fn say(ctx: PoiseContext<'_>, text: impl Into<String>) -> Result<(), Error> {
+    let text = text.into();
+    if needs_attachment(ctx, &text) {
+        ctx.send(attachment_reply(ctx, text)).await?;
+        return Ok(());
+    }
+    for chunk in split_message(&text, MESSAGE_LIMIT) {
+        ctx.say(chunk).await?;
+    }
+    Ok(())
+}
+
+/// Replaces the content of an earlier reply with `text`, continuing in new messages if it is
+/// too long
+pub async fn edit(
+    ctx: PoiseContext<'_>,
+    handle: ReplyHandle<'_>,
+    text: impl Into<String>,
+) -> Result<(), Error> {
+    let text = text.into();
+    if needs_attachment(ctx, &text) {
+        // Discord does not allow adding files to every kind of reply, so send a new one
+        handle.delete(ctx).await?;
+        ctx.send(attachment_reply(ctx, text)).await?;
+        return Ok(());
+    }
+    let mut chunks = split_message(&text, MESSAGE_LIMIT).into_iter();
+    let first = chunks.next().unwrap_or_default();
+    handle
+        .edit(ctx, CreateReply::default().content(first))
+        .await?;
+    for chunk in chunks {
+        ctx.say(chunk).await?;
+    }
+    Ok(())
+}
+
+/// Replies with `text` as a file named `name`
+pub async fn send_file(ctx: PoiseContext<'_>, text: String, name: &str) -> Result<(), Error> {
+    ctx.send(CreateReply::default().attachment(CreateAttachment::bytes(text.into_bytes(), name)))
+        .await?;
+    Ok(())
+}
+
+/// Deletes a placeholder reply, such as the one shown while the LLM is thinking. A failure is
+/// only logged, so it does not hide the result or error the placeholder was waiting for.
+pub async fn delete_placeholder(ctx: PoiseContext<'_>, placeholder: ReplyHandle<'_>) {
+    if let Err(err) = placeholder.delete(ctx).await {
+        tracing::warn!("Failed deleting a placeholder reply: {}", err);
+    }
+}
+
+fn needs_attachment(ctx: PoiseContext<'_>, text: &str) -> bool {
+    text.chars().count() > ctx.data().config.attachment_threshold
+}
+
+fn attachment_reply(ctx: PoiseContext<'_>, text: String) -> CreateReply {
+    CreateReply::default()
+        .content(translator(ctx).get("reply-attachment-note"))
+        .attachment(CreateAttachment::bytes(text.into_bytes(), ATTACHMENT_NAME))
+}
+
+/// Splits `text` into chunks of at most `limit` characters, preferring paragraph, line and
+/// sentence boundaries. A code fence that is open at the end of a chunk is closed there and
+/// reopened at the start of the next chunk.
+pub fn split_message(text: &str, limit: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = text.trim().to_owned();
+    while rest.chars().count() > limit {
+        // Leave room for closing a code fence
+        let budget = limit.saturating_sub(FENCE.len() + 1).max(1);
+        let window_end = rest
+            .char_indices()
+            .nth(budget)
+            .map_or(rest.len(), |(i, _)| i);
+        let window = &rest[..window_end];
+        let (end, next_start) = SEPARATORS
+            .iter()
+            .find_map(|separator| {
+                window
+                    .rfind(separator)
+                    .filter(|&i| i > 0)
+                    // Sentence ends keep their punctuation
+                    .map(|i| (i + separator.trim().len(), i + separator.len()))
+                    // A chunk ending in the opening of a fence would only be reopened as is
+                    .filter(|&(end, _)| !ends_in_fence_opening(&rest[..end]))
+            })
+            .unwrap_or((window_end, window_end));
+
+        let mut chunk = rest[..end].trim_end().to_owned();
+        let mut next = rest[next_start..].to_owned();
+        if let Some(opening) = open_fence(&chunk).map(str::to_owned) {
+            let reopened = format!("{}\n{}", opening, next);
+            // Reopening must not undo the progress, e.g. for an opening line above the limit
+            if reopened.len() < rest.len() {
+                chunk.push('\n');
+                chunk.push_str(FENCE);
+                next = reopened;
+            }
+        }
+        if !chunk.is_empty() {
+            chunks.push(chunk);
+        }
+        rest = next;
+    }
+    if !rest.trim().is_empty() {
+        chunks.push(rest);
+    }
+    chunks
+}
+
+/// Whether the last line of `text` opens a code fence
+fn ends_in_fence_opening(text: &str) -> bool {
+    open_fence(text).is_some()
+        && text
+            .trim_end()
+            .lines()
+            .last()
+            .is_some_and(|line| line.trim_start().starts_with(FENCE))
+}
+
+/// The opening line of the code fence that is still open at the end of `text`, if any
+fn open_fence(text: &str) -> Option<&str> {
+    let mut open = None;
+    for line in text.lines() {
+        let line = line.trim_start();
+        if line.starts_with(FENCE) {
+            open = match open {
+                None => Some(line.trim_end()),
+                Some(_) => None,
+            };
+        }
+    }
+    open
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_texts_are_not_split() {
+        assert_eq!(split_message("Hello there.", 20), vec!["Hello there."]);
+    }
+
+    #[test]
+    fn texts_are_split_at_paragraphs_then_sentences() {
+        let text = "First paragraph.\n\nSecond one. It has two sentences.";
+        assert_eq!(
+            split_message(text, 30),
+            vec!["First paragraph.", "Second one.", "It has two sentences."]
+        );
+    }
+
+    #[test]
+    fn long_lines_in_code_fences_are_split_at_the_limit() {
+        let text = format!("```\n{}\n```", "a".repeat(3000));
+        let chunks = split_message(&text, 2000);
+
+        assert_eq!(chunks.len(), 2);
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= 2000);
+            assert!(chunk.starts_with("```\na"));
+            assert!(chunk.ends_with("\n```"));
+        }
+    }
+
+    #[test]
+    fn code_fences_are_reopened_in_the_next_chunk() {
+        let code: Vec<String> = (0..40).map(|i| format!("let x{} = {};", i, i)).collect();
+        let text = format!("Some code:\n\n```rust\n{}\n```\nDone.", code.join("\n"));
+        let chunks = split_message(&text, 200);
+
+        assert!(chunks.len() > 2);
+        for chunk in &chunks {
+            assert!(chunk.chars().count() <= 200, "{:?} is too long", chunk);
+            assert!(
+                open_fence(chunk).is_none(),
+                "{:?} leaves a fence open",
+                chunk
+            );
+        }
+        assert!(chunks[2].starts_with("```rust\n"));
+        let joined = chunks.join("\n");
+        assert!(code.iter().all(|line| joined.contains(line.as_str())));
+    }
+}
@@ -0,0 +1,66 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// How often and how long to retry requests against flaky HTTP endpoints
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Upper bound of the delay before the first retry, doubled for every further retry
+    pub base_delay: Duration,
+    /// Upper bound of the delay between any two attempts
+    pub max_delay: Duration,
+    /// Deadline for the whole request, including all retries
+    pub timeout: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(5),
+            timeout: Duration::from_secs(20),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether another attempt may follow the given (1-based) attempt
+    pub fn should_retry(&self, attempt: u32) -> bool {
+        attempt < self.max_attempts
+    }
+
+    /// Delay before the retry following the given (1-based) attempt.
+    /// Uses exponential backoff with full jitter, so concurrent commands do not retry in lockstep.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let cap = exponential.min(self.max_delay);
+        rand::thread_rng().gen_range(Duration::ZERO..=cap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum_delay() {
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+            timeout: Duration::from_secs(1),
+        };
+        assert!(policy.should_retry(2));
+        assert!(!policy.should_retry(3));
+        for _ in 0..50 {
+            assert!(policy.backoff(1) <= Duration::from_millis(100));
+            assert!(policy.backoff(2) <= Duration::from_millis(200));
+            assert!(policy.backoff(10) <= Duration::from_millis(300));
+        }
+    }
+}
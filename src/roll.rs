@@ -0,0 +1,193 @@
+//! Free rolls of any dice outside of checks, written like `2d6+1` or `d4-d4`.
+
+use poise::CreateReply;
+use rand::Rng;
+
+use crate::dice_image::{self, Die};
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message};
+use crate::PoiseContext;
+
+/// Most dice rolled at once, so the reply and the picture stay readable
+const MAX_DICE: u32 = 20;
+const MAX_SIDES: u32 = 1000;
+
+#[derive(Debug, PartialEq)]
+enum Term {
+    Dice {
+        count: u32,
+        sides: u32,
+        negative: bool,
+    },
+    Constant(i64),
+}
+
+/// Parses terms like `2d6`, `d20` or `3` joined by `+` and `-`. `w` is accepted for dice as in
+/// German.
+fn parse_expression(expression: &str) -> Option<Vec<Term>> {
+    let expression: String = expression
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| if c == 'w' || c == 'W' { 'd' } else { c })
+        .collect::<String>()
+        .to_lowercase();
+
+    let mut terms = Vec::new();
+    let mut dice: u32 = 0;
+    let mut rest = expression.as_str();
+    while !rest.is_empty() {
+        let negative = rest.starts_with('-');
+        let unsigned = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+        let end = unsigned.find(['+', '-']).unwrap_or(unsigned.len());
+        let (term, after) = unsigned.split_at(end);
+        rest = after;
+
+        terms.push(match term.split_once('d') {
+            Some((count, sides)) => {
+                let count = if count.is_empty() {
+                    1
+                } else {
+                    count.parse().ok()?
+                };
+                let sides = sides.parse().ok()?;
+                if !(1..=MAX_DICE).contains(&count) || !(2..=MAX_SIDES).contains(&sides) {
+                    return None;
+                }
+                dice = dice.checked_add(count).filter(|dice| *dice <= MAX_DICE)?;
+                Term::Dice {
+                    count,
+                    sides,
+                    negative,
+                }
+            }
+            None => {
+                let value: i64 = term.parse().ok()?;
+                Term::Constant(if negative { -value } else { value })
+            }
+        });
+    }
+    (dice > 0).then_some(terms)
+}
+
+/// Rolls the dice of the terms. Returns the dice, the total and the rolls written out.
+fn roll_terms<R: Rng>(terms: &[Term], rng: &mut R) -> (Vec<Die>, i64, String) {
+    let mut dice = Vec::new();
+    let mut total = 0;
+    let mut text = String::new();
+    for term in terms {
+        let (negative, written) = match *term {
+            Term::Dice {
+                count,
+                sides,
+                negative,
+            } => {
+                let values: Vec<u32> = (0..count).map(|_| rng.gen_range(1..=sides)).collect();
+                let sum: i64 = values.iter().map(|value| *value as i64).sum();
+                total += if negative { -sum } else { sum };
+                dice.extend(values.iter().map(|value| Die {
+                    sides,
+                    value: *value,
+                    negative,
+                }));
+                let values: Vec<String> = values.iter().map(u32::to_string).collect();
+                (
+                    negative,
+                    format!("{}d{}({})", count, sides, values.join(", ")),
+                )
+            }
+            Term::Constant(value) => {
+                total += value;
+                (value < 0, value.abs().to_string())
+            }
+        };
+        match (text.is_empty(), negative) {
+            (true, true) => text.push('-'),
+            (true, false) => {}
+            (false, true) => text.push_str(" - "),
+            (false, false) => text.push_str(" + "),
+        }
+        text.push_str(&written);
+    }
+    (dice, total, text)
+}
+
+/// Roll any dice, e.g. 2d6+1
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn roll(
+    ctx: PoiseContext<'_>,
+    #[description = "Dice to roll, e.g. 2d6+1 or d4-d4"] dice: String,
+) -> Result<(), Error> {
+    let Some(terms) = parse_expression(&dice) else {
+        return Err(Error::InvalidArgument(
+            Message::new("roll-invalid")
+                .arg("dice", &dice)
+                .arg("max", MAX_DICE),
+        ));
+    };
+    let (rolled, total, rolls) = {
+        let mut rng: rand::rngs::StdRng = rand::SeedableRng::from_entropy();
+        roll_terms(&terms, &mut rng)
+    };
+
+    let tr = translator(ctx);
+    let text = tr!(
+        tr,
+        "roll-result",
+        dice = dice.as_str(),
+        rolls = rolls,
+        total = total
+    );
+    let mut reply = CreateReply::default().content(text);
+    // Pictures are left out for servers that prefer plain text results
+    let plain = ctx
+        .data()
+        .sheet_db()
+        .plain_checks(ctx.guild_id().unwrap().get())?;
+    if !plain {
+        if let Some(image) = dice_image::attachment(&rolled, &ctx.data().config.dice_theme) {
+            reply = reply.attachment(image);
+        }
+    }
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn expressions_are_rolled_term_by_term() {
+        assert_eq!(
+            parse_expression("2W6 - 1"),
+            Some(vec![
+                Term::Dice {
+                    count: 2,
+                    sides: 6,
+                    negative: false
+                },
+                Term::Constant(-1)
+            ])
+        );
+        assert_eq!(parse_expression("3"), None);
+        assert_eq!(parse_expression("21d6"), None);
+        assert_eq!(parse_expression("d1"), None);
+        assert_eq!(parse_expression("d4+"), None);
+        // Would wrap around to 1 die without checked adding
+        assert_eq!(parse_expression("d6+d6+4294967295d6"), None);
+        assert_eq!(parse_expression("10d6+11d6"), None);
+
+        let terms = parse_expression("d4-d4+2").unwrap();
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        let (dice, total, text) = roll_terms(&terms, &mut rng);
+        assert_eq!(dice.len(), 2);
+        assert!(dice[1].negative);
+        assert_eq!(total, dice[0].value as i64 - dice[1].value as i64 + 2);
+        assert_eq!(
+            text,
+            format!("1d4({}) - 1d4({}) + 2", dice[0].value, dice[1].value)
+        );
+    }
+}
@@ -0,0 +1,288 @@
+//! Question answering over the local rule documents.
+//!
+//! Markdown and text files in the rules directory are split into passages, one or more per
+//! section, and stored in a full-text index next to the other tables of the [`SheetDB`].
+
+use std::path::Path;
+
+use crate::completion::complete;
+use crate::db::{RulePassage, SheetDB};
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message};
+use crate::llm::{ChatMessage, CompletionRequest};
+use crate::persona;
+use crate::reply;
+use crate::PoiseContext;
+
+/// Passages longer than this are split at paragraph boundaries
+const MAX_PASSAGE_CHARS: usize = 1200;
+/// Quoted passages are cut off after this many characters
+const MAX_QUOTE_CHARS: usize = 400;
+
+/// Reads all `.md` and `.txt` files below `dir` and splits them into passages
+pub fn load_rules(dir: &Path) -> std::io::Result<Vec<RulePassage>> {
+    let mut passages = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        let mut entries: Vec<_> = std::fs::read_dir(&current)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<_>>()?;
+        entries.sort();
+        for path in entries {
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let is_document = path
+                .extension()
+                .is_some_and(|extension| extension == "md" || extension == "txt");
+            if is_document {
+                let source = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy();
+                let text = std::fs::read_to_string(&path)?;
+                passages.extend(split_document(&source, &text));
+            }
+        }
+    }
+    Ok(passages)
+}
+
+fn read_rules(dir: &Path) -> Result<Vec<RulePassage>, Error> {
+    load_rules(dir)
+        .map_err(|err| Error::Config(format!("Failed reading rules from {:?}: {}", dir, err)))
+}
+
+/// Re-indexes the rule documents, returning the number of indexed passages
+pub fn index_rules(sheet_db: &mut SheetDB, dir: &Path) -> Result<usize, Error> {
+    let passages = read_rules(dir)?;
+    sheet_db.replace_rule_passages(&passages)?;
+    Ok(passages.len())
+}
+
+/// Splits a document into passages at Markdown headings, and long sections at paragraphs
+fn split_document(source: &str, text: &str) -> Vec<RulePassage> {
+    let mut passages = Vec::new();
+    let mut heading = String::new();
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut paragraph = String::new();
+
+    let mut flush_section = |heading: &str, paragraphs: &mut Vec<String>| {
+        let mut content = String::new();
+        for paragraph in paragraphs.drain(..) {
+            if !content.is_empty() && content.len() + paragraph.len() > MAX_PASSAGE_CHARS {
+                passages.push(RulePassage {
+                    source: source.to_owned(),
+                    heading: heading.to_owned(),
+                    content: std::mem::take(&mut content),
+                });
+            }
+            if !content.is_empty() {
+                content.push_str("\n\n");
+            }
+            content.push_str(&paragraph);
+        }
+        if !content.is_empty() {
+            passages.push(RulePassage {
+                source: source.to_owned(),
+                heading: heading.to_owned(),
+                content,
+            });
+        }
+    };
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if (trimmed.is_empty() || trimmed.starts_with('#')) && !paragraph.is_empty() {
+            paragraphs.push(std::mem::take(&mut paragraph));
+        }
+        if trimmed.starts_with('#') {
+            flush_section(&heading, &mut paragraphs);
+            heading = trimmed.trim_start_matches('#').trim().to_owned();
+        } else if !trimmed.is_empty() {
+            if !paragraph.is_empty() {
+                paragraph.push('\n');
+            }
+            paragraph.push_str(trimmed);
+        }
+    }
+    if !paragraph.is_empty() {
+        paragraphs.push(paragraph);
+    }
+    flush_section(&heading, &mut paragraphs);
+    passages
+}
+
+/// Turns a free-form question into an FTS5 query matching any of its words
+fn fts_query(question: &str) -> Option<String> {
+    let words: Vec<String> = question
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 1)
+        .map(|word| format!("\"{}\"", word))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" OR "))
+}
+
+fn citation(passage: &RulePassage) -> String {
+    if passage.heading.is_empty() {
+        passage.source.clone()
+    } else {
+        format!("{}, {}", passage.source, passage.heading)
+    }
+}
+
+fn quote(passage: &RulePassage) -> String {
+    let mut content: String = passage.content.chars().take(MAX_QUOTE_CHARS).collect();
+    if content.len() < passage.content.len() {
+        content.push('…');
+    }
+    let quoted: Vec<String> = content.lines().map(|line| format!("> {}", line)).collect();
+    format!("{}\n— *{}*", quoted.join("\n"), citation(passage))
+}
+
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum RulesMode {
+    /// Quote the most relevant passages
+    #[name = "quote"]
+    Quote,
+    /// Let the LLM answer based on the most relevant passages
+    #[name = "answer"]
+    Answer,
+}
+
+/// Look up a question in the rule documents
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn ask_rules(
+    ctx: PoiseContext<'_>,
+    #[description = "Your question about the rules"] question: String,
+    #[description = "Quote the rules or let the LLM answer, defaults to quote"] mode: Option<
+        RulesMode,
+    >,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let query = fts_query(&question)
+        .ok_or_else(|| Error::InvalidArgument(Message::new("rules-question-without-words")))?;
+    let passages = data
+        .sheet_db()
+        .search_rules(&query, data.config.rules.passages)?;
+    let tr = translator(ctx);
+    if passages.is_empty() {
+        reply::say(ctx, tr.get("rules-nothing-found")).await?;
+        return Ok(());
+    }
+
+    match mode.unwrap_or(RulesMode::Quote) {
+        RulesMode::Quote => {
+            let quotes: Vec<String> = passages.iter().map(quote).collect();
+            reply::say(ctx, quotes.join("\n\n")).await?;
+        }
+        RulesMode::Answer => {
+            let guild_id = ctx.guild_id().unwrap();
+            let mut settings = persona::resolve(data, guild_id.into(), None)?;
+            settings.system_prompt.push_str(
+                "\n\nAnswer the question using only the following excerpts of the rules. \
+                 Cite the excerpts you use by their number, e.g. [1]. \
+                 If the excerpts do not answer the question, say so.",
+            );
+            for (i, passage) in passages.iter().enumerate() {
+                settings.system_prompt.push_str(&format!(
+                    "\n\n[{}] {}\n{}",
+                    i + 1,
+                    citation(passage),
+                    passage.content
+                ));
+            }
+
+            let placeholder = ctx.say(tr.get("thinking")).await?;
+            let request = CompletionRequest {
+                settings,
+                messages: vec![ChatMessage::user(question)],
+            };
+            let completion = match complete(ctx, request).await {
+                Ok(completion) => completion,
+                Err(err) => {
+                    reply::delete_placeholder(ctx, placeholder).await;
+                    return Err(err);
+                }
+            };
+
+            let sources: Vec<String> = passages
+                .iter()
+                .enumerate()
+                .map(|(i, passage)| format!("[{}] {}", i + 1, citation(passage)))
+                .collect();
+            let sources = tr!(tr, "rules-sources", sources = sources.join(" · "));
+            let answer = format!("{}\n\n-# {}", completion.text, sources);
+            reply::edit(ctx, placeholder, answer).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Re-read the rule documents into the rules index, which all servers share
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn reindex_rules(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    // Read without blocking the runtime, and only lock the DB for replacing the index
+    let dir = data.config.rules.dir.clone();
+    let passages = tokio::task::spawn_blocking(move || read_rules(&dir))
+        .await
+        .map_err(|err| Error::Config(format!("Failed reading rules: {}", err)))??;
+    data.sheet_db().replace_rule_passages(&passages)?;
+    let count = passages.len();
+    let tr = translator(ctx);
+    reply::say(ctx, tr!(tr, "rules-indexed", count = count)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_are_split_at_headings_and_long_sections() {
+        let long_paragraph = "x".repeat(MAX_PASSAGE_CHARS - 10);
+        let text = format!(
+            "Intro line\n\n# Combat\nRoll d4 - d4.\nAdd abilities.\n\n## Fate points\n{}\n\n{}\n",
+            long_paragraph, long_paragraph
+        );
+        let passages = split_document("rules.md", &text);
+
+        let summary: Vec<(&str, usize)> = passages
+            .iter()
+            .map(|p| (p.heading.as_str(), p.content.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("", 10),
+                ("Combat", 28),
+                ("Fate points", long_paragraph.len()),
+                ("Fate points", long_paragraph.len()),
+            ]
+        );
+        assert_eq!(passages[1].content, "Roll d4 - d4.\nAdd abilities.");
+    }
+
+    #[test]
+    fn search_ranks_matching_passages_first() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        let passage = |heading: &str, content: &str| RulePassage {
+            source: "rules.md".to_owned(),
+            heading: heading.to_owned(),
+            content: content.to_owned(),
+        };
+        sheet_db
+            .replace_rule_passages(&[
+                passage("Combat", "Each round every character acts once."),
+                passage(
+                    "Fate points",
+                    "A fate point cancels the negative die of a check.",
+                ),
+            ])
+            .unwrap();
+
+        let query = fts_query("How do fate points work?").unwrap();
+        let found = sheet_db.search_rules(&query, 3).unwrap();
+        assert_eq!(found[0].heading, "Fate points");
+        assert!(fts_query("?!").is_none());
+    }
+}
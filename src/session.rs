@@ -0,0 +1,295 @@
+//! Play sessions: a time window in a channel whose rolls, and optionally messages, are
+//! recorded so a recap can be written afterwards.
+
+use poise::serenity_prelude as serenity;
+
+use crate::completion::complete;
+use crate::db::{Session, SessionMessage, SessionRoll};
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message, Tr};
+use crate::llm::{ChatMessage, CompletionRequest};
+use crate::persona;
+use crate::reply;
+use crate::{Data, PoiseContext};
+
+/// Only the end of longer transcripts is given to the LLM
+const MAX_TRANSCRIPT_CHARS: usize = 12000;
+
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum RecapMode {
+    /// List the rolls of the session
+    #[name = "rolls"]
+    Rolls,
+    /// Let the LLM summarize the session
+    #[name = "summary"]
+    Summary,
+}
+
+/// Records a message in the running session of its channel, if that session captures messages
+pub fn record_message(data: &Data, message: &serenity::Message) -> Result<(), Error> {
+    let Some(guild_id) = message.guild_id else {
+        return Ok(());
+    };
+    if message.author.bot || message.content.is_empty() {
+        return Ok(());
+    }
+    let author = message
+        .author
+        .global_name
+        .as_deref()
+        .unwrap_or(&message.author.name);
+    data.sheet_db().record_session_message(
+        guild_id.get(),
+        message.channel_id.get(),
+        author,
+        &message.content,
+    )?;
+    Ok(())
+}
+
+/// `HH:MM` part of an SQLite timestamp
+fn time_of_day(timestamp: &str) -> &str {
+    timestamp.get(11..16).unwrap_or(timestamp)
+}
+
+fn session_window(tr: Tr, session: &Session) -> String {
+    let end = session
+        .ended_at
+        .clone()
+        .unwrap_or_else(|| tr.get("session-now"));
+    tr!(
+        tr,
+        "session-window",
+        start = session.started_at.as_str(),
+        end = end
+    )
+}
+
+/// Markdown heading of a recap
+fn recap_heading(tr: Tr, session: &Session) -> String {
+    format!(
+        "# {}\n*{}*\n\n",
+        tr.get("session-recap-title"),
+        session_window(tr, session)
+    )
+}
+
+/// Markdown list of the rolls of a session
+fn render_rolls(tr: Tr, session: &Session, rolls: &[SessionRoll]) -> String {
+    let mut recap = recap_heading(tr, session);
+    recap.push_str(&format!("## {}\n", tr.get("session-rolls-heading")));
+    if rolls.is_empty() {
+        recap.push_str(&tr.get("session-no-rolls"));
+        recap.push('\n');
+    }
+    for roll in rolls {
+        recap.push_str(&format!(
+            "- `{}` {}\n",
+            time_of_day(&roll.created_at),
+            roll.description
+        ));
+    }
+    recap
+}
+
+/// Plain transcript of the rolls and messages of a session for the LLM, oldest first
+fn transcript(rolls: &[SessionRoll], messages: &[SessionMessage]) -> String {
+    let mut entries: Vec<(&str, String)> = rolls
+        .iter()
+        .map(|roll| {
+            (
+                roll.created_at.as_str(),
+                format!("[roll] {}", roll.description),
+            )
+        })
+        .chain(messages.iter().map(|message| {
+            (
+                message.created_at.as_str(),
+                format!("{}: {}", message.author, message.content),
+            )
+        }))
+        .collect();
+    // Stable, so entries of the same second keep rolls before messages
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    let lines: Vec<String> = entries
+        .into_iter()
+        .map(|(created_at, entry)| format!("{} {}", time_of_day(created_at), entry))
+        .collect();
+    let transcript = lines.join("\n");
+
+    let chars = transcript.chars().count();
+    if chars <= MAX_TRANSCRIPT_CHARS {
+        return transcript;
+    }
+    transcript
+        .chars()
+        .skip(chars - MAX_TRANSCRIPT_CHARS)
+        .collect()
+}
+
+/// Record the rolls and messages of a play session for a recap
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    subcommands("session_start", "session_end", "session_recap"),
+    subcommand_required
+)]
+pub async fn session(_ctx: PoiseContext<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Start a session in this channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "start",
+    check = "crate::permissions::gm"
+)]
+async fn session_start(
+    ctx: PoiseContext<'_>,
+    #[description = "Also record the messages of this channel, defaults to no"]
+    capture_messages: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let channel_id = ctx.channel_id().get();
+    let capture_messages = capture_messages.unwrap_or(false);
+    {
+        let mut sheet_db = ctx.data().sheet_db();
+        if sheet_db.get_open_session(guild_id, channel_id)?.is_some() {
+            return Err(Error::InvalidArgument(Message::new("session-running")));
+        }
+        sheet_db.start_session(guild_id, channel_id, capture_messages)?;
+    }
+
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr.get(if capture_messages {
+            "session-started-messages"
+        } else {
+            "session-started"
+        }),
+    )
+    .await
+}
+
+/// End the session running in this channel
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    rename = "end",
+    check = "crate::permissions::gm"
+)]
+async fn session_end(ctx: PoiseContext<'_>) -> Result<(), Error> {
+    let guild_id = ctx.guild_id().unwrap().get();
+    let channel_id = ctx.channel_id().get();
+    let (session, rolls) = {
+        let mut sheet_db = ctx.data().sheet_db();
+        let session = sheet_db
+            .end_session(guild_id, channel_id)?
+            .ok_or_else(|| Error::InvalidArgument(Message::new("session-not-running")))?;
+        let rolls = sheet_db.get_session_rolls(session.id)?;
+        (session, rolls)
+    };
+
+    let tr = translator(ctx);
+    reply::say(
+        ctx,
+        tr!(
+            tr,
+            "session-ended",
+            window = session_window(tr, &session),
+            count = rolls.len()
+        ),
+    )
+    .await
+}
+
+/// Recap the latest session of this channel
+#[poise::command(prefix_command, slash_command, guild_only, rename = "recap")]
+async fn session_recap(
+    ctx: PoiseContext<'_>,
+    #[description = "List the rolls or let the LLM summarize, defaults to rolls"] mode: Option<
+        RecapMode,
+    >,
+    #[description = "Send the recap as a Markdown file"] export: Option<bool>,
+) -> Result<(), Error> {
+    let data = ctx.data();
+    let guild_id = ctx.guild_id().unwrap().get();
+    let channel_id = ctx.channel_id().get();
+    let (session, rolls, messages) = {
+        let mut sheet_db = data.sheet_db();
+        let session = sheet_db
+            .get_latest_session(guild_id, channel_id)?
+            .ok_or_else(|| Error::InvalidArgument(Message::new("session-none")))?;
+        let rolls = sheet_db.get_session_rolls(session.id)?;
+        let messages = sheet_db.get_session_messages(session.id)?;
+        (session, rolls, messages)
+    };
+
+    let tr = translator(ctx);
+    let recap = match mode.unwrap_or(RecapMode::Rolls) {
+        RecapMode::Rolls => render_rolls(tr, &session, &rolls),
+        RecapMode::Summary => {
+            if rolls.is_empty() && messages.is_empty() {
+                return Err(Error::InvalidArgument(Message::new("session-empty")));
+            }
+            let mut settings = persona::resolve(data, guild_id, None)?;
+            settings.system_prompt.push_str(
+                "\n\nWrite a recap of the following play session for the players, \
+                 as a few paragraphs of Markdown. Mention the important checks and their outcomes.",
+            );
+            let request = CompletionRequest {
+                settings,
+                messages: vec![ChatMessage::user(transcript(&rolls, &messages))],
+            };
+
+            let placeholder = ctx.say(tr.get("thinking")).await?;
+            let completion = complete(ctx, request).await;
+            reply::delete_placeholder(ctx, placeholder).await;
+            format!("{}{}\n", recap_heading(tr, &session), completion?.text)
+        }
+    };
+
+    if export.unwrap_or(false) {
+        reply::send_file(ctx, recap, &format!("session-{}.md", session.id)).await
+    } else {
+        reply::say(ctx, recap).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::SheetDB;
+    use crate::i18n::{Language, Translations};
+
+    #[test]
+    fn recap_lists_the_rolls_of_the_latest_session() {
+        let mut sheet_db = SheetDB::open_with_path(":memory:").unwrap();
+        assert!(!sheet_db
+            .record_session_roll(1, 2, "Gandalf", "before")
+            .unwrap());
+
+        sheet_db.start_session(1, 2, false).unwrap();
+        assert!(sheet_db
+            .record_session_roll(1, 2, "Gandalf", "**Gandalf** rolls **Zaubern**(2x5)")
+            .unwrap());
+        assert!(!sheet_db
+            .record_session_message(1, 2, "Frodo", "Hi")
+            .unwrap());
+        let session = sheet_db.end_session(1, 2).unwrap().unwrap();
+        assert!(session.ended_at.is_some());
+        assert!(sheet_db.end_session(1, 2).unwrap().is_none());
+
+        let rolls = sheet_db.get_session_rolls(session.id).unwrap();
+        let translations = Translations::load().unwrap();
+        let recap = render_rolls(Tr::new(&translations, Language::English), &session, &rolls);
+        assert!(recap.starts_with("# Session recap\n"));
+        assert!(recap.ends_with("**Gandalf** rolls **Zaubern**(2x5)\n"));
+        assert_eq!(recap.matches("\n- ").count(), 1);
+    }
+}
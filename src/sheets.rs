@@ -0,0 +1,155 @@
+extern crate google_sheets4 as sheets4;
+
+use sheets4::hyper::client::HttpConnector;
+use sheets4::hyper_rustls::HttpsConnector;
+use sheets4::oauth2::authenticator::Authenticator;
+use sheets4::{api, hyper, hyper_rustls, Sheets};
+
+use crate::error::Error;
+
+pub type SheetsAPI = Sheets<HttpsConnector<HttpConnector>>;
+
+/// Creates the Sheets API client, optionally talking to another endpoint than Google's
+pub fn build_sheets(
+    auth: Authenticator<HttpsConnector<HttpConnector>>,
+    base_url: Option<&str>,
+) -> SheetsAPI {
+    let mut sheets = Sheets::new(
+        hyper::Client::builder().build(
+            hyper_rustls::HttpsConnectorBuilder::new()
+                .with_native_roots()
+                .https_or_http()
+                .enable_http1()
+                .enable_http2()
+                .build(),
+        ),
+        auth,
+    );
+    if let Some(base_url) = base_url {
+        sheets.base_url(base_url.to_owned());
+        sheets.root_url(base_url.to_owned());
+    }
+    sheets
+}
+
+/// Fails with [`Error::InvalidCharacter`] if the spreadsheet has no sheet for the character
+pub async fn assert_character_name(
+    sheets_api: &SheetsAPI,
+    spreadsheet_id: &str,
+    character_name: &str,
+) -> Result<(), Error> {
+    let (_, spreadsheet) = sheets_api
+        .spreadsheets()
+        .get(spreadsheet_id)
+        .param("fields", "sheets.properties.title")
+        .doit()
+        .await?;
+
+    if match &spreadsheet.sheets {
+        Some(sheets) => !sheets
+            .iter()
+            .filter_map(|s| s.properties.as_ref())
+            .filter_map(|p| p.title.as_ref())
+            .any(|t| t == character_name),
+        None => true,
+    } {
+        Err(Error::InvalidCharacter(character_name.to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Quotes a sheet title for use in A1 notation, e.g. `'NPC Template'!A:G`
+pub fn a1_range(title: &str, range: &str) -> String {
+    format!("'{}'!{}", title.replace('\'', "''"), range)
+}
+
+/// Reads the formatted values of a range, one `Vec` per row
+pub async fn read_values(
+    sheets_api: &SheetsAPI,
+    spreadsheet_id: &str,
+    range: &str,
+) -> Result<Vec<Vec<String>>, Error> {
+    let (_, values) = sheets_api
+        .spreadsheets()
+        .values_get(spreadsheet_id, range)
+        .doit()
+        .await?;
+    Ok(values.values.unwrap_or_default())
+}
+
+/// Looks up the numeric ID of the sheet with the given title
+pub async fn sheet_id(
+    sheets_api: &SheetsAPI,
+    spreadsheet_id: &str,
+    title: &str,
+) -> Result<Option<i32>, Error> {
+    let (_, spreadsheet) = sheets_api
+        .spreadsheets()
+        .get(spreadsheet_id)
+        .param("fields", "sheets.properties(sheetId,title)")
+        .doit()
+        .await?;
+
+    Ok(spreadsheet
+        .sheets
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|s| s.properties)
+        .find(|p| p.title.as_deref() == Some(title))
+        .and_then(|p| p.sheet_id))
+}
+
+/// Copies the sheet with ID `source_sheet_id` to a new sheet titled `title`
+pub async fn duplicate_sheet(
+    sheets_api: &SheetsAPI,
+    spreadsheet_id: &str,
+    source_sheet_id: i32,
+    title: &str,
+) -> Result<(), Error> {
+    let request = api::BatchUpdateSpreadsheetRequest {
+        requests: Some(vec![api::Request {
+            duplicate_sheet: Some(api::DuplicateSheetRequest {
+                source_sheet_id: Some(source_sheet_id),
+                new_sheet_name: Some(title.to_owned()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }]),
+        ..Default::default()
+    };
+    sheets_api
+        .spreadsheets()
+        .batch_update(request, spreadsheet_id)
+        .doit()
+        .await?;
+    Ok(())
+}
+
+/// Writes single cells, given as A1 ranges and values, as if they were typed in by a user
+pub async fn write_cells(
+    sheets_api: &SheetsAPI,
+    spreadsheet_id: &str,
+    cells: Vec<(String, String)>,
+) -> Result<(), Error> {
+    let request = api::BatchUpdateValuesRequest {
+        data: Some(
+            cells
+                .into_iter()
+                .map(|(range, value)| api::ValueRange {
+                    range: Some(range),
+                    values: Some(vec![vec![value]]),
+                    ..Default::default()
+                })
+                .collect(),
+        ),
+        value_input_option: Some("USER_ENTERED".to_owned()),
+        ..Default::default()
+    };
+    sheets_api
+        .spreadsheets()
+        .values_batch_update(request, spreadsheet_id)
+        .doit()
+        .await?;
+    Ok(())
+}
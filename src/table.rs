@@ -0,0 +1,271 @@
+//! Random tables such as loot, encounters or weather, kept as tabs of the character spreadsheet.
+//!
+//! The first column of a table holds the roll and the second one the entry. A roll is either a
+//! weight, or a result or range of results of a die such as `1-3`, if any row has a range.
+//! Entries can refer to other tables as `[[Name]]`, which are rolled on in turn.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use rand::rngs::StdRng;
+use rand::Rng;
+
+use crate::error::Error;
+use crate::i18n::{tr, translator, Message};
+use crate::npc::parse_range;
+use crate::reply;
+use crate::sheets::{a1_range, read_values, sheet_id};
+use crate::{Data, PoiseContext};
+
+/// Nested tables are rolled on up to this depth, so tables referring to each other end
+const MAX_DEPTH: usize = 5;
+
+#[derive(Debug, PartialEq)]
+enum Table {
+    /// Entries with their weight
+    Weighted(Vec<(u32, String)>),
+    /// Entries with the results of a die with `sides` sides they are picked for
+    Dice {
+        sides: u32,
+        entries: Vec<((u32, u32), String)>,
+    },
+}
+
+/// Sum of the weights of a table, unless it overflows or is zero
+fn total_weight(entries: &[(u32, String)]) -> Option<u32> {
+    entries
+        .iter()
+        .try_fold(0u32, |total, (weight, _)| total.checked_add(*weight))
+        .filter(|total| *total > 0)
+}
+
+/// Reads a table from the rows of its first two columns.
+/// Rows without a roll or an entry, such as headings, are skipped.
+/// Fails with the ID of the message explaining why the table cannot be rolled on.
+fn parse_table(rows: &[Vec<String>]) -> Result<Table, &'static str> {
+    let rows: Vec<(&str, &str)> = rows
+        .iter()
+        .filter_map(|row| {
+            let entry = row.get(1)?.trim();
+            (!entry.is_empty()).then_some((row.first()?.trim(), entry))
+        })
+        .collect();
+
+    if rows.iter().any(|(roll, _)| roll.contains(['-', '–'])) {
+        let entries: Vec<((u32, u32), String)> = rows
+            .iter()
+            .filter_map(|(roll, entry)| Some((parse_range(roll)?, entry.to_string())))
+            .collect();
+        let sides = entries
+            .iter()
+            .map(|((_, max), _)| *max)
+            .max()
+            .ok_or("table-empty")?;
+        if sides == 0 {
+            return Err("table-invalid");
+        }
+        Ok(Table::Dice { sides, entries })
+    } else {
+        let entries: Vec<(u32, String)> = rows
+            .iter()
+            .filter_map(|(weight, entry)| Some((weight.parse().ok()?, entry.to_string())))
+            .filter(|(weight, _)| *weight > 0)
+            .collect();
+        if entries.is_empty() {
+            return Err("table-empty");
+        }
+        total_weight(&entries).ok_or("table-invalid")?;
+        Ok(Table::Weighted(entries))
+    }
+}
+
+impl Table {
+    /// Picks an entry, along with the die result for dice tables.
+    /// Dice tables can have gaps, where no entry is picked.
+    fn roll<R: Rng>(&self, rng: &mut R) -> (Option<u32>, Option<&str>) {
+        match self {
+            Table::Weighted(entries) => {
+                // Tables with invalid weights are rejected when they are read
+                let Some(total) = total_weight(entries) else {
+                    return (None, None);
+                };
+                let mut pick = rng.gen_range(0..total);
+                for (weight, entry) in entries {
+                    if pick < *weight {
+                        return (None, Some(entry));
+                    }
+                    pick -= weight;
+                }
+                unreachable!("the pick is below the total weight")
+            }
+            Table::Dice { sides, entries } => {
+                let result = rng.gen_range(1..=*sides);
+                let entry = entries
+                    .iter()
+                    .find(|((min, max), _)| (*min..=*max).contains(&result))
+                    .map(|(_, entry)| entry.as_str());
+                (Some(result), entry)
+            }
+        }
+    }
+}
+
+/// Names of the tables an entry refers to as `[[Name]]`, in order
+fn references(entry: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = entry;
+    while let Some((_, after)) = rest.split_once("[[") {
+        let Some((name, after)) = after.split_once("]]") else {
+            break;
+        };
+        names.push(name);
+        rest = after;
+    }
+    names
+}
+
+/// Reads a table by the title of its tab, or by an A1 range such as `Tables!A2:B20`
+async fn read_table(data: &Data, name: &str) -> Result<Table, Error> {
+    let spreadsheet_id = &data.config.character_spreadsheet_id;
+    let range = if name.contains('!') {
+        name.to_owned()
+    } else {
+        if sheet_id(&data.sheets, spreadsheet_id, name)
+            .await?
+            .is_none()
+        {
+            return Err(Error::InvalidArgument(
+                Message::new("table-unknown").arg("name", name),
+            ));
+        }
+        a1_range(name, "A:B")
+    };
+    let rows = read_values(&data.sheets, spreadsheet_id, &range).await?;
+    parse_table(&rows).map_err(|id| Error::InvalidArgument(Message::new(id).arg("name", name)))
+}
+
+/// The die result, for dice tables, and the entry rolled on a table
+type RollFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<(Option<u32>, String), Error>> + Send + 'a>>;
+
+/// Rolls on a table and on the tables its entry refers to, replacing the references with
+/// their entries. Tables are read once per call and kept in `tables`.
+fn roll_on<'a>(
+    data: &'a Data,
+    name: &'a str,
+    tables: &'a mut HashMap<String, Table>,
+    rng: &'a mut StdRng,
+    depth: usize,
+) -> RollFuture<'a> {
+    Box::pin(async move {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidArgument(
+                Message::new("table-too-deep").arg("name", name),
+            ));
+        }
+        if !tables.contains_key(name) {
+            let table = read_table(data, name).await?;
+            tables.insert(name.to_owned(), table);
+        }
+        let (result, entry) = tables[name].roll(rng);
+        let Some(entry) = entry.map(str::to_owned) else {
+            return Err(Error::InvalidArgument(
+                Message::new("table-no-entry")
+                    .arg("name", name)
+                    .arg("result", result.unwrap_or_default()),
+            ));
+        };
+
+        let mut text = entry.clone();
+        for reference in references(&entry) {
+            let (_, nested) = roll_on(data, reference, tables, rng, depth + 1).await?;
+            text = text.replacen(&format!("[[{}]]", reference), &nested, 1);
+        }
+        Ok((result, text))
+    })
+}
+
+/// Roll on a random table from the spreadsheet
+#[poise::command(prefix_command, slash_command, guild_only)]
+pub async fn table(
+    ctx: PoiseContext<'_>,
+    #[description = "Tab of the table, or a range such as Tables!A2:B20"] name: String,
+) -> Result<(), Error> {
+    let mut rng: StdRng = rand::SeedableRng::from_entropy();
+    let (result, entry) = roll_on(ctx.data(), &name, &mut HashMap::new(), &mut rng, 0).await?;
+
+    let tr = translator(ctx);
+    let text = match result {
+        Some(result) => tr!(
+            tr,
+            "table-result-dice",
+            name = name.as_str(),
+            result = result,
+            entry = entry
+        ),
+        None => tr!(tr, "table-result", name = name.as_str(), entry = entry),
+    };
+    reply::say(ctx, text).await
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::test_support::MockGoogle;
+
+    #[tokio::test]
+    async fn nested_tables_are_rolled_on() {
+        let mock = MockGoogle::start().await;
+        let data = mock.data().await;
+        let mut tables = HashMap::new();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        let mut seen = Vec::new();
+        for _ in 0..50 {
+            let (result, entry) = roll_on(&data, "Loot", &mut tables, &mut rng, 0)
+                .await
+                .unwrap();
+            assert!((1..=6).contains(&result.unwrap()));
+            assert!(!entry.contains("[["));
+            seen.push(entry);
+        }
+        assert!(seen.contains(&"A pouch of rubies".to_owned()));
+        assert!(seen.contains(&"A healing potion".to_owned()));
+        assert_eq!(tables.len(), 2);
+        assert_eq!(
+            tables["Gems"],
+            Table::Weighted(vec![(3, "amethysts".to_owned()), (1, "rubies".to_owned())])
+        );
+
+        assert!(matches!(
+            roll_on(&data, "Treasure", &mut tables, &mut rng, 0).await,
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(references("[[a]] and [[b]] [[c"), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn tables_with_invalid_rolls_are_rejected() {
+        let rows = |rows: &[(&str, &str)]| -> Vec<Vec<String>> {
+            rows.iter()
+                .map(|(roll, entry)| vec![roll.to_string(), entry.to_string()])
+                .collect()
+        };
+        assert_eq!(
+            parse_table(&rows(&[("4294967295", "a"), ("1", "b")])),
+            Err("table-invalid")
+        );
+        assert_eq!(parse_table(&rows(&[("0-0", "a")])), Err("table-invalid"));
+        assert_eq!(parse_table(&rows(&[("0", "a")])), Err("table-empty"));
+        assert_eq!(
+            parse_table(&rows(&[("1-2", "a"), ("3", "b")])),
+            Ok(Table::Dice {
+                sides: 3,
+                entries: vec![((1, 2), "a".to_owned()), ((3, 3), "b".to_owned())]
+            })
+        );
+    }
+}
@@ -0,0 +1,413 @@
+//! Local stand-in for the Google APIs the bot talks to, so commands can be tested without
+//! credentials or network access.
+//!
+//! The mock serves the OAuth token endpoint, `spreadsheets.get` title listings, `values.get`
+//! and gviz CSV queries, as well as OpenAI style chat completions under `llm/`. Every
+//! `<title>.csv` file in the fixture directory is one sheet of the spreadsheet, holding
+//! ability names and values, or the rolls and entries of a random table, as its first and
+//! second column.
+
+extern crate google_sheets4 as sheets4;
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use sheets4::hyper;
+use sheets4::hyper::service::{make_service_fn, service_fn};
+use sheets4::hyper::{Body, Method, Request, Response, Server, StatusCode};
+use sheets4::oauth2;
+use tokio::sync::oneshot;
+
+use crate::config::{Config, DiceTheme, GvizConfig, LlmConfig, QuotaConfig, RulesConfig};
+use crate::db::SheetDB;
+use crate::i18n::Translations;
+use crate::llm::{BackendKind, OpenAiBackend};
+use crate::retry::RetryPolicy;
+use crate::sheets::build_sheets;
+use crate::Data;
+
+pub const SPREADSHEET_ID: &str = "test-spreadsheet";
+
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+struct MockState {
+    spreadsheet_dir: PathBuf,
+    /// Number of upcoming gviz requests that fail with `gviz_failure`
+    failing_gviz_requests: AtomicUsize,
+    /// Status and `Retry-After` seconds of failing gviz requests
+    gviz_failure: Mutex<(StatusCode, Option<u64>)>,
+    gviz_requests: AtomicUsize,
+}
+
+pub struct MockGoogle {
+    /// Base URL of the mock, with trailing slash
+    pub base_url: String,
+    state: Arc<MockState>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockGoogle {
+    /// Starts the mock on a free local port, serving the default fixture spreadsheet
+    pub async fn start() -> MockGoogle {
+        MockGoogle::start_with_spreadsheet(fixtures_dir().join("spreadsheet")).await
+    }
+
+    pub async fn start_with_spreadsheet(spreadsheet_dir: PathBuf) -> MockGoogle {
+        let state = Arc::new(MockState {
+            spreadsheet_dir,
+            failing_gviz_requests: AtomicUsize::new(0),
+            gviz_failure: Mutex::new((StatusCode::SERVICE_UNAVAILABLE, None)),
+            gviz_requests: AtomicUsize::new(0),
+        });
+
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(handle(&state, req).await) }
+                }))
+            }
+        });
+
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let base_url = format!("http://{}/", server.local_addr());
+        tokio::spawn(server.with_graceful_shutdown(async {
+            shutdown_rx.await.ok();
+        }));
+
+        MockGoogle {
+            base_url,
+            state,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    /// Makes the next `n` gviz requests fail with `503 Service Unavailable`
+    pub fn fail_next_gviz_requests(&self, n: usize) {
+        self.fail_next_gviz_requests_with(n, StatusCode::SERVICE_UNAVAILABLE, None);
+    }
+
+    /// Makes the next `n` gviz requests fail with the given status, asking clients to retry
+    /// after `retry_after` seconds if given
+    pub fn fail_next_gviz_requests_with(
+        &self,
+        n: usize,
+        status: StatusCode,
+        retry_after: Option<u64>,
+    ) {
+        *self.state.gviz_failure.lock().unwrap() = (status, retry_after);
+        self.state.failing_gviz_requests.store(n, Ordering::SeqCst);
+    }
+
+    /// Number of gviz requests received so far, including failed ones
+    pub fn gviz_requests(&self) -> usize {
+        self.state.gviz_requests.load(Ordering::SeqCst)
+    }
+
+    pub fn config(&self) -> Config {
+        Config {
+            google_application_credentials: String::new(),
+            character_spreadsheet_id: SPREADSHEET_ID.to_owned(),
+            discord_bot_token: String::new(),
+            sheets_base_url: Some(self.base_url.clone()),
+            gviz: GvizConfig {
+                base_url: self.base_url.clone(),
+                retry: RetryPolicy {
+                    max_attempts: 3,
+                    base_delay: Duration::from_millis(1),
+                    max_delay: Duration::from_millis(10),
+                    timeout: Duration::from_secs(5),
+                },
+            },
+            llm: LlmConfig {
+                backend: BackendKind::OpenAi,
+                base_url: format!("{}llm/", self.base_url),
+                api_key: None,
+                timeout: Duration::from_secs(5),
+                history_turns: 10,
+                history_tokens: 2000,
+                python_site_packages: String::new(),
+                model: "test-model".to_owned(),
+                temperature: 0.0,
+                system_prompt: "You are a test".to_owned(),
+            },
+            rules: RulesConfig {
+                dir: PathBuf::new(),
+                passages: 3,
+            },
+            narration_cooldown: Duration::ZERO,
+            attachment_threshold: 8000,
+            npc_template_sheet: "NPC Template".to_owned(),
+            initiative_ability: "Strategie".to_owned(),
+            hp_ability: "Lebenspunkte".to_owned(),
+            dice_theme: DiceTheme {
+                positive: 0x2e_cc_71,
+                negative: 0xe7_4c_3c,
+            },
+            quota: QuotaConfig {
+                user_requests: 0,
+                user_tokens: 0,
+                guild_requests: 0,
+                guild_tokens: 0,
+                blocklist: Vec::new(),
+            },
+        }
+    }
+
+    /// Bot state wired to this mock and to an in-memory DB
+    pub async fn data(&self) -> Data {
+        self.data_with_config(self.config()).await
+    }
+
+    pub async fn data_with_config(&self, config: Config) -> Data {
+        let key = oauth2::ServiceAccountKey {
+            key_type: Some("service_account".to_owned()),
+            project_id: None,
+            private_key_id: None,
+            private_key: std::fs::read_to_string(
+                fixtures_dir().join("test_service_account_key.pem"),
+            )
+            .unwrap(),
+            client_email: "bot@test.invalid".to_owned(),
+            client_id: None,
+            auth_uri: None,
+            token_uri: format!("{}token", self.base_url),
+            auth_provider_x509_cert_url: None,
+            client_x509_cert_url: None,
+        };
+        let auth = oauth2::ServiceAccountAuthenticator::builder(key)
+            .build()
+            .await
+            .unwrap();
+        let sheets = build_sheets(auth, config.sheets_base_url.as_deref());
+        let llm = Box::new(OpenAiBackend::new(
+            &config.llm.base_url,
+            config.llm.api_key.clone(),
+        ));
+        Data::new(
+            config,
+            sheets,
+            SheetDB::open_with_path(":memory:").unwrap(),
+            llm,
+            Translations::load().unwrap(),
+        )
+    }
+}
+
+impl Drop for MockGoogle {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+async fn handle(state: &MockState, req: Request<Body>) -> Response<Body> {
+    let path = req.uri().path().to_owned();
+    let query = parse_query(req.uri().query().unwrap_or_default());
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+
+    match (req.method(), segments.as_slice()) {
+        (&Method::POST, ["token"]) => json_response(
+            r#"{"access_token":"test-token","token_type":"Bearer","expires_in":3600}"#.to_owned(),
+        ),
+        (&Method::GET, ["v4", "spreadsheets", SPREADSHEET_ID]) => {
+            let sheets: Vec<String> = sheet_titles(&state.spreadsheet_dir)
+                .iter()
+                .enumerate()
+                .map(|(id, title)| {
+                    format!(
+                        r#"{{"properties":{{"sheetId":{},"title":"{}"}}}}"#,
+                        id, title
+                    )
+                })
+                .collect();
+            json_response(format!(r#"{{"sheets":[{}]}}"#, sheets.join(",")))
+        }
+        (&Method::GET, ["v4", "spreadsheets", SPREADSHEET_ID, "values", range]) => {
+            values_response(state, &decode(range))
+        }
+        (&Method::GET, ["spreadsheets", "d", SPREADSHEET_ID, "gviz", "tq"]) => {
+            state.gviz_requests.fetch_add(1, Ordering::SeqCst);
+            let fail = state
+                .failing_gviz_requests
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok();
+            if fail {
+                let (status, retry_after) = *state.gviz_failure.lock().unwrap();
+                let mut response = status_response(status);
+                if let Some(seconds) = retry_after {
+                    response
+                        .headers_mut()
+                        .insert(hyper::header::RETRY_AFTER, seconds.into());
+                }
+                return response;
+            }
+            gviz_response(state, &query)
+        }
+        (&Method::POST, ["llm", "chat", "completions"]) => {
+            let body = hyper::body::to_bytes(req.into_body()).await.unwrap();
+            chat_completion_response(&body)
+        }
+        _ => status_response(StatusCode::NOT_FOUND),
+    }
+}
+
+/// Answers OpenAI style chat completions by echoing the model and the last message
+fn chat_completion_response(body: &[u8]) -> Response<Body> {
+    let Ok(request) = serde_json::from_slice::<serde_json::Value>(body) else {
+        return status_response(StatusCode::BAD_REQUEST);
+    };
+    let messages = request["messages"].as_array().cloned().unwrap_or_default();
+    let last = messages
+        .last()
+        .and_then(|message| message["content"].as_str())
+        .unwrap_or_default();
+    json_response(
+        serde_json::json!({
+            "choices": [{"message": {
+                "role": "assistant",
+                "content": format!("{} ({} messages): {}", request["model"].as_str().unwrap_or_default(), messages.len(), last),
+            }}],
+            "usage": {"total_tokens": 42},
+        })
+        .to_string(),
+    )
+}
+
+/// Answers the `select A, G where lower(A) starts with '<prefix>' limit <n>` queries of
+/// [`get_ability_value`](crate::get_ability_value::get_ability_value)
+fn gviz_response(state: &MockState, query: &HashMap<String, String>) -> Response<Body> {
+    let (Some(sheet), Some(tq)) = (query.get("sheet"), query.get("tq")) else {
+        return status_response(StatusCode::BAD_REQUEST);
+    };
+    let Ok(csv) = std::fs::read_to_string(state.spreadsheet_dir.join(format!("{}.csv", sheet)))
+    else {
+        return status_response(StatusCode::BAD_REQUEST);
+    };
+    // The literal is in single quotes, or in double quotes if it contains single quotes
+    let literal = tq.split("starts with ").nth(1).unwrap_or_default();
+    let prefix = literal
+        .chars()
+        .next()
+        .and_then(|quote| literal[quote.len_utf8()..].split(quote).next())
+        .unwrap_or_default()
+        .to_lowercase();
+    let limit = tq
+        .rsplit("limit ")
+        .next()
+        .and_then(|limit| limit.trim().parse().ok())
+        .unwrap_or(usize::MAX);
+
+    let rows: Vec<String> = csv
+        .lines()
+        .filter_map(|line| line.split_once(','))
+        .filter(|(name, _)| name.to_lowercase().starts_with(&prefix))
+        .take(limit)
+        .map(|(name, value)| format!("\"{}\",\"{}\"", name, value))
+        .collect();
+    Response::builder()
+        .header("Content-Type", "text/csv")
+        .body(Body::from(rows.join("\n")))
+        .unwrap()
+}
+
+/// Answers `values.get` requests for whole columns of a sheet, e.g. `A:G`, with the first
+/// column of the fixture in the first column and its second one in the last column
+fn values_response(state: &MockState, range: &str) -> Response<Body> {
+    let Some((title, columns)) = range.rsplit_once('!') else {
+        return status_response(StatusCode::BAD_REQUEST);
+    };
+    let width = match columns.as_bytes() {
+        [first, b':', last] if first < last => (last - first + 1) as usize,
+        _ => return status_response(StatusCode::BAD_REQUEST),
+    };
+    let title = title.trim_matches('\'').replace("''", "'");
+    let Ok(csv) = std::fs::read_to_string(state.spreadsheet_dir.join(format!("{}.csv", title)))
+    else {
+        return status_response(StatusCode::BAD_REQUEST);
+    };
+    let rows: Vec<Vec<&str>> = csv
+        .lines()
+        .filter_map(|line| line.split_once(','))
+        .map(|(name, value)| {
+            let mut row = vec![""; width];
+            row[0] = name;
+            row[width - 1] = value;
+            row
+        })
+        .collect();
+    json_response(
+        serde_json::json!({"range": range, "majorDimension": "ROWS", "values": rows}).to_string(),
+    )
+}
+
+fn sheet_titles(dir: &Path) -> Vec<String> {
+    let mut titles: Vec<String> = std::fs::read_dir(dir)
+        .unwrap()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            (path.extension()? == "csv").then(|| path.file_stem()?.to_str().map(str::to_owned))?
+        })
+        .collect();
+    titles.sort();
+    titles
+}
+
+/// Splits a query string into its decoded key value pairs
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (decode(key), decode(value)))
+        .collect()
+}
+
+fn decode(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex: Vec<u8> = iter.by_ref().take(2).collect();
+                match std::str::from_utf8(&hex)
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(decoded) => bytes.push(decoded),
+                    None => {
+                        bytes.push(b'%');
+                        bytes.extend(hex);
+                    }
+                }
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn json_response(body: String) -> Response<Body> {
+    Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::empty())
+        .unwrap()
+}
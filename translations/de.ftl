@@ -0,0 +1,489 @@
+# Antworten des Bots auf Deutsch
+
+thinking = denke nach…
+reply-attachment-note = Die Antwort ist zu lang für Discord, deshalb kommt sie als Datei.
+
+## Charaktere und Proben
+
+claim-missing-name = Bitte gib als erstes Argument einen Charakternamen an
+claim-done = Bogen { $character } beansprucht
+claim-failed = Bogen { $character } konnte nicht beansprucht werden
+claim-taken = { $character } wurde schon von jemand anderem beansprucht.
+my-character = Dein beanspruchter Charakter ist { $character }
+check-result = **{ $character }** würfelt { $abilities } + W4({ $pos }) - W4({ $neg }){ $modifier } = **{ $total }**
+check-result-dropped = **{ $character }** würfelt { $abilities } + W4({ $pos }){ $modifier } = **{ $total }**
+check-fate-dropped = (negativer Würfel mit einem Schicksalspunkt gestrichen)
+check-fate-rerolled = (negativer Würfel mit einem Schicksalspunkt neu gewürfelt)
+check-success = gegen { $target }: **Erfolg** um { $margin }
+check-failure = gegen { $target }: **Misserfolg** um { $margin }
+check-embed-title = { $character } würfelt
+check-embed-dice = W4
+check-embed-total = Summe
+check-embed-modifier = Modifikator
+check-button-reroll = Negativen Würfel neu würfeln (Schicksalspunkt)
+check-buttons-not-yours = Nur wer gewürfelt hat und die Spielleitung dürfen bei dieser Probe neu würfeln.
+check-buttons-gm-only = Nur die Spielleitung darf den Modifikator einer Probe ändern.
+check-already-rerolled = Der negative Würfel dieser Probe wurde schon neu gewürfelt.
+check-change-reroll = negativer Würfel von { $old } auf { $new } neu gewürfelt
+check-change-modifier = Modifikator { $modifier }
+check-embed-target = Ziel
+check-embed-success = **Erfolg** um { $margin }
+check-embed-failure = **Misserfolg** um { $margin }
+plain-checks-enabled = Proben werden als reiner Text gezeigt
+plain-checks-disabled = Proben werden als Embeds gezeigt
+
+## Fehler
+
+error-no-claimed-character = Du hast noch keinen Charakter beansprucht!
+error-invalid-character = Zum Charakternamen '{ $name }' gibt es keinen Bogen in der Tabelle!
+error-unknown-persona = Es gibt keine Persona namens '{ $name }'.
+error-not-allowed = Du hast keine Rolle, die diesen Befehl benutzen darf.
+error-quota-user-requests = Das tägliche Anfragekontingent deines Kontos ist aufgebraucht. Bitte versuch es morgen wieder.
+error-quota-user-tokens = Das tägliche Tokenkontingent deines Kontos ist aufgebraucht. Bitte versuch es morgen wieder.
+error-quota-guild-requests = Das tägliche Anfragekontingent dieses Servers ist aufgebraucht. Bitte versucht es morgen wieder.
+error-quota-guild-tokens = Das tägliche Tokenkontingent dieses Servers ist aufgebraucht. Bitte versucht es morgen wieder.
+error-content-blocked = Die Anfrage oder ihre Antwort enthält gesperrte Inhalte.
+error-missing-level-gm = Das darf nur die Spielleitung.
+error-missing-level-admin = Das dürfen nur Admins.
+error-not-your-character = Du darfst nur für deinen beanspruchten Charakter würfeln, nicht für { $name }.
+error-no-ability = Auf dem Charakterbogen gibt es keine Fähigkeit '{ $ability }'.
+error-ambiguous-ability = '{ $ability }' ist mehrdeutig und passt auf { $found }. Bitte sei genauer.
+error-sheet-value = Der Charakterbogen enthält einen Wert, der nicht gelesen werden konnte.
+error-sheets-unreachable = Die Charaktertabelle ist gerade nicht erreichbar. Bitte versuch es später wieder.
+error-db = Die Datenbank des Bots ist gerade nicht verfügbar.
+error-llm = Die Vervollständigung ist fehlgeschlagen.
+error-llm-timeout = Die Vervollständigung hat zu lange gedauert und wurde abgebrochen. Bitte versuch es noch einmal.
+error-internal = Beim Bot ist etwas schiefgegangen.
+
+## Sprache
+
+language-user-set = Ich antworte dir auf Deutsch.
+language-server-set = Ich antworte auf diesem Server auf Deutsch.
+
+## Schicksalspunkte
+
+fate-points = { $character } hat { $points ->
+        [one] { $points } Schicksalspunkt
+       *[other] { $points } Schicksalspunkte
+    }
+fate-none-yet = Noch hat kein Charakter Schicksalspunkte.
+fate-none-left = { $character } hat keine Schicksalspunkte mehr.
+fate-invalid-grant = Bitte vergib mindestens einen Schicksalspunkt.
+fate-invalid-reset = Schicksalspunkte können nicht negativ sein.
+fate-reset-all = Jeder Charakter hat jetzt { $points ->
+        [one] { $points } Schicksalspunkt
+       *[other] { $points } Schicksalspunkte
+    }
+fate-reason-grant = vergeben
+fate-reason-spend = ausgegeben
+fate-reason-check = für eine Probe ausgegeben
+fate-reason-reset = zurückgesetzt
+
+## Initiative
+
+init-round = Runde { $round }
+init-empty = Noch ist niemand dem Kampf beigetreten.
+init-not-running = In diesem Kanal läuft kein Kampf.
+init-running = In diesem Kanal läuft schon ein Kampf.
+init-unknown = { $name } ist nicht am Kampf beteiligt.
+init-cannot-delay = { $name } kann nicht abwarten, in dieser Runde handelt niemand mehr danach.
+init-changed = Die Reihenfolge des Kampfes hat sich inzwischen geändert, bitte versuch es noch einmal.
+init-skipped = Für { $names } konnte keine Initiative gewürfelt werden
+init-ended = Der Kampf ist vorbei.
+
+## Lebenspunkte und Zustände
+
+hp-status = { $character }: { $current }/{ $max } LP
+hp-down = ({ $character } ist kampfunfähig!)
+condition-rounds = { $condition } ({ $rounds ->
+        [one] { $rounds } Runde
+       *[other] { $rounds } Runden
+    })
+condition-added = { $character } ist jetzt { $condition }.
+condition-removed = { $character } ist nicht mehr { $condition }.
+condition-unknown = { $character } ist nicht { $condition }.
+condition-invalid-rounds = Ein Zustand muss mindestens eine Runde dauern.
+condition-expired = Abgelaufen: { $conditions }
+status-title = Zustand der Gruppe
+status-empty = Noch hat niemand einen Charakter beansprucht oder Lebenspunkte.
+status-hp = ❤️ { $current }/{ $max }
+status-hp-unknown = ❤️ ?
+
+## Erfahrung
+
+marks-no-session = Fähigkeiten können nur in einer laufenden Sitzung markiert werden.
+marks-none = { $character } hat keine markierten Fähigkeiten.
+marks-heading = Markierte Fähigkeiten von **{ $character }**:
+marks-entry = { $ability } ({ $sessions ->
+        [one] { $sessions } Sitzung
+       *[other] { $sessions } Sitzungen
+    }, { $checks ->
+        [one] { $checks } Probe
+       *[other] { $checks } Proben
+    })
+marks-ambiguous = Mehrere markierte Fähigkeiten passen zu { $ability }: { $names }
+marks-cleared = Markierungen von { $character } gelöscht: { $abilities }
+marks-improved = { $ability }: { $old } → { $new }
+
+## Zufallstabellen
+
+table-result = 🎲 **{ $name }**: { $entry }
+table-result-dice = 🎲 **{ $name }** ({ $result }): { $entry }
+table-unknown = In der Tabelle gibt es kein Blatt { $name } mit einer Zufallstabelle.
+table-empty = Die Zufallstabelle { $name } hat keine Einträge mit Gewicht oder Würfelergebnis.
+table-invalid = Die Gewichte oder Würfelergebnisse der Zufallstabelle { $name } sind ungültig.
+table-no-entry = Die Zufallstabelle { $name } hat keinen Eintrag für { $result }.
+table-too-deep = Die Zufallstabellen verweisen zu tief aufeinander, abgebrochen bei { $name }.
+
+## Freie Würfe
+
+roll-result = 🎲 **{ $dice }**: { $rolls } = **{ $total }**
+roll-invalid = { $dice } kann nicht gewürfelt werden. Schreibe Würfel wie 2w6+1 oder w4-w4, mit höchstens { $max } Würfeln.
+
+## Vervollständigungen und Kontingente
+
+forget-done = { $count ->
+        [one] { $count } Nachricht
+       *[other] { $count } Nachrichten
+    } dieses Kanals vergessen
+usage-unlimited = unbegrenzt
+usage-line = { $requests } von { $request_limit } Anfragen, { $tokens } von { $token_limit } Tokens
+usage-summary =
+    Heute verbrauchte Vervollständigungen
+    Du: { $user }
+    Dieser Server: { $server }
+completion-role-allowed = { $role } darf Vervollständigungen benutzen
+completion-role-disallowed = { $role } darf keine Vervollständigungen mehr benutzen
+completion-role-not-allowed = { $role } durfte keine Vervollständigungen benutzen.
+completion-roles-everyone = Alle dürfen Vervollständigungen benutzen
+completion-roles-list = Rollen, die Vervollständigungen benutzen dürfen: { $roles }
+
+## Personas und Erzählung
+
+persona-invalid-temperature = Die Temperatur muss zwischen 0 und 2 liegen.
+persona-stored = Persona { $name } gespeichert
+persona-none = Es gibt noch keine Personas.
+persona-default-marker = { $name } (Standard)
+persona-settings = **{ $name }** (Modell { $model }, Temperatur { $temperature })
+persona-removed = Persona { $name } entfernt
+persona-default-set = Standardpersona ist jetzt { $name }
+persona-default-reset = Die eingebaute Standardpersona wird benutzt
+narration-enabled = Proben werden erzählt
+narration-disabled = Proben werden nicht erzählt
+
+## Berechtigungen
+
+level-player = Spieler
+level-gm = Spielleitung
+level-admin = Admin
+gm-role-set = Mitglieder von { $role } sind jetzt Spielleitung
+gm-role-removed = Es gibt keine Spielleitungsrolle mehr
+open-rolls-enabled = Spieler dürfen für jeden Charakter würfeln
+open-rolls-disabled = Spieler dürfen nur für ihre beanspruchten Charaktere würfeln
+permissions-no-gm-role = keine
+permissions-rolls-any = jeden Charakter
+permissions-rolls-claimed = nur beanspruchte Charaktere
+permissions-show =
+    Spielleitungsrolle: { $gm_role }
+    Spieler würfeln für: { $rolls }
+    Deine Stufe: { $level }
+
+## Regeln
+
+rules-question-without-words = Bitte stell eine Frage, die Wörter enthält.
+rules-nothing-found = Dazu habe ich in den Regeln nichts gefunden.
+rules-sources = Quellen: { $sources }
+rules-indexed = { $count } Regelabschnitte indiziert
+
+## NSCs
+
+npc-empty-template = Der Bogen '{ $sheet }' hat in Spalte G keine Fähigkeiten mit Wertebereichen.
+npc-unnamed = Namenloser NSC
+npc-needs-name = Bitte gib dem NSC einen Namen oder lass ihn beschreiben, um ihn zu speichern.
+npc-sheet-exists = Es gibt schon einen Bogen namens '{ $name }'.
+npc-saved = Als Bogen { $name } gespeichert, würfle für ihn mit `/probe_charakter { $name }`
+
+## Sitzungen
+
+session-now = jetzt
+session-window = { $start } – { $end } UTC
+session-recap-title = Sitzungsrückblick
+session-rolls-heading = Würfe
+session-no-rolls = Es wurde nicht gewürfelt.
+session-running = In diesem Kanal läuft schon eine Sitzung.
+session-not-running = In diesem Kanal läuft keine Sitzung.
+session-none = In diesem Kanal gab es noch keine Sitzung.
+session-empty = In dieser Sitzung wurde nichts aufgezeichnet.
+session-started = Sitzung gestartet, die Würfe dieses Kanals werden aufgezeichnet
+session-started-messages = Sitzung gestartet, die Würfe und Nachrichten dieses Kanals werden aufgezeichnet
+session-ended = Sitzung beendet ({ $window }) mit { $count ->
+        [one] { $count } Wurf
+       *[other] { $count } Würfen
+    }. Mit `/sitzung rückblick` gibt es einen Rückblick.
+
+## Befehle, nach dem Namen ihrer Funktion. Attribute übersetzen die Beschreibung,
+## die Namen der Parameter und deren Beschreibungen.
+
+language_setting = sprache
+    .description = Wähle die Sprache, in der der Bot antwortet
+language_user = benutzer
+    .description = Wähle die Sprache, in der der Bot dir antwortet
+    .language = sprache
+    .language-description = Deine Sprache, leer lassen für die des Servers
+language_server = server
+    .description = Wähle die Sprache, in der der Bot auf diesem Server antwortet
+    .language = sprache
+    .language-description = Sprache des Servers, leer lassen für die Discord-Sprache jedes Mitglieds
+claim = beanspruchen
+    .description = Beanspruche einen Charakterbogen
+    .character_name = charakter
+    .character_name-description = Charakter, den du beanspruchen willst
+my_character = mein_charakter
+    .description = Zeige, welchen Charakter du beansprucht hast
+check = probe
+    .description = Würfle eine Probe für deinen Charakter
+    .first_ability = erste_fähigkeit
+    .first_ability-description = Erste Fähigkeit, auf die du würfeln willst
+    .second_ability = zweite_fähigkeit
+    .second_ability-description = Zweite Fähigkeit, auf die du würfeln willst
+    .target = ziel
+    .target-description = Summe, die für einen Erfolg nötig ist
+    .fate = schicksal
+    .fate-description = Gib einen Schicksalspunkt aus, um den negativen Würfel zu streichen oder neu zu würfeln
+    .mark = markieren
+    .mark-description = Markiere die Fähigkeiten in der laufenden Sitzung für den Aufstieg
+check_character = probe_charakter
+    .description = Würfle eine Probe für einen bestimmten Charakter
+    .character_name = charakter
+    .character_name-description = Charakter, für den du würfeln willst
+    .first_ability = erste_fähigkeit
+    .first_ability-description = Erste Fähigkeit, auf die du würfeln willst
+    .second_ability = zweite_fähigkeit
+    .second_ability-description = Zweite Fähigkeit, auf die du würfeln willst
+    .target = ziel
+    .target-description = Summe, die für einen Erfolg nötig ist
+    .fate = schicksal
+    .fate-description = Gib einen Schicksalspunkt aus, um den negativen Würfel zu streichen oder neu zu würfeln
+    .mark = markieren
+    .mark-description = Markiere die Fähigkeiten in der laufenden Sitzung für den Aufstieg
+plain_checks = proben_als_text
+    .description = Zeige Probenergebnisse als reinen Text statt als Embeds, z.B. für Screenreader
+    .enabled = aktiv
+    .enabled-description = Ob Proben als reiner Text gezeigt werden sollen
+fate = schicksal
+    .description = Vergib, gib aus und zeige die Schicksalspunkte von Charakteren
+fate_show = anzeigen
+    .description = Zeige die Schicksalspunkte eines Charakters und ihre letzten Änderungen, oder die aller Charaktere
+    .character_name = charakter
+    .character_name-description = Charakter, leer lassen für alle Charaktere
+fate_grant = vergeben
+    .description = Vergib Schicksalspunkte an einen Charakter
+    .character_name = charakter
+    .character_name-description = Charakter, der die Punkte bekommt
+    .points = punkte
+    .points-description = Anzahl der Punkte, standardmäßig 1
+fate_spend = ausgeben
+    .description = Gib einen Schicksalspunkt außerhalb einer Probe aus
+    .character_name = charakter
+    .character_name-description = Charakter, der den Punkt ausgibt, standardmäßig dein beanspruchter
+fate_reset = zurücksetzen
+    .description = Setze die Schicksalspunkte eines oder aller Charaktere, z.B. zu Beginn einer Sitzung
+    .points = punkte
+    .points-description = Punkte, mit denen jeder Charakter beginnt
+    .character_name = charakter
+    .character_name-description = Charakter, leer lassen für alle beanspruchten Charaktere
+init = initiative
+    .description = Verfolge die Zugreihenfolge eines Kampfes in diesem Kanal
+init_start = starten
+    .description = Starte einen Kampf in diesem Kanal und würfle Initiative für alle beanspruchten Charaktere
+init_join = beitreten
+    .description = Würfle Initiative für einen Charakter und tritt dem Kampf bei
+    .character_name = charakter
+    .character_name-description = Charakter, der beitritt, standardmäßig dein beanspruchter
+init_npc = nsc
+    .description = Würfle Initiative für einen NSC und füge ihn dem Kampf hinzu
+    .name = name
+    .name-description = Name des NSC
+    .value = wert
+    .value-description = Wert des NSC in der Initiativefähigkeit, leer lassen, um seinen Bogen zu lesen
+init_next = weiter
+    .description = Beende den aktuellen Zug und gib ihn weiter
+init_delay = abwarten
+    .description = Handle erst nach dem nächsten Kämpfer
+    .name = name
+    .name-description = Wer abwartet, standardmäßig wer gerade am Zug ist
+init_remove = entfernen
+    .description = Entferne einen Kämpfer aus dem Kampf
+    .name = name
+    .name-description = Kämpfer, der entfernt wird
+init_show = anzeigen
+    .description = Zeige die Zugreihenfolge des Kampfes in diesem Kanal
+init_end = beenden
+    .description = Beende den Kampf in diesem Kanal
+hp = lp
+    .description = Verfolge die Lebenspunkte von Charakteren und NSCs
+hp_damage = schaden
+    .description = Füge einem Charakter oder NSC Schaden zu
+    .character_name = charakter
+    .character_name-description = Charakter oder NSC, der den Schaden nimmt
+    .amount = menge
+    .amount-description = Schadenspunkte
+hp_heal = heilen
+    .description = Heile einen Charakter oder NSC, höchstens bis zu seinen vollen Lebenspunkten
+    .character_name = charakter
+    .character_name-description = Charakter oder NSC, der geheilt wird
+    .amount = menge
+    .amount-description = Geheilte Punkte
+hp_set = setzen
+    .description = Setze die Lebenspunkte eines Charakters oder NSC, z.B. für NSCs ohne Bogen
+    .character_name = charakter
+    .character_name-description = Charakter oder NSC
+    .max = maximum
+    .max-description = Volle Lebenspunkte
+    .current = aktuell
+    .current-description = Aktuelle Lebenspunkte, standardmäßig die vollen
+condition = zustand
+    .description = Verfolge Zustände wie "betäubt" von Charakteren und NSCs
+condition_add = hinzufügen
+    .description = Gib einem Charakter oder NSC einen Zustand
+    .character_name = charakter
+    .character_name-description = Charakter oder NSC
+    .condition = zustand
+    .condition-description = Zustand, z.B. betäubt
+    .rounds = runden
+    .rounds-description = Runden der Zugreihenfolge, die er anhält, leer lassen bis er entfernt wird
+condition_remove = entfernen
+    .description = Entferne einen Zustand von einem Charakter oder NSC
+    .character_name = charakter
+    .character_name-description = Charakter oder NSC
+    .condition = zustand
+    .condition-description = Zustand, der entfernt wird
+status = status
+    .description = Zeige Lebenspunkte und Zustände der Gruppe und des Kampfes in diesem Kanal
+marks = markierungen
+    .description = Zeige die für den Aufstieg markierten Fähigkeiten eines Charakters
+    .character_name = charakter
+    .character_name-description = Charakter, standardmäßig dein beanspruchter
+advance = aufstieg
+    .description = Lösche die Markierungen eines Charakters und erhöhe die Fähigkeiten auf Wunsch auf dem Bogen
+    .character_name = charakter
+    .character_name-description = Charakter, der aufsteigt
+    .ability = fähigkeit
+    .ability-description = Nur diese Fähigkeit löschen, leer lassen für alle markierten
+    .improve = erhöhen
+    .improve-description = Erhöhe jede gelöschte Fähigkeit auf dem Bogen um eins, standardmäßig nein
+table = zufallstabelle
+    .description = Würfle auf einer Zufallstabelle aus der Tabelle
+    .name = name
+    .name-description = Blatt der Zufallstabelle, oder ein Bereich wie Tabellen!A2:B20
+roll = würfeln
+    .description = Würfle beliebige Würfel, z.B. 2w6+1
+    .dice = würfel
+    .dice-description = Zu würfelnde Würfel, z.B. 2w6+1 oder w4-w4
+help = hilfe
+    .description = Zeige die Hilfe
+    .command = befehl
+    .command-description = Befehl, zu dem du Hilfe willst
+completion = vervollständigung
+    .description = Lass das LLM antworten, mit der letzten Unterhaltung dieses Kanals
+    .message = nachricht
+    .message-description = Nachricht, auf die geantwortet werden soll
+    .persona = persona
+    .persona-description = Persona, die statt der Standardpersona antworten soll
+forget = vergessen
+    .description = Vergiss die Unterhaltung dieses Kanals
+usage = verbrauch
+    .description = Zeige den heutigen Verbrauch von dir und diesem Server
+completion_roles = vervollständigung_rollen
+    .description = Verwalte die Rollen, die Vervollständigungen benutzen dürfen
+completion_roles_allow = erlauben
+    .description = Erlaube einer Rolle, Vervollständigungen zu benutzen
+    .role = rolle
+    .role-description = Rolle, die Vervollständigungen benutzen darf
+completion_roles_disallow = verbieten
+    .description = Nimm einer Rolle die Erlaubnis für Vervollständigungen
+    .role = rolle
+    .role-description = Rolle, die keine Vervollständigungen mehr benutzen darf
+completion_roles_list = liste
+    .description = Zeige die Rollen, die Vervollständigungen benutzen dürfen
+ask_rules = regeln_fragen
+    .description = Schlage eine Frage in den Regeln nach
+    .question = frage
+    .question-description = Deine Frage zu den Regeln
+    .mode = modus
+    .mode-description = Regeln zitieren oder vom LLM beantworten lassen, standardmäßig zitieren
+reindex_rules = regeln_neu_indizieren
+    .description = Lies die Regeldokumente neu in den Index aller Server ein
+narration = erzählung
+    .description = Schalte das Erzählen von Proben durch das LLM an oder aus
+    .enabled = aktiv
+    .enabled-description = Ob Proben erzählt werden sollen
+session = sitzung
+    .description = Zeichne die Würfe und Nachrichten einer Spielsitzung für einen Rückblick auf
+session_start = starten
+    .description = Starte eine Sitzung in diesem Kanal
+    .capture_messages = nachrichten_aufzeichnen
+    .capture_messages-description = Auch die Nachrichten dieses Kanals aufzeichnen, standardmäßig nein
+session_end = beenden
+    .description = Beende die Sitzung in diesem Kanal
+session_recap = rückblick
+    .description = Blicke auf die letzte Sitzung dieses Kanals zurück
+    .mode = modus
+    .mode-description = Würfe auflisten oder vom LLM zusammenfassen lassen, standardmäßig Würfe
+    .export = exportieren
+    .export-description = Den Rückblick als Markdown-Datei schicken
+permissions = berechtigungen
+    .description = Verwalte, wer was darf
+permissions_gm_role = spielleitungsrolle
+    .description = Lege die Rolle der Spielleitung fest oder entferne sie
+    .role = rolle
+    .role-description = Spielleitungsrolle, leer lassen für keine Spielleitung außer Admins
+permissions_open_rolls = offene_würfe
+    .description = Erlaube oder verbiete Spielern, für fremde Charaktere zu würfeln
+    .open = offen
+    .open-description = Ob Spieler für jeden Charakter würfeln dürfen
+permissions_show = anzeigen
+    .description = Zeige die Spielleitungsrolle und deine eigene Stufe
+npc = nsc
+    .description = Erstelle einen NSC aus einem Vorlagenbogen
+    .name = name
+    .name-description = Name des NSC, leer lassen, damit das LLM einen wählt, wenn beschreiben gesetzt ist
+    .describe = beschreiben
+    .describe-description = Lass das LLM Persönlichkeit und Aussehen des NSC beschreiben
+    .save = speichern
+    .save-description = Den NSC als neuen Charakterbogen speichern
+    .template = vorlage
+    .template-description = Vorlagenbogen statt des Standardbogens
+persona = persona
+    .description = Verwalte die Personas, als die das LLM antwortet
+persona_set = festlegen
+    .description = Lege eine Persona an oder ändere sie
+    .name = name
+    .name-description = Name der Persona, z.B. narrator
+    .system_prompt = systemprompt
+    .system_prompt-description = Systemprompt, der dem LLM sagt, wie es sich verhalten soll
+    .model = modell
+    .model-description = Modell statt des Standardmodells
+    .temperature = temperatur
+    .temperature-description = Sampling-Temperatur zwischen 0 und 2
+persona_list = liste
+    .description = Zeige die Personas dieses Servers
+persona_show = anzeigen
+    .description = Zeige die Einstellungen einer Persona
+    .name = name
+    .name-description = Name der Persona
+persona_remove = entfernen
+    .description = Entferne eine Persona
+    .name = name
+    .name-description = Name der Persona
+persona_default = standard
+    .description = Lege die Standardpersona fest oder setze sie auf die eingebaute zurück
+    .name = name
+    .name-description = Name der Persona, leer lassen für die eingebaute
+
+choice-quote = zitieren
+choice-answer = antworten
+choice-rolls = würfe
+choice-summary = zusammenfassung
+choice-drop = streichen
+choice-reroll = neu würfeln
@@ -0,0 +1,223 @@
+# Replies of the bot in English, the fallback for missing translations.
+# The English names and descriptions of commands come from their doc comments.
+
+thinking = thinking…
+reply-attachment-note = The reply is too long for Discord, so here it is as a file.
+
+## Characters and checks
+
+claim-missing-name = Please specify a character name as the first argument
+claim-done = Claimed sheet { $character }
+claim-failed = Failed claiming sheet { $character }
+claim-taken = { $character } is already claimed by another player.
+my-character = Your claimed character is { $character }
+check-result = **{ $character }** rolls { $abilities } + d4({ $pos }) - d4({ $neg }){ $modifier } = **{ $total }**
+check-result-dropped = **{ $character }** rolls { $abilities } + d4({ $pos }){ $modifier } = **{ $total }**
+check-fate-dropped = (negative die dropped with a fate point)
+check-fate-rerolled = (negative die rerolled with a fate point)
+check-success = vs { $target }: **success** by { $margin }
+check-failure = vs { $target }: **failure** by { $margin }
+check-embed-title = { $character } rolls
+check-embed-dice = d4
+check-embed-total = Total
+check-embed-modifier = Modifier
+check-button-reroll = Reroll negative die (fate point)
+check-buttons-not-yours = Only the player who rolled and GMs may reroll this check.
+check-buttons-gm-only = Only GMs may change the modifier of a check.
+check-already-rerolled = The negative die of this check was already rerolled.
+check-change-reroll = negative die rerolled from { $old } to { $new }
+check-change-modifier = modifier { $modifier }
+check-embed-target = Target
+check-embed-success = **Success** by { $margin }
+check-embed-failure = **Failure** by { $margin }
+plain-checks-enabled = Checks will be shown as plain text
+plain-checks-disabled = Checks will be shown as embeds
+
+## Errors
+
+error-no-claimed-character = You have not claimed a character yet!
+error-invalid-character = Character name '{ $name }' does not correspond to a valid sheet in the spreadsheet!
+error-unknown-persona = There is no persona named '{ $name }'.
+error-not-allowed = You do not have a role that may use this command.
+error-quota-user-requests = The daily request quota of your account is used up. Please try again tomorrow.
+error-quota-user-tokens = The daily token quota of your account is used up. Please try again tomorrow.
+error-quota-guild-requests = The daily request quota of this server is used up. Please try again tomorrow.
+error-quota-guild-tokens = The daily token quota of this server is used up. Please try again tomorrow.
+error-content-blocked = The prompt or its answer contains blocked content.
+error-missing-level-gm = Only a GM may do that.
+error-missing-level-admin = Only an admin may do that.
+error-not-your-character = You may only roll for the character you claimed, not for { $name }.
+error-no-ability = No ability '{ $ability }' was found on the character sheet.
+error-ambiguous-ability = '{ $ability }' is ambiguous, it matches { $found }. Please be more specific.
+error-sheet-value = The character sheet contains a value that could not be read.
+error-sheets-unreachable = The character spreadsheet could not be reached. Please try again later.
+error-db = The bot's database is not available right now.
+error-llm = The completion failed.
+error-llm-timeout = The completion took too long and was cancelled. Please try again.
+error-internal = Something went wrong on the bot's side.
+
+## Language
+
+language-user-set = I will answer you in English.
+language-server-set = I will answer in English on this server.
+
+## Fate points
+
+fate-points = { $character } has { $points ->
+        [one] { $points } fate point
+       *[other] { $points } fate points
+    }
+fate-none-yet = No character has fate points yet.
+fate-none-left = { $character } has no fate points left.
+fate-invalid-grant = Please grant at least one fate point.
+fate-invalid-reset = Fate points cannot be negative.
+fate-reset-all = Every character has { $points ->
+        [one] { $points } fate point
+       *[other] { $points } fate points
+    } now
+fate-reason-grant = granted
+fate-reason-spend = spent
+fate-reason-check = spent on a check
+fate-reason-reset = reset
+
+## Initiative
+
+init-round = Round { $round }
+init-empty = Nobody has joined the fight yet.
+init-not-running = There is no fight in this channel.
+init-running = A fight is already running in this channel.
+init-unknown = { $name } is not part of the fight.
+init-cannot-delay = { $name } cannot delay, nobody acts after them this round.
+init-changed = The order of the fight changed in the meantime, please try again.
+init-skipped = Could not roll initiative for { $names }
+init-ended = The fight is over.
+
+## Hit points and conditions
+
+hp-status = { $character }: { $current }/{ $max } HP
+hp-down = ({ $character } is down!)
+condition-rounds = { $condition } ({ $rounds ->
+        [one] { $rounds } round
+       *[other] { $rounds } rounds
+    })
+condition-added = { $character } is now { $condition }.
+condition-removed = { $character } is no longer { $condition }.
+condition-unknown = { $character } is not { $condition }.
+condition-invalid-rounds = A condition has to last at least one round.
+condition-expired = Ran out: { $conditions }
+status-title = Party status
+status-empty = Nobody has claimed a character or hit points yet.
+status-hp = ❤️ { $current }/{ $max }
+status-hp-unknown = ❤️ ?
+
+## Experience
+
+marks-no-session = Abilities can only be marked in a running session.
+marks-none = { $character } has no marked abilities.
+marks-heading = Marked abilities of **{ $character }**:
+marks-entry = { $ability } ({ $sessions ->
+        [one] { $sessions } session
+       *[other] { $sessions } sessions
+    }, { $checks ->
+        [one] { $checks } check
+       *[other] { $checks } checks
+    })
+marks-ambiguous = Several marked abilities match { $ability }: { $names }
+marks-cleared = Cleared the marks of { $character }: { $abilities }
+marks-improved = { $ability }: { $old } → { $new }
+
+## Random tables
+
+table-result = 🎲 **{ $name }**: { $entry }
+table-result-dice = 🎲 **{ $name }** ({ $result }): { $entry }
+table-unknown = There is no table { $name } in the spreadsheet.
+table-empty = The table { $name } has no entries with a weight or a die result.
+table-invalid = The weights or die results of the table { $name } are out of range.
+table-no-entry = The table { $name } has no entry for { $result }.
+table-too-deep = The tables refer to each other too deeply, stopped at { $name }.
+
+## Free rolls
+
+roll-result = 🎲 **{ $dice }**: { $rolls } = **{ $total }**
+roll-invalid = Cannot roll { $dice }. Write dice like 2d6+1 or d4-d4, with at most { $max } dice.
+
+## Completions and quotas
+
+forget-done = Forgot { $count ->
+        [one] { $count } message
+       *[other] { $count } messages
+    } of this channel
+usage-unlimited = unlimited
+usage-line = { $requests } of { $request_limit } requests, { $tokens } of { $token_limit } tokens
+usage-summary =
+    Completions used today
+    You: { $user }
+    This server: { $server }
+completion-role-allowed = { $role } may use completions
+completion-role-disallowed = { $role } may no longer use completions
+completion-role-not-allowed = { $role } was not allowed to use completions.
+completion-roles-everyone = Everyone may use completions
+completion-roles-list = Roles that may use completions: { $roles }
+
+## Personas and narration
+
+persona-invalid-temperature = The temperature must be between 0 and 2.
+persona-stored = Stored persona { $name }
+persona-none = There are no personas yet.
+persona-default-marker = { $name } (default)
+persona-settings = **{ $name }** (model { $model }, temperature { $temperature })
+persona-removed = Removed persona { $name }
+persona-default-set = Default persona is now { $name }
+persona-default-reset = Using the built-in default persona
+narration-enabled = Checks will be narrated
+narration-disabled = Checks will not be narrated
+
+## Permissions
+
+level-player = player
+level-gm = GM
+level-admin = admin
+gm-role-set = Members of { $role } are GMs now
+gm-role-removed = There is no GM role anymore
+open-rolls-enabled = Players may roll for any character
+open-rolls-disabled = Players may only roll for the characters they claimed
+permissions-no-gm-role = none
+permissions-rolls-any = any character
+permissions-rolls-claimed = claimed characters only
+permissions-show =
+    GM role: { $gm_role }
+    Players roll for: { $rolls }
+    Your level: { $level }
+
+## Rules
+
+rules-question-without-words = Please ask a question with some words in it.
+rules-nothing-found = I could not find anything about that in the rules.
+rules-sources = Sources: { $sources }
+rules-indexed = Indexed { $count } rule passages
+
+## NPCs
+
+npc-empty-template = The sheet '{ $sheet }' has no abilities with value ranges in column G.
+npc-unnamed = Unnamed NPC
+npc-needs-name = Please give the NPC a name or let the LLM describe it to save it.
+npc-sheet-exists = There already is a sheet named '{ $name }'.
+npc-saved = Saved as sheet { $name }, roll for it with `/check_character { $name }`
+
+## Sessions
+
+session-now = now
+session-window = { $start } – { $end } UTC
+session-recap-title = Session recap
+session-rolls-heading = Rolls
+session-no-rolls = No rolls were made.
+session-running = A session is already running in this channel.
+session-not-running = No session is running in this channel.
+session-none = There was no session in this channel yet.
+session-empty = Nothing was recorded in this session.
+session-started = Session started, recording the rolls of this channel
+session-started-messages = Session started, recording the rolls and messages of this channel
+session-ended = Session ended ({ $window }) with { $count ->
+        [one] { $count } roll
+       *[other] { $count } rolls
+    }. Use `/session recap` for a recap.